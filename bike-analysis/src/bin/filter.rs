@@ -1,19 +1,26 @@
 use anyhow::Context;
 use bike_analysis::{
+    filter::{filter_by_source, IndexConvention, SourceFilter},
     output::write_json,
     record::{AnalysisResult, DecodingFailureAnalysis},
+    stream::{decompressed, process_json_array},
 };
 use bike_decoder::{
     decoder::DecodingFailure,
+    keys::Key,
+    ncw::NcwOverlapsBatch,
     parameters::{BLOCK_LENGTH, BLOCK_WEIGHT, ERROR_WEIGHT},
+    vectors::Index,
 };
 use clap::Parser;
 use rayon::prelude::*;
 use serde::Deserialize;
 use serde_json::Deserializer;
-use std::{io, time::Instant};
+use std::{collections::HashMap, io, time::Instant};
 
 type AnalysisRecord = bike_analysis::record::AnalysisRecord<BLOCK_WEIGHT, BLOCK_LENGTH>;
+type PatternBatch = NcwOverlapsBatch<BLOCK_WEIGHT, BLOCK_LENGTH>;
+type KeySupports = ([Index; BLOCK_WEIGHT], [Index; BLOCK_WEIGHT]);
 
 #[derive(Debug, Parser)]
 #[command(about = "Filters and analyzes decoding failures received on stdin", long_about = None)]
@@ -27,51 +34,144 @@ struct Cli {
         help = "Maximum number of iterations to search for cycles"
     )]
     iters: usize,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        help = "Order in which the input indexes the coefficients of each block"
+    )]
+    index_convention: IndexConvention,
     #[arg(long, help = "Classify e_in - e_out into near-codeword sets")]
     ncw: bool,
     #[arg(long, help = "Classify e_in into near-codeword sets")]
     ncw_in: bool,
     #[arg(short, long, help = "Run in parallel using multiple threads")]
     parallel: bool,
+    #[arg(
+        long,
+        help = "Only keep decoding failures with error vectors from this source"
+    )]
+    source: Option<SourceFilter>,
+    #[arg(
+        long,
+        conflicts_with = "parallel",
+        help = "Process input one decoding failure at a time, writing results as a JSON array"
+    )]
+    stream: bool,
+    #[arg(
+        long,
+        help = "Record the syndrome weight after each step of the decoder"
+    )]
+    trajectory: bool,
 }
 
 fn decoding_failures_from_stdin() -> Result<Vec<DecodingFailure>, anyhow::Error> {
-    let mut de = Deserializer::from_reader(io::stdin());
+    let reader = decompressed(io::stdin().lock()).context("Failed to read from stdin")?;
+    let mut de = Deserializer::from_reader(reader);
     <Vec<DecodingFailure>>::deserialize(&mut de)
         .context("Failed to parse JSON input as Vec<DecodingFailure>")
 }
 
-fn run(cli: Cli, decoding_failures: Vec<DecodingFailure>) -> AnalysisRecord {
+// Identifies keys up to the order of their supports
+fn key_supports(key: &Key) -> KeySupports {
+    let key = key.clone().sorted();
+    (*key.h0().support(), *key.h1().support())
+}
+
+// Precomputes the near-codeword patterns of each distinct key, so they're computed only once
+// per key rather than once per decoding failure.
+fn pattern_batches(
+    results: &[DecodingFailureAnalysis],
+    parallel: bool,
+) -> HashMap<KeySupports, PatternBatch> {
+    let keys: HashMap<KeySupports, &Key> = results
+        .iter()
+        .map(|df_analysis| (key_supports(df_analysis.key()), df_analysis.key()))
+        .collect();
+    if parallel {
+        keys.into_par_iter()
+            .map(|(supports, key)| (supports, PatternBatch::new(key)))
+            .collect()
+    } else {
+        keys.into_iter()
+            .map(|(supports, key)| (supports, PatternBatch::new(key)))
+            .collect()
+    }
+}
+
+// Analyzes a decoding failure, using the precomputed near-codeword patterns of its key (which
+// are only needed with --ncw or --ncw-in).
+fn analyze(cli: &Cli, df_analysis: &mut DecodingFailureAnalysis, batch: Option<&PatternBatch>) {
+    if let Some(batch) = batch {
+        if cli.ncw {
+            df_analysis.compute_overlaps_diff_with(batch);
+        }
+        if cli.ncw_in {
+            df_analysis.compute_overlaps_e_in_with(batch);
+        }
+    }
+    if cli.absorbing {
+        df_analysis.compute_absorbing();
+    }
+    if cli.trajectory {
+        df_analysis.compute_syndrome_weight_trajectory();
+    }
+}
+
+// Classifies decoding failures one at a time as they're read from stdin, so that memory
+// usage stays flat for large inputs.
+fn run_streaming(cli: Cli) -> Result<(), anyhow::Error> {
+    let stdout = io::BufWriter::new(io::stdout().lock());
+    let reader = decompressed(io::stdin().lock()).context("Failed to read from stdin")?;
+    // Patterns for the most recent key, which are reused as long as the key stays the same
+    let mut cached_batch: Option<(KeySupports, PatternBatch)> = None;
+    process_json_array(reader, stdout, |df: DecodingFailure| {
+        let df = cli.index_convention.apply(df);
+        if let Some(source) = cli.source {
+            if !source.matches(df.vector().source()) {
+                return None;
+            }
+        }
+        let mut df_analysis = DecodingFailureAnalysis::new(&df, cli.iters);
+        if cli.ncw || cli.ncw_in {
+            let supports = key_supports(df.key());
+            if cached_batch.as_ref().map(|(cached, _)| cached) != Some(&supports) {
+                cached_batch = Some((supports, PatternBatch::new(df.key())));
+            }
+        }
+        analyze(
+            &cli,
+            &mut df_analysis,
+            cached_batch.as_ref().map(|(_, batch)| batch),
+        );
+        Some(df_analysis)
+    })?;
+    Ok(())
+}
+
+fn run(cli: Cli, mut decoding_failures: Vec<DecodingFailure>) -> AnalysisRecord {
+    if let Some(source) = cli.source {
+        filter_by_source(&mut decoding_failures, source);
+    }
     let count = decoding_failures.len();
     let start_time = Instant::now();
     let mut results = decoding_failures
         .iter()
         .map(|df| DecodingFailureAnalysis::new(df, cli.iters))
         .collect::<Vec<_>>();
+    let batches = if cli.ncw || cli.ncw_in {
+        pattern_batches(&results, cli.parallel)
+    } else {
+        HashMap::new()
+    };
+    let analyze_with_batch = |df_analysis: &mut DecodingFailureAnalysis| {
+        let batch = batches.get(&key_supports(df_analysis.key()));
+        analyze(&cli, df_analysis, batch);
+    };
     if cli.parallel {
-        results.par_iter_mut().for_each(|df_analysis| {
-            if cli.ncw {
-                df_analysis.compute_overlaps_diff();
-            }
-            if cli.ncw_in {
-                df_analysis.compute_overlaps_e_in();
-            }
-            if cli.absorbing {
-                df_analysis.compute_absorbing();
-            }
-        });
+        results.par_iter_mut().for_each(analyze_with_batch);
     } else {
-        results.iter_mut().for_each(|df_analysis| {
-            if cli.ncw {
-                df_analysis.compute_overlaps_diff();
-            }
-            if cli.ncw_in {
-                df_analysis.compute_overlaps_e_in();
-            }
-            if cli.absorbing {
-                df_analysis.compute_absorbing();
-            }
-        });
+        results.iter_mut().for_each(analyze_with_batch);
     }
     let results = results
         .into_iter()
@@ -82,7 +182,13 @@ fn run(cli: Cli, decoding_failures: Vec<DecodingFailure>) -> AnalysisRecord {
 
 fn main() -> Result<(), anyhow::Error> {
     let cli = Cli::parse();
-    let decoding_failures = decoding_failures_from_stdin()?;
+    if cli.stream {
+        return run_streaming(cli);
+    }
+    let decoding_failures = decoding_failures_from_stdin()?
+        .into_iter()
+        .map(|df| cli.index_convention.apply(df))
+        .collect();
     let record = run(cli, decoding_failures);
     write_json(&record)
 }
@@ -1,11 +1,12 @@
 use anyhow::{anyhow, Context};
 use bike_analysis::{
+    enumerate::{enumerate_filtered, par_combinations, DEFAULT_CHUNK_SIZE},
     output::write_json,
-    record::{AnalysisResult, SampleAnalysis},
+    record::{AnalysisResult, OverlapHistogram, SampleAnalysis},
 };
 use bike_decoder::{
-    env_or_usize, graphs::TannerGraphEdges, keys::QuasiCyclic, random::custom_thread_rng,
-    vectors::Index,
+    env_or_usize, graphs::TannerGraphEdges, keys::QuasiCyclic, ncw::NcwOverlaps,
+    random::custom_thread_rng, vectors::Index,
 };
 use clap::{ArgGroup, Parser};
 use itertools::Itertools;
@@ -43,8 +44,21 @@ struct Cli {
     absorbing: bool,
     #[arg(long, help = "Classify vectors in near-codeword sets")]
     ncw: bool,
+    #[arg(
+        long,
+        requires = "ncw",
+        conflicts_with = "absorbing",
+        help = "Output only the distribution of near-codeword overlaps"
+    )]
+    histogram: bool,
     #[arg(short, long, help = "Run in parallel using multiple threads")]
     parallel: bool,
+    #[arg(
+        long,
+        default_value_t = DEFAULT_CHUNK_SIZE,
+        help = "Number of candidates per task when enumerating in parallel"
+    )]
+    chunk_size: usize,
     #[arg(short, long, help = "Weight of absorbing sets")]
     weight: usize,
 }
@@ -54,8 +68,10 @@ struct Settings {
     key: SampleKey,
     sample_method: SampleMethod,
     ncw: bool,
+    histogram: bool,
     absorbing: bool,
     parallel: bool,
+    chunk_size: usize,
     weight: usize,
 }
 
@@ -66,8 +82,10 @@ impl TryFrom<Cli> for Settings {
             key: parse_key_or_random(cli.key.as_deref())?,
             sample_method: SampleMethod::new(cli.number, cli.enumerate)?,
             ncw: cli.ncw,
+            histogram: cli.histogram,
             absorbing: cli.absorbing,
             parallel: cli.parallel,
+            chunk_size: cli.chunk_size,
             weight: cli.weight,
         };
         if let SampleMethod::Sample(samples) = settings.sample_method {
@@ -147,22 +165,59 @@ fn enumerate_set<const WT: usize, const LEN: usize>(
     key: &QuasiCyclic<WT, LEN>,
     weight: usize,
     parallel: bool,
+    chunk_size: usize,
 ) -> Vec<SampleAnalysis<WT, LEN>> {
     let edges = TannerGraphEdges::new(key);
     let n = 2 * LEN as Index;
-    let combinations = (0..n).combinations(weight);
+    let supports = enumerate_filtered(n, weight, chunk_size, parallel, |_| true);
     if parallel {
-        combinations
-            .par_bridge()
+        supports
+            .into_par_iter()
             .map(|supp| SampleAnalysis::with_edges(key.clone(), supp, edges.clone()))
             .collect()
     } else {
-        combinations
+        supports
+            .into_iter()
             .map(|supp| SampleAnalysis::with_edges(key.clone(), supp, edges.clone()))
             .collect()
     }
 }
 
+// Computes the distribution of near-codeword overlaps without storing the individual
+// vectors, since there may be a very large number of them.
+fn overlap_histogram<const WT: usize, const LEN: usize>(
+    key: &QuasiCyclic<WT, LEN>,
+    weight: usize,
+    sample_method: SampleMethod,
+    parallel: bool,
+    chunk_size: usize,
+) -> OverlapHistogram {
+    let n = 2 * LEN as Index;
+    let overlaps = |supp: Vec<Index>| NcwOverlaps::new(key, &supp);
+    let add = |mut histogram: OverlapHistogram, overlaps: NcwOverlaps| {
+        histogram.add(&overlaps);
+        histogram
+    };
+    match (sample_method, parallel) {
+        (SampleMethod::Sample(num_samples), true) => (0..num_samples)
+            .into_par_iter()
+            .map(|_| (0..n).choose_multiple(&mut custom_thread_rng(), weight))
+            .map(overlaps)
+            .fold(OverlapHistogram::default, add)
+            .reduce(OverlapHistogram::default, OverlapHistogram::merge),
+        (SampleMethod::Sample(num_samples), false) => (0..num_samples)
+            .map(|_| (0..n).choose_multiple(&mut custom_thread_rng(), weight))
+            .map(overlaps)
+            .collect(),
+        (SampleMethod::Enumerate, true) => par_combinations(n, weight, chunk_size)
+            .flat_map_iter(|chunk| chunk)
+            .map(overlaps)
+            .fold(OverlapHistogram::default, add)
+            .reduce(OverlapHistogram::default, OverlapHistogram::merge),
+        (SampleMethod::Enumerate, false) => (0..n).combinations(weight).map(overlaps).collect(),
+    }
+}
+
 fn run(settings: Settings) -> AnalysisRecord {
     let start_time = Instant::now();
     let num_processed = settings.count();
@@ -171,12 +226,25 @@ fn run(settings: Settings) -> AnalysisRecord {
         sample_method,
         weight,
         ncw,
+        histogram,
         absorbing,
         parallel,
+        chunk_size,
     } = settings;
+    if histogram {
+        let histogram = overlap_histogram(&key, weight, sample_method, parallel, chunk_size);
+        return AnalysisRecord::new(
+            Some(key),
+            weight,
+            num_processed,
+            Vec::new(),
+            start_time.elapsed(),
+        )
+        .with_ncw_histogram(histogram);
+    }
     let mut results = match sample_method {
         SampleMethod::Sample(num_samples) => sample_set(&key, weight, num_samples, parallel),
-        SampleMethod::Enumerate => enumerate_set(&key, weight, parallel),
+        SampleMethod::Enumerate => enumerate_set(&key, weight, parallel, chunk_size),
     };
     if parallel {
         results.par_iter_mut().for_each(|sample| {
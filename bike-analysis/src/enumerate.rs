@@ -0,0 +1,81 @@
+use bike_decoder::{
+    graphs::{binomial, combinations_from},
+    vectors::Index,
+};
+use rayon::prelude::*;
+
+/// Default number of candidate supports handled by each parallel task.
+pub const DEFAULT_CHUNK_SIZE: usize = 4096;
+
+/// Parallel iterator over all subsets of `0..n` of size `weight`. The subsets are split by
+/// lexicographic rank into chunks of `chunk_size` consecutive subsets, and each chunk is
+/// unranked and enumerated independently, so the work is spread evenly across threads.
+/// Collecting the iterator yields the subsets in lexicographic order.
+pub fn par_combinations(
+    n: Index,
+    weight: usize,
+    chunk_size: usize,
+) -> impl IndexedParallelIterator<Item = impl Iterator<Item = Vec<Index>>> {
+    let chunk_size = chunk_size.max(1) as u128;
+    let total = binomial(u128::from(n), weight as u128);
+    // u128::div_ceil requires Rust 1.73
+    #[allow(clippy::manual_div_ceil)]
+    let num_chunks = usize::try_from((total + chunk_size - 1) / chunk_size)
+        .expect("number of chunks should fit in a usize");
+    (0..num_chunks)
+        .into_par_iter()
+        .map(move |chunk| combinations_from(n, weight, chunk as u128 * chunk_size, chunk_size))
+}
+
+/// Enumerates all subsets of `0..n` of size `weight` satisfying `predicate`, sorted
+/// lexicographically. If `parallel` is set, the enumeration is split into chunks of
+/// `chunk_size` subsets as in `par_combinations`; the output doesn't depend on `parallel`,
+/// `chunk_size`, or the number of threads.
+pub fn enumerate_filtered<F>(
+    n: Index,
+    weight: usize,
+    chunk_size: usize,
+    parallel: bool,
+    predicate: F,
+) -> Vec<Vec<Index>>
+where
+    F: Fn(&[Index]) -> bool + Sync,
+{
+    let mut supports: Vec<Vec<Index>> = if parallel {
+        par_combinations(n, weight, chunk_size)
+            .flat_map_iter(|chunk| chunk.filter(|supp| predicate(supp)))
+            .collect()
+    } else {
+        combinations_from(n, weight, 0, u128::MAX)
+            .filter(|supp| predicate(supp))
+            .collect()
+    };
+    supports.par_sort_unstable();
+    supports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bike_decoder::{
+        graphs::{enumerate_absorbing_sets, is_absorbing_subgraph, TannerGraphEdges},
+        keys::QuasiCyclic,
+    };
+
+    #[test]
+    fn parallel_matches_sequential() {
+        let key = QuasiCyclic::<2, 7>::from_support([0, 1], [0, 2]).unwrap();
+        let edges = TannerGraphEdges::new(&key);
+        let is_absorbing = |supp: &[Index]| is_absorbing_subgraph(&edges, supp);
+        let sequential = enumerate_filtered(14, 4, DEFAULT_CHUNK_SIZE, false, is_absorbing);
+        assert!(!sequential.is_empty());
+        assert_eq!(sequential, enumerate_absorbing_sets(&key, 4));
+        for chunk_size in [1, 7, 100, 5000] {
+            let parallel = enumerate_filtered(14, 4, chunk_size, true, is_absorbing);
+            assert_eq!(parallel, sequential);
+        }
+        let all = enumerate_filtered(14, 4, 10, true, |_| true);
+        assert_eq!(all.len() as u128, binomial(14, 4));
+        assert!(all.windows(2).all(|w| w[0] < w[1]));
+    }
+}
@@ -0,0 +1,126 @@
+use bike_decoder::{decoder::DecodingFailure, ncw::ErrorVectorSource};
+use clap::ValueEnum;
+
+/// Selects decoding failures according to how their error vectors were generated.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum SourceFilter {
+    /// Uniformly random error vectors
+    Random,
+    /// Error vectors sampled from near-codeword sets A_{t,l}(S)
+    Ncw,
+    /// Error vectors from any other or unknown source
+    Other,
+}
+
+impl SourceFilter {
+    pub fn matches(&self, source: &ErrorVectorSource) -> bool {
+        match self {
+            Self::Random => matches!(source, ErrorVectorSource::Random),
+            Self::Ncw => matches!(source, ErrorVectorSource::NearCodeword(_)),
+            Self::Other => matches!(
+                source,
+                ErrorVectorSource::Other | ErrorVectorSource::Unknown
+            ),
+        }
+    }
+}
+
+/// Keeps only the decoding failures whose error vector source matches the filter.
+pub fn filter_by_source(decoding_failures: &mut Vec<DecodingFailure>, filter: SourceFilter) {
+    decoding_failures.retain(|df| filter.matches(df.vector().source()));
+}
+
+/// Order in which the input indexes the coefficients of each circulant block.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum IndexConvention {
+    /// Index `i` is the coefficient of `x^i`, as in the output of `bike-trials`
+    #[default]
+    Forward,
+    /// Index `i` is the coefficient of `x^(r - 1 - i)`
+    Reverse,
+}
+
+impl IndexConvention {
+    /// Converts a decoding failure read in this index convention to the forward convention
+    /// used by the decoder. Both blocks of the key and both halves of the error vector are
+    /// converted.
+    pub fn apply(&self, df: DecodingFailure) -> DecodingFailure {
+        match self {
+            Self::Forward => df,
+            Self::Reverse => df.reversed(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bike_decoder::{parameters::BLOCK_LENGTH, vectors::Index};
+    use serde_json::{json, Value};
+
+    fn decoding_failure_with_source(source: Value) -> DecodingFailure {
+        let mut df = json!({
+            "h0": [11, 21, 100, 124, 229, 271, 284, 307, 380, 397, 420, 438, 445, 495, 555],
+            "h1": [10, 41, 50, 59, 62, 119, 153, 164, 179, 208, 284, 384, 438, 513, 554],
+            "e_supp": [42, 187, 189, 336, 409, 445, 464, 485, 524, 532, 617, 804, 877, 892,
+                1085, 1099, 1117, 1150],
+            "thread": null
+        });
+        if !source.is_null() {
+            df["e_source"] = source;
+        }
+        serde_json::from_value(df).unwrap()
+    }
+
+    #[test]
+    fn filter_mixed_sources() {
+        let sources = [
+            json!("Random"),
+            json!({"NearCodeword": {"class": "N", "l": 15, "delta": 2}}),
+            json!("Other"),
+            json!("Random"),
+            Value::Null,
+            json!({"NearCodeword": {"class": "2N", "l": 9, "delta": 0}}),
+        ];
+        let decoding_failures: Vec<DecodingFailure> = sources
+            .into_iter()
+            .map(decoding_failure_with_source)
+            .collect();
+        for (filter, expected_count) in [
+            (SourceFilter::Random, 2),
+            (SourceFilter::Ncw, 2),
+            (SourceFilter::Other, 2),
+        ] {
+            let mut filtered = decoding_failures.clone();
+            filter_by_source(&mut filtered, filter);
+            assert_eq!(filtered.len(), expected_count);
+            assert!(filtered
+                .iter()
+                .all(|df| filter.matches(df.vector().source())));
+        }
+        let mut filtered = decoding_failures;
+        filter_by_source(&mut filtered, SourceFilter::Ncw);
+        assert!(matches!(
+            filtered[0].vector().source(),
+            ErrorVectorSource::NearCodeword(set) if set.l() == 15
+        ));
+    }
+
+    #[test]
+    fn reverse_convention_involution() {
+        let df = decoding_failure_with_source(json!("Random"));
+        let reversed = IndexConvention::Reverse.apply(df.clone());
+        assert_ne!(reversed.key(), df.key());
+        let r = BLOCK_LENGTH as Index;
+        assert_eq!(reversed.key().h0().get(0), r - 1 - 11);
+        assert_eq!(reversed.vector().vector().get(0), r - 1 - 42);
+        assert_eq!(reversed.vector().vector().get(10), r + (r - 1 - (617 - r)));
+        assert_eq!(reversed.vector().source(), df.vector().source());
+        let twice = IndexConvention::Reverse.apply(reversed);
+        assert_eq!(twice.key(), df.key());
+        assert_eq!(twice.vector(), df.vector());
+        let forward = IndexConvention::Forward.apply(df.clone());
+        assert_eq!(forward.key(), df.key());
+        assert_eq!(forward.vector(), df.vector());
+    }
+}
@@ -1,2 +1,5 @@
+pub mod enumerate;
+pub mod filter;
 pub mod output;
 pub mod record;
+pub mod stream;
@@ -1,19 +1,20 @@
 use bike_decoder::{
-    decoder::{find_bgf_cycle, DecoderCycle, DecodingFailure, DecodingResult},
+    decoder::{bgf_decoder_traced, find_bgf_cycle, DecoderCycle, DecodingFailure, DecodingResult},
     graphs::{
         is_absorbing_subgraph, odd_check_node_neighbors, AbsorbingDecodingResult, CheckNode,
         TannerGraphEdges,
     },
-    keys::QuasiCyclic,
-    ncw::NcwOverlaps,
+    keys::{Key, QuasiCyclic},
+    ncw::{NcwOverlaps, NcwOverlapsBatch},
     parameters::{BLOCK_LENGTH, BLOCK_WEIGHT, GRAY_THRESHOLD_DIFF},
     random::{global_seed, Seed},
+    syndrome::Syndrome,
     threshold::{bf_masked_threshold, bf_threshold_min},
     vectors::Index,
 };
 use serde::{Deserialize, Serialize};
 use serde_with::{formats::Flexible, serde_as, skip_serializing_none, DurationSecondsWithFrac};
-use std::time::Duration;
+use std::{collections::BTreeMap, time::Duration};
 
 #[serde_as]
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -27,6 +28,8 @@ pub struct AnalysisRecord<const WT: usize, const LEN: usize> {
     fixed_key: Option<QuasiCyclic<WT, LEN>>,
     num_processed: usize,
     data: Vec<AnalysisResult<WT, LEN>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ncw_histogram: Option<OverlapHistogram>,
     seed: Option<Seed>,
     #[serde_as(as = "DurationSecondsWithFrac<f64, Flexible>")]
     runtime: Duration,
@@ -50,10 +53,64 @@ impl<const WT: usize, const LEN: usize> AnalysisRecord<WT, LEN> {
             fixed_key,
             num_processed,
             data,
+            ncw_histogram: None,
             seed: global_seed(),
             runtime,
         }
     }
+
+    /// Attaches a histogram of near-codeword overlaps, used in place of per-vector data.
+    pub fn with_ncw_histogram(mut self, histogram: OverlapHistogram) -> Self {
+        self.ncw_histogram = Some(histogram);
+        self
+    }
+}
+
+/// Distribution of the near-codeword overlaps `c`, `n`, and `2n` over a collection of
+/// vectors, stored as maps from overlap value to count.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OverlapHistogram {
+    pub c: BTreeMap<usize, usize>,
+    pub n: BTreeMap<usize, usize>,
+    #[serde(rename = "2n")]
+    pub two_n: BTreeMap<usize, usize>,
+}
+
+impl OverlapHistogram {
+    pub fn add(&mut self, overlaps: &NcwOverlaps) {
+        *self.c.entry(overlaps.c).or_default() += 1;
+        *self.n.entry(overlaps.n).or_default() += 1;
+        *self.two_n.entry(overlaps.two_n).or_default() += 1;
+    }
+
+    /// Combines the counts of two histograms, e.g. when computed in parallel.
+    pub fn merge(mut self, other: Self) -> Self {
+        for (hist, other_hist) in [
+            (&mut self.c, other.c),
+            (&mut self.n, other.n),
+            (&mut self.two_n, other.two_n),
+        ] {
+            for (value, count) in other_hist {
+                *hist.entry(value).or_default() += count;
+            }
+        }
+        self
+    }
+
+    /// Number of vectors counted in the histogram.
+    pub fn total(&self) -> usize {
+        self.c.values().sum()
+    }
+}
+
+impl FromIterator<NcwOverlaps> for OverlapHistogram {
+    fn from_iter<I: IntoIterator<Item = NcwOverlaps>>(iter: I) -> Self {
+        let mut histogram = Self::default();
+        for overlaps in iter {
+            histogram.add(&overlaps);
+        }
+        histogram
+    }
 }
 
 #[allow(clippy::large_enum_variant)]
@@ -70,6 +127,8 @@ pub struct DecodingFailureAnalysis {
     decoder_cycle: DecoderCycle,
     diff: RawSupportAnalysis,
     e_in_data: RawSupportAnalysis,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    syndrome_weight_trajectory: Option<Vec<usize>>,
 }
 
 impl DecodingFailureAnalysis {
@@ -81,9 +140,25 @@ impl DecodingFailureAnalysis {
             decoder_cycle,
             diff,
             e_in_data,
+            syndrome_weight_trajectory: None,
         }
     }
 
+    /// Records the syndrome weight after each step of the BGF decoder with `NB_ITER`
+    /// iterations, as returned by `bgf_decoder_traced`.
+    pub fn compute_syndrome_weight_trajectory(&mut self) {
+        self.syndrome_weight_trajectory.get_or_insert_with(|| {
+            let key = self.decoder_cycle.key();
+            let mut s = Syndrome::from_sparse(key, self.decoder_cycle.e_in());
+            bgf_decoder_traced(key, &mut s).2
+        });
+    }
+
+    #[inline]
+    pub fn syndrome_weight_trajectory(&self) -> Option<&[usize]> {
+        self.syndrome_weight_trajectory.as_deref()
+    }
+
     pub fn compute_overlaps_diff(&mut self) {
         self.diff
             .ncw_overlaps
@@ -96,6 +171,33 @@ impl DecodingFailureAnalysis {
         });
     }
 
+    /// Same as `compute_overlaps_diff`, but uses near-codeword patterns precomputed from
+    /// the key of the decoding failure.
+    pub fn compute_overlaps_diff_with(
+        &mut self,
+        batch: &NcwOverlapsBatch<BLOCK_WEIGHT, BLOCK_LENGTH>,
+    ) {
+        self.diff
+            .ncw_overlaps
+            .get_or_insert_with(|| batch.overlaps_of(&self.diff.supp));
+    }
+
+    /// Same as `compute_overlaps_e_in`, but uses near-codeword patterns precomputed from
+    /// the key of the decoding failure.
+    pub fn compute_overlaps_e_in_with(
+        &mut self,
+        batch: &NcwOverlapsBatch<BLOCK_WEIGHT, BLOCK_LENGTH>,
+    ) {
+        self.e_in_data
+            .ncw_overlaps
+            .get_or_insert_with(|| batch.overlaps_of(&self.e_in_data.supp));
+    }
+
+    #[inline]
+    pub fn key(&self) -> &Key {
+        self.decoder_cycle.key()
+    }
+
     pub fn compute_absorbing(&mut self) {
         if self.diff.is_absorbing.is_some() {
             return;
@@ -210,6 +312,7 @@ pub struct AbsorbingParameters {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use bike_decoder::{decoder::bgf_decoder, parameters::NB_ITER, vectors::SparseVector};
     use serde_json::json;
 
     #[test]
@@ -224,4 +327,52 @@ mod tests {
         let data: RawSupportAnalysis = serde_json::from_value(value.clone()).unwrap();
         assert_eq!(serde_json::to_value(data).unwrap(), value);
     }
+
+    #[test]
+    fn syndrome_weight_trajectory() {
+        let df: DecodingFailure = serde_json::from_value(json!({
+            "h0": [93, 99, 105, 121, 126, 141, 156, 193, 194, 197, 264, 301, 360, 400, 429],
+            "h1": [100, 117, 189, 191, 211, 325, 340, 386, 440, 461, 465, 474, 534, 565, 578],
+            "e_supp": [
+                16, 73, 89, 201, 346, 522, 547, 553, 574, 575, 613, 619, 637, 713, 955, 960,
+                983, 1008
+            ]
+        }))
+        .unwrap();
+        let mut analysis = DecodingFailureAnalysis::new(&df, 100);
+        assert!(analysis.syndrome_weight_trajectory().is_none());
+        analysis.compute_syndrome_weight_trajectory();
+        let trajectory = analysis.syndrome_weight_trajectory().unwrap().to_vec();
+        assert!(trajectory.len() <= NB_ITER + 2);
+        let mut s = Syndrome::from_sparse(df.key(), df.vector().vector());
+        bgf_decoder(df.key(), &mut s);
+        assert_eq!(*trajectory.last().unwrap(), s.hamming_weight());
+        let value = serde_json::to_value(&analysis).unwrap();
+        assert_eq!(value["syndrome_weight_trajectory"], json!(trajectory));
+    }
+
+    #[test]
+    fn overlap_histogram_totals() {
+        const SAMPLES: usize = 500;
+        let mut rng = rand::thread_rng();
+        let key = QuasiCyclic::<5, 19>::random(&mut rng);
+        let overlaps: Vec<NcwOverlaps> = (0..SAMPLES)
+            .map(|_| {
+                let supp = SparseVector::<6, 38>::random(&mut rng);
+                NcwOverlaps::new(&key, supp.support())
+            })
+            .collect();
+        let histogram: OverlapHistogram = overlaps.iter().cloned().collect();
+        assert_eq!(histogram.total(), SAMPLES);
+        for hist in [&histogram.c, &histogram.n, &histogram.two_n] {
+            assert_eq!(hist.values().sum::<usize>(), SAMPLES);
+        }
+        let (first, second) = overlaps.split_at(SAMPLES / 3);
+        let merged = first
+            .iter()
+            .cloned()
+            .collect::<OverlapHistogram>()
+            .merge(second.iter().cloned().collect());
+        assert_eq!(merged, histogram);
+    }
 }
@@ -0,0 +1,163 @@
+use anyhow::Context;
+use serde::{
+    de::{self, DeserializeOwned, SeqAccess, Visitor},
+    Deserializer as _, Serialize,
+};
+use serde_json::Deserializer;
+use std::{
+    fmt,
+    io::{self, BufRead, Read, Write},
+    marker::PhantomData,
+};
+
+/// Magic number at the start of every zstd frame, in little-endian byte order.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Wraps `reader` in a zstd decoder if its contents start with a zstd frame, and otherwise
+/// reads it unchanged, so compressed and uncompressed JSON input can be read the same way.
+pub fn decompressed<'a, R>(mut reader: R) -> io::Result<Box<dyn Read + 'a>>
+where
+    R: BufRead + 'a,
+{
+    if reader.fill_buf()?.starts_with(&ZSTD_MAGIC) {
+        Ok(Box::new(zstd::Decoder::with_buffer(reader)?))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Reads a JSON array from `reader` one element at a time, applies `process` to each element,
+/// and writes the results that aren't `None` to `writer` as a JSON array. Since at most one
+/// element is held in memory at a time, memory usage doesn't depend on the size of the input.
+/// Returns the number of elements read.
+pub fn process_json_array<T, U, R, W, F>(
+    reader: R,
+    mut writer: W,
+    process: F,
+) -> Result<usize, anyhow::Error>
+where
+    T: DeserializeOwned,
+    U: Serialize,
+    R: Read,
+    W: Write,
+    F: FnMut(T) -> Option<U>,
+{
+    let mut de = Deserializer::from_reader(reader);
+    writer.write_all(b"[")?;
+    let visitor = ArrayVisitor {
+        writer: &mut writer,
+        process,
+        marker: PhantomData,
+    };
+    let count = de
+        .deserialize_seq(visitor)
+        .context("Failed to parse JSON input as array")?;
+    de.end()
+        .context("Unexpected trailing data after JSON array")?;
+    writer.write_all(b"]\n")?;
+    writer.flush()?;
+    Ok(count)
+}
+
+struct ArrayVisitor<'a, T, U, W, F> {
+    writer: &'a mut W,
+    process: F,
+    marker: PhantomData<fn(T) -> U>,
+}
+
+impl<'de, 'a, T, U, W, F> Visitor<'de> for ArrayVisitor<'a, T, U, W, F>
+where
+    T: DeserializeOwned,
+    U: Serialize,
+    W: Write,
+    F: FnMut(T) -> Option<U>,
+{
+    type Value = usize;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a JSON array")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<usize, A::Error> {
+        let mut count = 0;
+        let mut first = true;
+        while let Some(item) = seq.next_element::<T>()? {
+            count += 1;
+            let Some(output) = (self.process)(item) else {
+                continue;
+            };
+            if !first {
+                self.writer.write_all(b",").map_err(de::Error::custom)?;
+            }
+            first = false;
+            serde_json::to_writer(&mut *self.writer, &output).map_err(de::Error::custom)?;
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::DecodingFailureAnalysis;
+    use bike_decoder::decoder::DecodingFailure;
+
+    const INPUT: &str = r#"[
+        {"h0":[0,11,14,53,69,134,190,213,218,245,378,408,411,480,545],"h1":[26,104,110,137,
+        207,252,258,310,326,351,367,459,461,506,570],"e_supp":[16,37,83,130,186,289,351,460,
+        481,527,558,662,724,772,1008,1011,1038,1072]},
+        {"h0":[93,99,105,121,126,141,156,193,194,197,264,301,360,400,429],"h1":[100,117,189,
+        191,211,325,340,386,440,461,465,474,534,565,578],"e_supp":[16,73,89,201,346,522,547,553,
+        574,575,613,619,637,713,955,960,983,1008]}
+    ]"#;
+
+    fn analyze(df: &DecodingFailure) -> DecodingFailureAnalysis {
+        let mut analysis = DecodingFailureAnalysis::new(df, 1000);
+        analysis.compute_overlaps_diff();
+        analysis.compute_absorbing();
+        analysis
+    }
+
+    #[test]
+    fn streaming_matches_buffered() {
+        let decoding_failures: Vec<DecodingFailure> = serde_json::from_str(INPUT).unwrap();
+        let buffered: Vec<_> = decoding_failures.iter().map(analyze).collect();
+        let mut expected = serde_json::to_string(&buffered).unwrap();
+        expected.push('\n');
+        let mut output = Vec::new();
+        let count =
+            process_json_array(INPUT.as_bytes(), &mut output, |df| Some(analyze(&df))).unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+
+    #[test]
+    fn compressed_input() {
+        let compressed = zstd::encode_all(INPUT.as_bytes(), 0).unwrap();
+        let mut expected = Vec::new();
+        process_json_array(INPUT.as_bytes(), &mut expected, |df| Some(analyze(&df))).unwrap();
+        for input in [&compressed[..], INPUT.as_bytes()] {
+            let mut output = Vec::new();
+            let reader = decompressed(input).unwrap();
+            process_json_array(reader, &mut output, |df| Some(analyze(&df))).unwrap();
+            assert_eq!(output, expected);
+        }
+    }
+
+    #[test]
+    fn streaming_skips_and_rejects() {
+        let mut output = Vec::new();
+        let count = process_json_array(
+            INPUT.as_bytes(),
+            &mut output,
+            |_: DecodingFailure| None::<()>,
+        )
+        .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(output, b"[]\n");
+        let mut output = Vec::new();
+        assert!(process_json_array(&b"{}"[..], &mut output, Some::<u32>).is_err());
+        let mut output = Vec::new();
+        assert!(process_json_array(&b"[1, 2] 3"[..], &mut output, Some::<u32>).is_err());
+    }
+}
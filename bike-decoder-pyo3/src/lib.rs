@@ -4,14 +4,50 @@ use bike_decoder::{
     ncw::NcwOverlaps,
     parameters::*,
     syndrome::Syndrome,
-    vectors::{ErrorVector, SparseErrorVector},
+    threshold::ThresholdError as RustThresholdError,
+    vectors::{ErrorVector, InvalidSupport, SparseErrorVector},
 };
-use pyo3::{exceptions::PyValueError, prelude::*};
+use pyo3::{exceptions::PyValueError, prelude::*, types::PyDict};
 use std::collections::HashMap;
 
+use exceptions::{BikeError, InvalidKeyError, InvalidSupportError, ThresholdError};
+
+// Exception hierarchy. BikeError subclasses ValueError so that existing code catching
+// ValueError continues to work. The allow is needed because create_exception! in this
+// version of pyo3 expands to a cfg that newer compilers don't recognize.
+#[allow(unexpected_cfgs)]
+mod exceptions {
+    use pyo3::{create_exception, exceptions::PyValueError};
+
+    create_exception!(
+        bike_decoder_pyo3,
+        BikeError,
+        PyValueError,
+        "Base class for errors raised by bike_decoder_pyo3."
+    );
+    create_exception!(
+        bike_decoder_pyo3,
+        InvalidSupportError,
+        BikeError,
+        "Support of a vector is out of bounds, has the wrong length, or has a repeated index."
+    );
+    create_exception!(
+        bike_decoder_pyo3,
+        InvalidKeyError,
+        InvalidSupportError,
+        "Supports (h0, h1) don't define a valid key."
+    );
+    create_exception!(
+        bike_decoder_pyo3,
+        ThresholdError,
+        BikeError,
+        "Threshold could not be computed for the given parameters."
+    );
+}
+
 /// Optimized, non-cryptographic Rust implementation of BGF decoder used in BIKE.
 #[pymodule]
-fn bike_decoder_pyo3(_py: Python, m: &PyModule) -> PyResult<()> {
+fn bike_decoder_pyo3(py: Python, m: &PyModule) -> PyResult<()> {
     // Constants from bike_decoder::parameters
     m.add("BLOCK_LENGTH", BLOCK_LENGTH)?;
     m.add("BLOCK_WEIGHT", BLOCK_WEIGHT)?;
@@ -20,9 +56,15 @@ fn bike_decoder_pyo3(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add("GRAY_THRESHOLD_DIFF", GRAY_THRESHOLD_DIFF)?;
     m.add("ROW_LENGTH", ROW_LENGTH)?;
     m.add("ROW_WEIGHT", ROW_WEIGHT)?;
+    // Exceptions
+    m.add("BikeError", py.get_type::<BikeError>())?;
+    m.add("InvalidSupportError", py.get_type::<InvalidSupportError>())?;
+    m.add("InvalidKeyError", py.get_type::<InvalidKeyError>())?;
+    m.add("ThresholdError", py.get_type::<ThresholdError>())?;
     // Functions providing a Python interface to the BGF decoder and related utilities
     m.add_function(wrap_pyfunction!(bgf_decoder, m)?)?;
     m.add_function(wrap_pyfunction!(step_by_step_bitflip, m)?)?;
+    m.add_function(wrap_pyfunction!(find_bgf_cycle, m)?)?;
     m.add_function(wrap_pyfunction!(random_key, m)?)?;
     m.add_function(wrap_pyfunction!(random_non_weak_key, m)?)?;
     m.add_function(wrap_pyfunction!(random_error_support, m)?)?;
@@ -31,6 +73,7 @@ fn bike_decoder_pyo3(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(bf_iter_no_mask, m)?)?;
     m.add_function(wrap_pyfunction!(bf_masked_iter, m)?)?;
     m.add_function(wrap_pyfunction!(unsatisfied_parity_checks, m)?)?;
+    m.add_function(wrap_pyfunction!(upc_histogram, m)?)?;
     m.add_function(wrap_pyfunction!(exact_threshold, m)?)?;
     m.add_function(wrap_pyfunction!(ncw_overlaps, m)?)?;
     m.add_function(wrap_pyfunction!(is_absorbing, m)?)?;
@@ -64,6 +107,36 @@ fn step_by_step_bitflip(
     Ok((e_out.contents().to_vec(), steps, s.hamming_weight()))
 }
 
+/// Runs BGF decoder on key `(h0, h1)` and error vector with support `e_supp` for up to
+/// `max_iters` iterations, stopping if the decoder enters a cycle. Returns `None` if no cycle was
+/// found; otherwise returns a dict containing the support of the resulting error vector `e_out`
+/// along with the cycle's `start`, `length`, `weight`, `syndrome_weight`, `threshold`, and
+/// `max_upc`.
+#[pyfunction]
+fn find_bgf_cycle(
+    py: Python,
+    h0: Vec<u32>,
+    h1: Vec<u32>,
+    e_supp: Vec<u32>,
+    max_iters: usize,
+) -> PyResult<Option<PyObject>> {
+    let key = key_from_vec_supp(h0, h1)?;
+    let e_in = sparse_err_from_vec(e_supp)?;
+    let cycle = decoder::find_bgf_cycle(&key, &e_in, max_iters);
+    let Some(data) = cycle.cycle() else {
+        return Ok(None);
+    };
+    let dict = PyDict::new(py);
+    dict.set_item("e_out", cycle.e_out())?;
+    dict.set_item("start", data.start)?;
+    dict.set_item("length", data.length)?;
+    dict.set_item("weight", data.weight)?;
+    dict.set_item("syndrome_weight", data.syndrome_weight)?;
+    dict.set_item("threshold", data.threshold)?;
+    dict.set_item("max_upc", data.max_upc)?;
+    Ok(Some(dict.into()))
+}
+
 /// Generates a random key
 #[pyfunction]
 fn random_key() -> (Vec<u32>, Vec<u32>) {
@@ -76,7 +149,7 @@ fn random_key() -> (Vec<u32>, Vec<u32>) {
 #[pyfunction]
 fn random_non_weak_key(threshold: u8) -> PyResult<(Vec<u32>, Vec<u32>)> {
     if threshold < 3 {
-        return Err(PyValueError::new_err("weak key threshold must be >= 3"));
+        return Err(BikeError::new_err("weak key threshold must be >= 3"));
     }
     let mut rng = bike_decoder::random::custom_thread_rng();
     let key = Key::random_non_weak(threshold, &mut rng).sorted();
@@ -95,12 +168,8 @@ fn random_error_support() -> Vec<u32> {
 #[pyfunction]
 fn syndrome(h0: Vec<u32>, h1: Vec<u32>, e_supp: Vec<u32>) -> PyResult<Vec<bool>> {
     let key = key_from_vec_supp(h0, h1)?;
-    let Ok(err) = ErrorVector::try_from(&*e_supp) else {
-        return Err(PyValueError::new_err(format!(
-            "indices must be <= {}",
-            2 * BLOCK_LENGTH
-        )));
-    };
+    let err = ErrorVector::try_from(&*e_supp)
+        .map_err(|e| InvalidSupportError::new_err(format!("e_supp was not valid: {e}")))?;
     Ok(Syndrome::from_dense(&key, &err).contents().to_vec())
 }
 
@@ -179,14 +248,22 @@ fn unsatisfied_parity_checks(
     Ok((Vec::from(upc0), Vec::from(upc1)))
 }
 
+/// Computes the number of positions with each number of unsatisfied parity checks
+/// `0..=BLOCK_WEIGHT` for the given syndrome and key.
+#[pyfunction]
+fn upc_histogram(h0: Vec<u32>, h1: Vec<u32>, s: Vec<bool>) -> PyResult<Vec<usize>> {
+    let key = key_from_vec_supp(h0, h1)?;
+    let mut s = syndrome_from_vec(s)?;
+    Ok(Vec::from(decoder::upc_histogram(&key, &mut s)))
+}
+
 /// Computes the exact threshold used in the bit-flipping algorithm for syndrome weight `ws`,
 /// block length `r`, block weight `d`, and error weight `t`. The parameters `(r, d, t)` default
 /// to the values `(BLOCK_LENGTH, BLOCK_WEIGHT, ERROR_WEIGHT)` set at compile-time.
 #[pyfunction]
 #[pyo3(signature = (ws, r=BLOCK_LENGTH, d=BLOCK_WEIGHT, t=ERROR_WEIGHT))]
 fn exact_threshold(ws: usize, r: usize, d: usize, t: usize) -> PyResult<u8> {
-    bike_decoder::threshold::exact_threshold_ineq(ws, r, d, t, None)
-        .map_err(|e| PyValueError::new_err(e.to_string()))
+    bike_decoder::threshold::exact_threshold_ineq(ws, r, d, t, None).map_err(threshold_error)
 }
 
 /// Computes the maximum overlap of the vector with support `supp` with each of the near-codeword
@@ -215,19 +292,17 @@ fn is_absorbing(h0: Vec<u32>, h1: Vec<u32>, supp: Vec<u32>) -> PyResult<bool> {
 
 fn key_from_vec_supp(h0: Vec<u32>, h1: Vec<u32>) -> PyResult<Key> {
     let Ok(h0) = <[u32; BLOCK_WEIGHT]>::try_from(h0) else {
-        return Err(PyValueError::new_err(format!(
+        return Err(InvalidKeyError::new_err(format!(
             "h0 must have length {BLOCK_WEIGHT}"
         )));
     };
     let Ok(h1) = <[u32; BLOCK_WEIGHT]>::try_from(h1) else {
-        return Err(PyValueError::new_err(format!(
+        return Err(InvalidKeyError::new_err(format!(
             "h1 must have length {BLOCK_WEIGHT}"
         )));
     };
-    Key::from_support(h0, h1).map_err(|e| {
-        let msg = format!("(h0, h1) was not a valid key: {e}");
-        PyValueError::new_err(msg)
-    })
+    Key::from_support(h0, h1)
+        .map_err(|e| InvalidKeyError::new_err(format!("(h0, h1) was not a valid key: {e}")))
 }
 
 fn syndrome_from_vec(s: Vec<bool>) -> PyResult<Syndrome> {
@@ -240,6 +315,16 @@ fn syndrome_from_vec(s: Vec<bool>) -> PyResult<Syndrome> {
     }
 }
 
+fn sparse_err_from_vec(e_supp: Vec<u32>) -> PyResult<SparseErrorVector> {
+    let Ok(e_supp) = <[u32; ERROR_WEIGHT]>::try_from(e_supp) else {
+        return Err(invalid_support(
+            "e_supp",
+            InvalidSupport::WrongLength(ERROR_WEIGHT),
+        ));
+    };
+    SparseErrorVector::from_support(e_supp).map_err(|e| invalid_support("e_supp", e))
+}
+
 fn err_from_vec(e: Vec<bool>) -> PyResult<ErrorVector> {
     if let Ok(e) = <[bool; ROW_LENGTH]>::try_from(e) {
         Ok(ErrorVector::new(e))
@@ -254,8 +339,16 @@ fn mask_from_vec_pair((mask0, mask1): (Vec<usize>, Vec<usize>)) -> PyResult<[Vec
     if mask0.iter().chain(&mask1).all(|idx| *idx < BLOCK_LENGTH) {
         Ok([mask0, mask1])
     } else {
-        Err(PyValueError::new_err(format!(
+        Err(InvalidSupportError::new_err(format!(
             "mask entries must be < {BLOCK_LENGTH}"
         )))
     }
 }
+
+fn invalid_support(name: &str, err: InvalidSupport) -> PyErr {
+    InvalidSupportError::new_err(format!("{name} was not a valid support: {err}"))
+}
+
+fn threshold_error(err: RustThresholdError) -> PyErr {
+    ThresholdError::new_err(err.to_string())
+}
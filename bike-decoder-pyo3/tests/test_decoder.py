@@ -0,0 +1,100 @@
+import pytest
+
+from bike_decoder_pyo3 import (
+    BLOCK_LENGTH,
+    BLOCK_WEIGHT,
+    ERROR_WEIGHT,
+    BikeError,
+    InvalidKeyError,
+    InvalidSupportError,
+    ThresholdError,
+    bf_masked_iter,
+    bgf_decoder,
+    exact_threshold,
+    find_bgf_cycle,
+    random_error_support,
+    random_key,
+    syndrome,
+    upc_histogram,
+)
+
+
+def test_bgf_cycle_example():
+    assert (BLOCK_LENGTH, BLOCK_WEIGHT, ERROR_WEIGHT) == (587, 15, 18)
+    h0 = [93, 99, 105, 121, 126, 141, 156, 193, 194, 197, 264, 301, 360, 400, 429]
+    h1 = [100, 117, 189, 191, 211, 325, 340, 386, 440, 461, 465, 474, 534, 565, 578]
+    e_in = [
+        16, 73, 89, 201, 346, 522, 547, 553, 574, 575, 613, 619, 637, 713, 955, 960, 983, 1008,
+    ]
+    cycle = find_bgf_cycle(h0, h1, e_in, 100)
+    assert cycle == {
+        "e_out": [67, 73, 201, 242, 459, 481, 501, 507, 547, 575, 637, 759, 922, 955, 1008],
+        "start": 25,
+        "length": 2,
+        "weight": 19,
+        "syndrome_weight": 101,
+        "threshold": 8,
+        "max_upc": 11,
+    }
+    assert find_bgf_cycle(h0, h1, e_in, 20) is None
+
+
+def test_upc_histogram():
+    h0, h1 = random_key()
+    s = syndrome(h0, h1, random_error_support())
+    histogram = upc_histogram(h0, h1, s)
+    assert len(histogram) == BLOCK_WEIGHT + 1
+    assert sum(histogram) == 2 * BLOCK_LENGTH
+    assert upc_histogram(h0, h1, [True] * BLOCK_LENGTH)[BLOCK_WEIGHT] == 2 * BLOCK_LENGTH
+
+
+def test_find_bgf_cycle_invalid_input():
+    h0 = list(range(BLOCK_WEIGHT))
+    h1 = list(range(BLOCK_WEIGHT))
+    with pytest.raises(ValueError):
+        find_bgf_cycle(h0, h1, list(range(ERROR_WEIGHT - 1)), 100)
+    with pytest.raises(ValueError):
+        find_bgf_cycle(h0, h1, list(range(2 * BLOCK_LENGTH, 2 * BLOCK_LENGTH + ERROR_WEIGHT)), 100)
+
+
+def test_exception_hierarchy():
+    assert issubclass(BikeError, ValueError)
+    assert issubclass(InvalidSupportError, BikeError)
+    assert issubclass(InvalidKeyError, InvalidSupportError)
+    assert issubclass(ThresholdError, BikeError)
+
+
+def test_invalid_key_raises_specific_exception():
+    h0, h1 = random_key()
+    e_supp = random_error_support()
+    repeated = [h0[0]] + h0[:-1]
+    with pytest.raises(InvalidKeyError, match="distinct"):
+        syndrome(repeated, h1, e_supp)
+    with pytest.raises(InvalidKeyError, match="range"):
+        syndrome(h0, h1[:-1] + [BLOCK_LENGTH], e_supp)
+    with pytest.raises(InvalidKeyError, match="length"):
+        syndrome(h0[:-1], h1, e_supp)
+
+
+def test_invalid_support_and_threshold_exceptions():
+    h0, h1 = random_key()
+    with pytest.raises(InvalidSupportError) as excinfo:
+        find_bgf_cycle(h0, h1, [0] * ERROR_WEIGHT, 100)
+    assert not isinstance(excinfo.value, InvalidKeyError)
+    with pytest.raises(InvalidSupportError):
+        syndrome(h0, h1, [2 * BLOCK_LENGTH])
+    with pytest.raises(ThresholdError):
+        exact_threshold(BLOCK_LENGTH + 1)
+
+
+def test_wrong_length_vectors_raise_value_error():
+    h0, h1 = random_key()
+    s = syndrome(h0, h1, random_error_support())
+    with pytest.raises(ValueError, match="syndrome must have length") as excinfo:
+        bgf_decoder(h0, h1, s[:-1])
+    assert not isinstance(excinfo.value, BikeError)
+    with pytest.raises(ValueError, match="error vector must have length") as excinfo:
+        bf_masked_iter(h0, h1, s, [False] * BLOCK_LENGTH, ([], []), 8)
+    assert not isinstance(excinfo.value, BikeError)
+    with pytest.raises(InvalidSupportError, match="mask entries"):
+        bf_masked_iter(h0, h1, s, [False] * (2 * BLOCK_LENGTH), ([BLOCK_LENGTH], []), 8)
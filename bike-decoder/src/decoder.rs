@@ -1,22 +1,38 @@
 use crate::{
-    keys::Key,
+    graphs::{combinations_from, failure_subgraph, TannerGraphEdges},
+    keys::{Key, QuasiCyclic, WeakKeyClassification},
     ncw::TaggedErrorVector,
     parameters::*,
     syndrome::Syndrome,
-    threshold::{bf_masked_threshold, build_threshold_cache},
-    vectors::{ErrorVector, Index, SparseErrorVector},
+    threshold::{bf_masked_threshold, bf_threshold_min, ThresholdCache, ThresholdRule},
+    vectors::{DynErrorVector, ErrorVector, Index, SparseErrorVector, SparseVector},
 };
 use getset::{CopyGetters, Getters};
 use once_cell::sync::Lazy;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
 use thiserror::Error;
 
-static THRESHOLD_CACHE: Lazy<Vec<u8>> = Lazy::new(|| {
-    build_threshold_cache(BLOCK_LENGTH, BLOCK_WEIGHT, ERROR_WEIGHT)
+static THRESHOLD_CACHE: Lazy<ThresholdCache> = Lazy::new(|| {
+    ThresholdCache::new(BLOCK_LENGTH, BLOCK_WEIGHT, ERROR_WEIGHT)
         .expect("Must be able to initialize threshold cache")
 });
 
+/// Thresholds for the compile-time parameters, as used by `bgf_decoder`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CachedThreshold;
+
+impl ThresholdRule for CachedThreshold {
+    #[inline]
+    fn threshold(&self, ws: usize) -> u8 {
+        THRESHOLD_CACHE[ws]
+    }
+}
+
 #[derive(Clone, CopyGetters, Debug, Getters, Serialize, Deserialize)]
 pub struct DecodingResult {
     #[getset(get = "pub")]
@@ -28,18 +44,48 @@ pub struct DecodingResult {
 }
 
 impl DecodingResult {
+    #[inline]
     pub fn from_key_vector(key: Key, vector: TaggedErrorVector) -> Self {
+        Self::from_key_vector_with_iters(key, vector, NB_ITER)
+    }
+
+    /// Same as `from_key_vector`, but runs the decoder for `nb_iter` iterations instead of
+    /// the compile-time default `NB_ITER`.
+    pub fn from_key_vector_with_iters(key: Key, vector: TaggedErrorVector, nb_iter: usize) -> Self {
+        Self::from_key_vector_with_params(key, vector, nb_iter, GRAY_THRESHOLD_DIFF)
+    }
+
+    /// Same as `from_key_vector_with_iters`, but also uses the gray threshold difference
+    /// `tau` instead of the compile-time default `GRAY_THRESHOLD_DIFF`.
+    pub fn from_key_vector_with_params(
+        key: Key,
+        vector: TaggedErrorVector,
+        nb_iter: usize,
+        tau: u8,
+    ) -> Self {
+        Self::from_key_vector_with_output(key, vector, nb_iter, tau).0
+    }
+
+    /// Same as `from_key_vector_with_params`, but also returns the final syndrome and the
+    /// output vector of the decoder.
+    pub fn from_key_vector_with_output(
+        key: Key,
+        vector: TaggedErrorVector,
+        nb_iter: usize,
+        tau: u8,
+    ) -> (Self, Syndrome, ErrorVector) {
         let e_supp = vector.vector();
         let e_in = e_supp.dense();
         let mut syn = Syndrome::from_sparse(&key, e_supp);
-        let (e_out, same_syndrome) = bgf_decoder(&key, &mut syn);
+        let (e_out, same_syndrome) = bgf_decoder_with_params(&key, &mut syn, nb_iter, tau);
         let success = e_in == e_out;
         assert!(same_syndrome || !success);
-        Self {
+        let result = Self {
             key,
             vector,
             success,
-        }
+        };
+        (result, syn, e_out)
     }
 
     #[inline]
@@ -48,7 +94,7 @@ impl DecodingResult {
     }
 }
 
-#[derive(Clone, Debug, Getters, Serialize, Deserialize)]
+#[derive(Clone, Debug, Getters, Serialize, Deserialize, PartialEq, Eq)]
 pub struct DecodingFailure {
     #[serde(flatten)]
     #[getset(get = "pub")]
@@ -57,6 +103,22 @@ pub struct DecodingFailure {
     #[getset(get = "pub")]
     vector: TaggedErrorVector,
     pub thread: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weak_key: Option<WeakKeyClassification>,
+}
+
+impl DecodingFailure {
+    /// Returns the decoding failure with the coefficients of each block of the key and of
+    /// the error vector in reverse order (see `QuasiCyclic::reversed` and
+    /// `TaggedErrorVector::reversed`).
+    pub fn reversed(&self) -> Self {
+        Self {
+            key: self.key.reversed(),
+            vector: self.vector.reversed(),
+            thread: self.thread,
+            weak_key: self.weak_key,
+        }
+    }
 }
 
 impl From<DecodingFailure> for DecodingResult {
@@ -80,6 +142,7 @@ impl TryFrom<DecodingResult> for DecodingFailure {
                 key: key.sorted(),
                 vector: vector.sorted(),
                 thread: None,
+                weak_key: None,
             })
         } else {
             Err(NotFailureError)
@@ -146,28 +209,585 @@ impl From<DecoderCycle> for DecodingResult {
     }
 }
 
+#[inline]
 pub fn bgf_decoder(key: &Key, s: &mut Syndrome) -> (ErrorVector, bool) {
-    const BF_MASKED_THRESHOLD: u8 = bf_masked_threshold(BLOCK_WEIGHT);
+    bgf_decoder_with_iters(key, s, NB_ITER)
+}
+
+/// Runs the BGF decoder with `nb_iter` iterations in place of the compile-time default
+/// `NB_ITER`. The first iteration, including the masked rounds, always runs, even if
+/// `nb_iter` is zero.
+#[inline]
+pub fn bgf_decoder_with_iters(key: &Key, s: &mut Syndrome, nb_iter: usize) -> (ErrorVector, bool) {
+    bgf_decoder_with_params(key, s, nb_iter, GRAY_THRESHOLD_DIFF)
+}
+
+/// Runs the BGF decoder with `nb_iter` iterations and gray threshold difference `tau` in
+/// place of the compile-time defaults `NB_ITER` and `GRAY_THRESHOLD_DIFF`. Only the first
+/// iteration uses `tau`, since it's the only one that computes the gray mask.
+pub fn bgf_decoder_with_params(
+    key: &Key,
+    s: &mut Syndrome,
+    nb_iter: usize,
+    tau: u8,
+) -> (ErrorVector, bool) {
+    let mut e_out = ErrorVector::zero();
+    let success = bgf_decoder_inspect(
+        key,
+        s,
+        &mut e_out,
+        nb_iter,
+        tau,
+        cached_threshold,
+        |_, _, _| (),
+    );
+    (e_out, success)
+}
+
+/// Runs the BGF decoder with thresholds chosen by `rule` in place of the exact thresholds
+/// for the compile-time parameters. `bgf_decoder` is the case where `rule` is
+/// `CachedThreshold`.
+pub fn bgf_decoder_with_rule(
+    key: &Key,
+    s: &mut Syndrome,
+    rule: &dyn ThresholdRule,
+) -> (ErrorVector, bool) {
     let mut e_out = ErrorVector::zero();
+    let success = bgf_decoder_inspect(
+        key,
+        s,
+        &mut e_out,
+        NB_ITER,
+        GRAY_THRESHOLD_DIFF,
+        |_, ws| rule.threshold(ws),
+        |_, _, _| (),
+    );
+    (e_out, success)
+}
+
+/// Runs the BGF decoder starting from the error estimate `e_init` instead of zero, with
+/// the syndrome `s` first adjusted by the syndrome of `e_init`. If the adjusted syndrome
+/// is already zero, returns `e_init` immediately with success. `bgf_decoder` is the case
+/// where `e_init` is zero.
+pub fn bgf_decoder_from(key: &Key, s: &mut Syndrome, e_init: &ErrorVector) -> (ErrorVector, bool) {
+    let mut e_out = e_init.clone();
+    *s = s.clone() + Syndrome::from_dense(key, e_init);
+    if s.hamming_weight() == 0 {
+        return (e_out, true);
+    }
+    let success = bgf_decoder_into(key, s, &mut e_out, NB_ITER);
+    (e_out, success)
+}
+
+// Runs the BGF decoder, storing the output in e_out, which holds the initial error
+// estimate (usually zero) and must be consistent with s.
+// Returns true if the resulting syndrome is zero.
+#[inline]
+fn bgf_decoder_into(key: &Key, s: &mut Syndrome, e_out: &mut ErrorVector, nb_iter: usize) -> bool {
+    bgf_decoder_inspect(
+        key,
+        s,
+        e_out,
+        nb_iter,
+        GRAY_THRESHOLD_DIFF,
+        cached_threshold,
+        |_, _, _| (),
+    )
+}
+
+// Threshold used by bgf_decoder in every iteration
+#[inline]
+fn cached_threshold(_iteration: usize, ws: usize) -> u8 {
+    THRESHOLD_CACHE[ws]
+}
+
+/// Step of the BGF decoder after which the callback of `bgf_decoder_inspect` is called.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BgfStep {
+    /// The unmasked part of iteration 0, which computes the black and gray masks
+    Initial,
+    /// The masked round of iteration 0 using the black mask
+    BlackMask,
+    /// The masked round of iteration 0 using the gray mask, which ends iteration 0
+    GrayMask,
+    /// Iteration `i >= 1`
+    Iteration(usize),
+}
+
+impl BgfStep {
+    #[inline]
+    fn ends_iteration(self) -> bool {
+        matches!(self, Self::GrayMask | Self::Iteration(_))
+    }
+}
+
+// How the bit flipping steps of the BGF decoder compute the unsatisfied parity checks
+trait BitFlipper {
+    fn iter_tau(
+        &mut self,
+        key: &Key,
+        s: &mut Syndrome,
+        e_out: &mut ErrorVector,
+        thr: u8,
+        tau: u8,
+    ) -> ([Vec<usize>; 2], [Vec<usize>; 2]);
+
+    fn masked_iter(
+        &mut self,
+        key: &Key,
+        s: &mut Syndrome,
+        e_out: &mut ErrorVector,
+        mask: [Vec<usize>; 2],
+        thr: u8,
+    );
+
+    fn iter_no_mask(&mut self, key: &Key, s: &mut Syndrome, e_out: &mut ErrorVector, thr: u8);
+}
+
+// Recomputes the unsatisfied parity checks in full at the start of every step
+struct RecomputeUpc;
+
+impl BitFlipper for RecomputeUpc {
+    #[inline]
+    fn iter_tau(
+        &mut self,
+        key: &Key,
+        s: &mut Syndrome,
+        e_out: &mut ErrorVector,
+        thr: u8,
+        tau: u8,
+    ) -> ([Vec<usize>; 2], [Vec<usize>; 2]) {
+        bf_iter_tau(key, s, e_out, thr, tau)
+    }
+
+    #[inline]
+    fn masked_iter(
+        &mut self,
+        key: &Key,
+        s: &mut Syndrome,
+        e_out: &mut ErrorVector,
+        mask: [Vec<usize>; 2],
+        thr: u8,
+    ) {
+        bf_masked_iter(key, s, e_out, mask, thr);
+    }
+
+    #[inline]
+    fn iter_no_mask(&mut self, key: &Key, s: &mut Syndrome, e_out: &mut ErrorVector, thr: u8) {
+        bf_iter_no_mask(key, s, e_out, thr);
+    }
+}
+
+// Keeps the unsatisfied parity checks up to date as bits are flipped
+struct IncrementalUpc([[u8; BLOCK_LENGTH]; 2]);
+
+impl BitFlipper for IncrementalUpc {
+    #[inline]
+    fn iter_tau(
+        &mut self,
+        key: &Key,
+        s: &mut Syndrome,
+        e_out: &mut ErrorVector,
+        thr: u8,
+        tau: u8,
+    ) -> ([Vec<usize>; 2], [Vec<usize>; 2]) {
+        debug_assert_eq!(tau, GRAY_THRESHOLD_DIFF);
+        bf_iter_incremental(key, s, e_out, &mut self.0, thr)
+    }
+
+    #[inline]
+    fn masked_iter(
+        &mut self,
+        key: &Key,
+        s: &mut Syndrome,
+        e_out: &mut ErrorVector,
+        mask: [Vec<usize>; 2],
+        thr: u8,
+    ) {
+        bf_masked_iter_incremental(key, s, e_out, &mut self.0, mask, thr);
+    }
+
+    #[inline]
+    fn iter_no_mask(&mut self, key: &Key, s: &mut Syndrome, e_out: &mut ErrorVector, thr: u8) {
+        bf_iter_no_mask_incremental(key, s, e_out, &mut self.0, thr);
+    }
+}
+
+// Same as bgf_decoder_into, but uses gray threshold difference tau, takes the threshold of
+// each iteration from threshold(iteration, ws), where ws is the syndrome weight at the start
+// of the iteration, and calls inspect on the syndrome and e_out after each step.
+#[inline]
+fn bgf_decoder_inspect<T, F>(
+    key: &Key,
+    s: &mut Syndrome,
+    e_out: &mut ErrorVector,
+    nb_iter: usize,
+    tau: u8,
+    threshold: T,
+    inspect: F,
+) -> bool
+where
+    T: FnMut(usize, usize) -> u8,
+    F: FnMut(BgfStep, &Syndrome, &ErrorVector),
+{
+    bgf_decoder_loop(
+        key,
+        s,
+        e_out,
+        nb_iter,
+        tau,
+        &mut RecomputeUpc,
+        threshold,
+        inspect,
+    )
+}
+
+// Same as bgf_decoder_inspect, but uses flipper for the bit flipping steps
+#[allow(clippy::too_many_arguments)]
+fn bgf_decoder_loop<B, T, F>(
+    key: &Key,
+    s: &mut Syndrome,
+    e_out: &mut ErrorVector,
+    nb_iter: usize,
+    tau: u8,
+    flipper: &mut B,
+    mut threshold: T,
+    mut inspect: F,
+) -> bool
+where
+    B: BitFlipper,
+    T: FnMut(usize, usize) -> u8,
+    F: FnMut(BgfStep, &Syndrome, &ErrorVector),
+{
+    const BF_MASKED_THRESHOLD: u8 = bf_masked_threshold(BLOCK_WEIGHT);
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("bgf_decoder", nb_iter, tau).entered();
     let mut ws = s.hamming_weight();
     // Iteration 0
-    let thr = THRESHOLD_CACHE[ws];
-    let (black, gray) = bf_iter(key, s, &mut e_out, thr);
-    bf_masked_iter(key, s, &mut e_out, black, BF_MASKED_THRESHOLD);
-    bf_masked_iter(key, s, &mut e_out, gray, BF_MASKED_THRESHOLD);
+    let thr = threshold(0, ws);
+    #[cfg(feature = "tracing")]
+    tracing::debug!(iteration = 0, syndrome_weight = ws, threshold = thr);
+    let (black, gray) = flipper.iter_tau(key, s, e_out, thr, tau);
+    inspect(BgfStep::Initial, s, e_out);
+    flipper.masked_iter(key, s, e_out, black, BF_MASKED_THRESHOLD);
+    inspect(BgfStep::BlackMask, s, e_out);
+    flipper.masked_iter(key, s, e_out, gray, BF_MASKED_THRESHOLD);
+    inspect(BgfStep::GrayMask, s, e_out);
     ws = s.hamming_weight();
     if ws == 0 {
-        return (e_out, true);
+        return true;
     }
-    for _ in 1..NB_ITER {
-        let thr = THRESHOLD_CACHE[ws];
-        bf_iter_no_mask(key, s, &mut e_out, thr);
+    for iteration in 1..nb_iter {
+        let thr = threshold(iteration, ws);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(iteration, syndrome_weight = ws, threshold = thr);
+        flipper.iter_no_mask(key, s, e_out, thr);
+        inspect(BgfStep::Iteration(iteration), s, e_out);
         ws = s.hamming_weight();
         if ws == 0 {
             break;
         }
     }
-    (e_out, ws == 0)
+    ws == 0
+}
+
+// Sorted list of the positions where e_out differs from e_prev, which is then updated to
+// match e_out.
+fn flips_since(e_prev: &mut ErrorVector, e_out: &ErrorVector) -> Vec<usize> {
+    let flips: Vec<usize> = e_out
+        .contents()
+        .iter()
+        .zip(e_prev.contents())
+        .enumerate()
+        .filter_map(|(idx, (&a, &b))| (a ^ b).then_some(idx))
+        .collect();
+    for &idx in &flips {
+        e_prev.flip(idx);
+    }
+    flips
+}
+
+/// Runs the BGF decoder, additionally returning for each iteration the positions of the
+/// error estimate flipped during that iteration. Applying these deltas in sequence to the
+/// zero vector reconstructs the output of the decoder.
+pub fn bgf_decoder_deltas(key: &Key, s: &mut Syndrome) -> (ErrorVector, bool, Vec<Vec<Index>>) {
+    let mut e_out = ErrorVector::zero();
+    let mut e_prev = ErrorVector::zero();
+    let mut deltas = Vec::with_capacity(NB_ITER);
+    let inspect = |step: BgfStep, _: &Syndrome, e_out: &ErrorVector| {
+        if step.ends_iteration() {
+            let delta = flips_since(&mut e_prev, e_out);
+            deltas.push(delta.into_iter().map(|idx| idx as Index).collect());
+        }
+    };
+    let success = bgf_decoder_inspect(
+        key,
+        s,
+        &mut e_out,
+        NB_ITER,
+        GRAY_THRESHOLD_DIFF,
+        cached_threshold,
+        inspect,
+    );
+    (e_out, success, deltas)
+}
+
+/// Runs the BGF decoder and returns the sorted list of positions flipped during each step.
+/// The initial iteration and its two masked sub-steps (black and gray) are recorded as
+/// separate entries, so entry `i + 2` corresponds to iteration `i` for `i >= 1`. This is
+/// intended for debugging; use `bgf_decoder` when the history isn't needed.
+#[inline]
+pub fn bgf_decoder_history(key: &Key, s: &mut Syndrome) -> Vec<Vec<usize>> {
+    bgf_decoder_history_with_iters(key, s, NB_ITER)
+}
+
+fn bgf_decoder_history_with_iters(key: &Key, s: &mut Syndrome, nb_iter: usize) -> Vec<Vec<usize>> {
+    let mut e_out = ErrorVector::zero();
+    let mut e_prev = ErrorVector::zero();
+    let mut history = Vec::with_capacity(nb_iter + 2);
+    bgf_decoder_inspect(
+        key,
+        s,
+        &mut e_out,
+        nb_iter,
+        GRAY_THRESHOLD_DIFF,
+        cached_threshold,
+        |_, _, e_out| history.push(flips_since(&mut e_prev, e_out)),
+    );
+    history
+}
+
+/// Runs the BGF decoder, additionally returning the syndrome weight after each step. As in
+/// `bgf_decoder_history`, the initial iteration and its two masked sub-steps are recorded as
+/// separate entries, so the trajectory has at most `NB_ITER + 2` entries, the last of which
+/// is the weight of the final syndrome.
+pub fn bgf_decoder_traced(key: &Key, s: &mut Syndrome) -> (ErrorVector, bool, Vec<usize>) {
+    let mut e_out = ErrorVector::zero();
+    let mut trajectory = Vec::with_capacity(NB_ITER + 2);
+    let success = bgf_decoder_inspect(
+        key,
+        s,
+        &mut e_out,
+        NB_ITER,
+        GRAY_THRESHOLD_DIFF,
+        cached_threshold,
+        |_, s, _| trajectory.push(s.hamming_weight()),
+    );
+    (e_out, success, trajectory)
+}
+
+/// Runs the BGF decoder with one iteration per entry of `thresholds`, using `thresholds[i]`
+/// as the threshold of iteration `i` in place of the threshold determined by the syndrome
+/// weight. Thresholds below `bf_threshold_min(BLOCK_WEIGHT)` are raised to that minimum.
+/// As with `bgf_decoder`, decoding stops early once the syndrome is zero; if `thresholds`
+/// is empty, no iterations are run.
+pub fn bgf_decoder_fixed_thresholds(
+    key: &Key,
+    s: &mut Syndrome,
+    thresholds: &[u8],
+) -> (ErrorVector, bool) {
+    const BF_THRESHOLD_MIN: u8 = bf_threshold_min(BLOCK_WEIGHT);
+    let mut e_out = ErrorVector::zero();
+    if thresholds.is_empty() {
+        return (e_out, s.hamming_weight() == 0);
+    }
+    let success = bgf_decoder_inspect(
+        key,
+        s,
+        &mut e_out,
+        thresholds.len(),
+        GRAY_THRESHOLD_DIFF,
+        |iteration, _| thresholds[iteration].max(BF_THRESHOLD_MIN),
+        |_, _, _| (),
+    );
+    (e_out, success)
+}
+
+/// Decodes each of the given error vectors with the key `(h0, h1)`, returning whether
+/// decoding succeeded for each one. A single syndrome and output buffer are reused
+/// across the whole batch.
+pub fn decode_batch(key: &Key, errors: &[SparseErrorVector]) -> Vec<bool> {
+    let mut s = Syndrome::zero();
+    let mut e_out = ErrorVector::zero();
+    errors
+        .iter()
+        .map(|e_in| {
+            s.set_all_zero();
+            e_out.set_all_zero();
+            for &idx in e_in.support() {
+                let idx = idx as usize;
+                s.recompute_flipped_bit(key, idx / BLOCK_LENGTH, idx % BLOCK_LENGTH);
+            }
+            bgf_decoder_into(key, &mut s, &mut e_out, NB_ITER)
+                && e_in.support().iter().all(|&idx| e_out.get(idx as usize))
+                && e_out.hamming_weight() == e_in.weight()
+        })
+        .collect()
+}
+
+/// Decodes `num_trials` uniformly random error vectors of weight `ERROR_WEIGHT` with the
+/// key `(h0, h1)` and returns the total time spent computing syndromes and decoding. Time
+/// spent sampling the error vectors isn't counted.
+pub fn benchmark<R>(key: &Key, num_trials: usize, rng: &mut R) -> Duration
+where
+    R: Rng + ?Sized,
+{
+    let mut s = Syndrome::zero();
+    let mut e_out = ErrorVector::zero();
+    let mut elapsed = Duration::ZERO;
+    for _ in 0..num_trials {
+        let e_in = SparseErrorVector::random(rng);
+        let start = Instant::now();
+        s.set_all_zero();
+        e_out.set_all_zero();
+        for &idx in e_in.support() {
+            let idx = idx as usize;
+            s.recompute_flipped_bit(key, idx / BLOCK_LENGTH, idx % BLOCK_LENGTH);
+        }
+        std::hint::black_box(bgf_decoder_into(key, &mut s, &mut e_out, NB_ITER));
+        elapsed += start.elapsed();
+    }
+    elapsed
+}
+
+/// Decoding throughput corresponding to `num_trials` decodes taking `elapsed` in total, as
+/// returned by `benchmark`. Returns 0 if `elapsed` is zero, so the result is always finite.
+#[inline]
+pub fn decodes_per_second(num_trials: usize, elapsed: Duration) -> f64 {
+    if elapsed.is_zero() {
+        0.0
+    } else {
+        num_trials as f64 / elapsed.as_secs_f64()
+    }
+}
+
+/// Exhaustively checks that the BGF decoder corrects every error vector of weight `T` with
+/// the key `(h0, h1)`, trying supports in lexicographic order. Returns the first error
+/// vector that isn't corrected, or `None` if decoding succeeds for all of them. Works for
+/// any code parameters, so the error vectors have length `N`, which must be `2 * LEN`; the
+/// thresholds are computed for block length `LEN`, block weight `WT` and error weight `T`.
+/// To check the full correction radius, call this for each weight up to the radius. The
+/// number of vectors grows very quickly with `T`, so this is only practical for small
+/// weights or small codes.
+///
+/// # Panics
+///
+/// Panics if `N != 2 * LEN` or if the thresholds can't be computed for these parameters.
+pub fn verify_correction_radius<
+    const WT: usize,
+    const LEN: usize,
+    const T: usize,
+    const N: usize,
+>(
+    key: &QuasiCyclic<WT, LEN>,
+) -> Option<SparseVector<T, N>> {
+    assert_eq!(
+        N,
+        2 * LEN,
+        "error vector length must be twice the block length"
+    );
+    let thresholds = ThresholdCache::new(LEN, WT, T)
+        .expect("thresholds should be computable for the key parameters");
+    combinations_from(N as Index, T, 0, u128::MAX)
+        .find(|supp| {
+            let mut e_in = vec![false; N];
+            for &idx in supp {
+                e_in[idx as usize] = true;
+            }
+            let mut s = vec![false; LEN];
+            for (k, block) in [key.h0(), key.h1()].into_iter().enumerate() {
+                for &idx in supp.iter().filter(|&&idx| idx as usize / LEN == k) {
+                    for &j in block.support() {
+                        let check = (idx as usize % LEN + j as usize) % LEN;
+                        s[check] = !s[check];
+                    }
+                }
+            }
+            let mut e_out = vec![false; N];
+            !(bgf_decoder_generic(key, &mut s, &mut e_out, &thresholds) && e_out == e_in)
+        })
+        .map(|supp| {
+            SparseVector::try_from(supp.as_slice()).expect("enumerated support should be valid")
+        })
+}
+
+// Reference implementation of the BGF decoder for keys with any parameters, using dense
+// boolean vectors for the syndrome and error vector. Follows the same steps as
+// bgf_decoder_loop, but is much slower than bgf_decoder.
+fn bgf_decoder_generic<const WT: usize, const LEN: usize>(
+    key: &QuasiCyclic<WT, LEN>,
+    s: &mut [bool],
+    e_out: &mut [bool],
+    thresholds: &ThresholdCache,
+) -> bool {
+    let masked_threshold = bf_masked_threshold(WT);
+    let threshold = |s: &[bool]| {
+        let ws = s.iter().filter(|&&bit| bit).count();
+        thresholds
+            .get(ws)
+            .expect("syndrome weight should be at most LEN")
+    };
+    // Flips every bit in mask whose unsatisfied parity check count is at least thr, then
+    // returns those counts.
+    let flip_where = |s: &mut [bool], e_out: &mut [bool], mask: &[bool], thr: u8| {
+        let upc: Vec<u8> = (0..2 * LEN)
+            .map(|idx| {
+                let block = if idx < LEN { key.h0() } else { key.h1() };
+                block
+                    .support()
+                    .iter()
+                    .filter(|&&j| s[(idx % LEN + j as usize) % LEN])
+                    .count() as u8
+            })
+            .collect();
+        for idx in (0..2 * LEN).filter(|&idx| mask[idx] && upc[idx] >= thr) {
+            e_out[idx] = !e_out[idx];
+            let block = if idx < LEN { key.h0() } else { key.h1() };
+            for &j in block.support() {
+                let check = (idx % LEN + j as usize) % LEN;
+                s[check] = !s[check];
+            }
+        }
+        upc
+    };
+    let all = vec![true; 2 * LEN];
+    // Iteration 0
+    let thr = threshold(s);
+    let upc = flip_where(s, e_out, &all, thr);
+    let black: Vec<bool> = upc.iter().map(|&upc| upc >= thr).collect();
+    let gray: Vec<bool> = upc
+        .iter()
+        .map(|&upc| upc < thr && upc >= thr.saturating_sub(GRAY_THRESHOLD_DIFF))
+        .collect();
+    flip_where(s, e_out, &black, masked_threshold);
+    flip_where(s, e_out, &gray, masked_threshold);
+    for _ in 1..NB_ITER {
+        if s.iter().all(|&bit| !bit) {
+            break;
+        }
+        let thr = threshold(s);
+        flip_where(s, e_out, &all, thr);
+    }
+    s.iter().all(|&bit| !bit)
+}
+
+/// Variant of `bgf_decoder` that computes the unsatisfied parity checks in full only once,
+/// then updates them incrementally after each bit flip rather than recomputing them at the
+/// start of every iteration. Returns the same results as `bgf_decoder`.
+pub fn bgf_decoder_incremental(key: &Key, s: &mut Syndrome) -> (ErrorVector, bool) {
+    let mut e_out = ErrorVector::zero();
+    let mut flipper = IncrementalUpc(unsatisfied_parity_checks(key, s));
+    let success = bgf_decoder_loop(
+        key,
+        s,
+        &mut e_out,
+        NB_ITER,
+        GRAY_THRESHOLD_DIFF,
+        &mut flipper,
+        cached_threshold,
+        |_, _, _| (),
+    );
+    (e_out, success)
 }
 
 /// Runs step-by-step decoder (Algorithm 7.1 in Vasseur's thesis) on key `(h0, h1)` and syndrome
@@ -258,50 +878,245 @@ pub fn find_bgf_cycle(key: &Key, e_in: &SparseErrorVector, max_iters: usize) ->
     }
 }
 
+/// Maximum number of iterations `explain_failure` runs the decoder for while looking for a
+/// cycle.
+pub const EXPLAIN_FAILURE_MAX_ITERS: usize = 10_000;
+
+/// Diagnosis of why the BGF decoder fails to decode an error vector, as returned by
+/// `explain_failure`.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum FailureReason {
+    /// The decoder enters a cycle of length at least 2 with nonzero syndrome.
+    Cycle(CycleData),
+    /// The decoder stalls at nonzero syndrome weight `ws`, either at a fixed point or
+    /// without entering a cycle within `EXPLAIN_FAILURE_MAX_ITERS` iterations.
+    StuckHighSyndrome { ws: usize },
+    /// The syndrome reaches zero, but the output differs from the input error vector by a
+    /// nonzero codeword of weight `diff_weight`.
+    WrongCodeword { diff_weight: usize },
+    /// The decoder recovers the input error vector. `iterations` is the number of iterations
+    /// run until the syndrome becomes zero, counting the first iteration (including its
+    /// masked rounds) as one, so this is only a decoding failure if `iterations` exceeds
+    /// `NB_ITER`.
+    Converged { iterations: usize },
+}
+
+impl fmt::Display for FailureReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Cycle(data) => write!(
+                f,
+                "{}-cycle starting at iteration {} with syndrome weight {}",
+                data.length, data.start, data.syndrome_weight
+            ),
+            Self::StuckHighSyndrome { ws } => write!(f, "stuck at syndrome weight {ws}"),
+            Self::WrongCodeword { diff_weight } => {
+                write!(f, "converged to wrong codeword at distance {diff_weight}")
+            }
+            Self::Converged { iterations } => {
+                write!(f, "converged after {iterations} iterations")
+            }
+        }
+    }
+}
+
+/// Runs the decoder on `e_in` until it reaches a cycle (see `find_bgf_cycle`) to diagnose
+/// why decoding fails.
+pub fn explain_failure(key: &Key, e_in: &SparseErrorVector) -> FailureReason {
+    let cycle = find_bgf_cycle(key, e_in, EXPLAIN_FAILURE_MAX_ITERS);
+    match cycle.cycle() {
+        Some(data) if data.syndrome_weight == 0 && data.weight == 0 => {
+            let mut s = Syndrome::from_sparse(key, e_in);
+            let mut e_out = ErrorVector::zero();
+            let mut iterations = 0;
+            bgf_decoder_inspect(
+                key,
+                &mut s,
+                &mut e_out,
+                EXPLAIN_FAILURE_MAX_ITERS,
+                GRAY_THRESHOLD_DIFF,
+                cached_threshold,
+                |step, _, _| iterations += usize::from(step.ends_iteration()),
+            );
+            FailureReason::Converged { iterations }
+        }
+        Some(data) if data.syndrome_weight == 0 => FailureReason::WrongCodeword {
+            diff_weight: data.weight,
+        },
+        Some(data) if data.length > 1 => FailureReason::Cycle(data),
+        Some(data) => FailureReason::StuckHighSyndrome {
+            ws: data.syndrome_weight,
+        },
+        None => {
+            let diff = DynErrorVector::from_support(cycle.diff(), ROW_LENGTH as Index)
+                .expect("diff should be a valid support");
+            FailureReason::StuckHighSyndrome {
+                ws: Syndrome::from_dyn_sparse(key, &diff).hamming_weight(),
+            }
+        }
+    }
+}
+
+/// Runs the BGF decoder on key `(h0, h1)` and error vector `e_in` for up to `max_iter`
+/// iterations, stopping early if the syndrome becomes zero. Returns the check nodes of the
+/// failure subgraph of the residual `e_in + e_out` (see `graphs::failure_subgraph`), i.e.,
+/// the unsatisfied check nodes, after each iteration, which determines the sequence of
+/// Tanner subgraphs traversed by the decoder.
+pub fn trajectory_subgraphs(
+    key: &Key,
+    e_in: &SparseErrorVector,
+    max_iter: usize,
+) -> Vec<Vec<Index>> {
+    let mut trajectory = Vec::with_capacity(max_iter);
+    if max_iter == 0 {
+        return trajectory;
+    }
+    let edges = TannerGraphEdges::new(key);
+    let e_in_dense = e_in.dense();
+    let mut s = Syndrome::from_sparse(key, e_in);
+    let mut e_out = ErrorVector::zero();
+    bgf_decoder_inspect(
+        key,
+        &mut s,
+        &mut e_out,
+        max_iter,
+        GRAY_THRESHOLD_DIFF,
+        cached_threshold,
+        |step, _, e_out| {
+            if step.ends_iteration() {
+                let residual = e_out.clone() + e_in_dense.clone();
+                trajectory.push(failure_subgraph(&edges, &residual.support()));
+            }
+        },
+    );
+    trajectory
+}
+
+/// Returns true if `unsatisfied_parity_checks` uses SIMD instructions on this machine.
+pub fn simd_enabled() -> bool {
+    #[cfg(all(
+        any(target_arch = "x86", target_arch = "x86_64"),
+        target_feature = "avx2"
+    ))]
+    {
+        std::arch::is_x86_feature_detected!("avx2")
+    }
+    #[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+    {
+        std::arch::is_aarch64_feature_detected!("neon")
+    }
+    #[cfg(not(any(
+        all(
+            any(target_arch = "x86", target_arch = "x86_64"),
+            target_feature = "avx2"
+        ),
+        all(target_arch = "aarch64", target_feature = "neon")
+    )))]
+    {
+        false
+    }
+}
+
 pub fn unsatisfied_parity_checks(key: &Key, s: &mut Syndrome) -> [[u8; BLOCK_LENGTH]; 2] {
     // Duplicate the syndrome to precompute cyclic shifts and avoid modulo operations
     s.duplicate_contents();
-    let h_supp = [key.h0().support(), key.h1().support()];
     #[cfg(all(
         any(target_arch = "x86", target_arch = "x86_64"),
         target_feature = "avx2"
     ))]
     {
         if std::arch::is_x86_feature_detected!("avx2") {
-            #[inline]
-            fn truncate_buffer(buf: [u8; SIZE_AVX]) -> [u8; BLOCK_LENGTH] {
-                (&buf[..BLOCK_LENGTH])
-                    .try_into()
-                    .expect("Must ensure BLOCK_LENGTH <= SIZE_AVX")
-            }
+            let h_supp = [key.h0().support(), key.h1().support()];
             let mut upc = [[0u8; SIZE_AVX]; 2];
             multiply_avx2(&mut upc[0], h_supp[0], s.contents_with_buffer());
             multiply_avx2(&mut upc[1], h_supp[1], s.contents_with_buffer());
             return [truncate_buffer(upc[0]), truncate_buffer(upc[1])];
         }
     }
+    #[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+    {
+        if std::arch::is_aarch64_feature_detected!("neon") {
+            let h_supp = [key.h0().support(), key.h1().support()];
+            let mut upc = [[0u8; SIZE_AVX]; 2];
+            multiply_neon(&mut upc[0], h_supp[0], s.contents_with_buffer());
+            multiply_neon(&mut upc[1], h_supp[1], s.contents_with_buffer());
+            return [truncate_buffer(upc[0]), truncate_buffer(upc[1])];
+        }
+    }
+    upc_scalar_duplicated(key, s)
+}
+
+#[cfg(any(
+    all(
+        any(target_arch = "x86", target_arch = "x86_64"),
+        target_feature = "avx2"
+    ),
+    all(target_arch = "aarch64", target_feature = "neon")
+))]
+#[inline]
+fn truncate_buffer(buf: [u8; SIZE_AVX]) -> [u8; BLOCK_LENGTH] {
+    (&buf[..BLOCK_LENGTH])
+        .try_into()
+        .expect("Must ensure BLOCK_LENGTH <= SIZE_AVX")
+}
+
+/// Counts the positions of the error vector (over both blocks) with each possible number of
+/// unsatisfied parity checks for the syndrome `s`, so that entry `k` is the number of
+/// positions with exactly `k` unsatisfied checks. The sum of the entries from `thr` on is the
+/// number of positions an unmasked bit-flipping iteration with threshold `thr` would flip.
+pub fn upc_histogram(key: &Key, s: &mut Syndrome) -> [usize; BLOCK_WEIGHT + 1] {
+    let mut histogram = [0; BLOCK_WEIGHT + 1];
+    for &upc in unsatisfied_parity_checks(key, s).iter().flatten() {
+        histogram[upc as usize] += 1;
+    }
+    histogram
+}
+
+/// Computes the unsatisfied parity checks without using SIMD instructions, even if
+/// they are available. Mainly useful for testing and benchmarking.
+pub fn unsatisfied_parity_checks_scalar(key: &Key, s: &mut Syndrome) -> [[u8; BLOCK_LENGTH]; 2] {
+    s.duplicate_contents();
+    upc_scalar_duplicated(key, s)
+}
+
+// Assumes that the syndrome contents have already been duplicated
+fn upc_scalar_duplicated(key: &Key, s: &Syndrome) -> [[u8; BLOCK_LENGTH]; 2] {
+    let h_supp = [key.h0().support(), key.h1().support()];
     let mut upc = [[0u8; BLOCK_LENGTH]; 2];
-    for k in 0..2 {
-        for i in 0..BLOCK_LENGTH {
-            for &j in h_supp[k] {
+    for (upc_k, h_supp_k) in upc.iter_mut().zip(h_supp) {
+        for (i, upc_ki) in upc_k.iter_mut().enumerate() {
+            for &j in h_supp_k {
                 // If i + j >= BLOCK_LENGTH, this wraps around because we duplicated s
-                upc[k][i] += u8::from(s.get(i + j as usize));
+                *upc_ki += u8::from(s.get(i + j as usize));
             }
         }
     }
     upc
 }
 
+#[inline]
+pub fn bf_iter(
+    key: &Key,
+    s: &mut Syndrome,
+    e_out: &mut ErrorVector,
+    thr: u8,
+) -> ([Vec<usize>; 2], [Vec<usize>; 2]) {
+    bf_iter_tau(key, s, e_out, thr, GRAY_THRESHOLD_DIFF)
+}
+
+/// Same as `bf_iter`, but positions are added to the gray mask if their number of
+/// unsatisfied parity checks is at least `thr - tau` rather than `thr - GRAY_THRESHOLD_DIFF`.
 // the compiler seems to make some bad optimization choices if allowed to inline this
 #[inline(never)]
-pub fn bf_iter(
+pub fn bf_iter_tau(
     key: &Key,
     s: &mut Syndrome,
     e_out: &mut ErrorVector,
     thr: u8,
+    tau: u8,
 ) -> ([Vec<usize>; 2], [Vec<usize>; 2]) {
     let upc = unsatisfied_parity_checks(key, s);
-    let gray_thr = thr - GRAY_THRESHOLD_DIFF;
+    let gray_thr = thr.saturating_sub(tau);
     let mut black = [
         Vec::with_capacity(BLOCK_LENGTH),
         Vec::with_capacity(BLOCK_LENGTH),
@@ -325,9 +1140,43 @@ pub fn bf_iter(
             }
         }
     }
+    #[cfg(feature = "tracing")]
+    tracing::trace!(
+        threshold = thr,
+        black = black[0].len() + black[1].len(),
+        gray = gray[0].len() + gray[1].len(),
+        "bf_iter"
+    );
     (black, gray)
 }
 
+/// Same as `bf_iter`, but writes the black and gray masks as boolean arrays into `black` and
+/// `gray` (overwriting their previous contents) instead of returning them as vectors of
+/// indices, to avoid allocating on every iteration.
+#[inline(never)]
+pub fn bf_iter_dense(
+    key: &Key,
+    s: &mut Syndrome,
+    e_out: &mut ErrorVector,
+    thr: u8,
+    black: &mut [[bool; BLOCK_LENGTH]; 2],
+    gray: &mut [[bool; BLOCK_LENGTH]; 2],
+) {
+    let upc = unsatisfied_parity_checks(key, s);
+    let gray_thr = thr.saturating_sub(GRAY_THRESHOLD_DIFF);
+    for (k, upc_k) in upc.iter().enumerate() {
+        for (i, &upc_ki) in upc_k.iter().enumerate() {
+            let is_black = upc_ki >= thr;
+            if is_black {
+                e_out.flip(i + k * BLOCK_LENGTH);
+                s.recompute_flipped_bit(key, k, i);
+            }
+            black[k][i] = is_black;
+            gray[k][i] = !is_black && upc_ki >= gray_thr;
+        }
+    }
+}
+
 #[inline(never)]
 pub fn bf_iter_no_mask(key: &Key, s: &mut Syndrome, e_out: &mut ErrorVector, thr: u8) {
     let upc = unsatisfied_parity_checks(key, s);
@@ -341,6 +1190,16 @@ pub fn bf_iter_no_mask(key: &Key, s: &mut Syndrome, e_out: &mut ErrorVector, thr
             s.recompute_flipped_bit(key, k, i);
         }
     }
+    #[cfg(feature = "tracing")]
+    tracing::trace!(
+        threshold = thr,
+        flips = upc
+            .iter()
+            .flatten()
+            .filter(|&&upc_ki| upc_ki >= thr)
+            .count(),
+        "bf_iter_no_mask"
+    );
 }
 
 pub fn bf_masked_iter(
@@ -350,6 +1209,10 @@ pub fn bf_masked_iter(
     mask: [Vec<usize>; 2],
     thr: u8,
 ) {
+    // Nothing can be flipped, so skip computing the unsatisfied parity checks
+    if mask.iter().all(Vec::is_empty) {
+        return;
+    }
     let upc = unsatisfied_parity_checks(key, s);
     for k in 0..2 {
         for &i in mask[k].iter() {
@@ -361,36 +1224,115 @@ pub fn bf_masked_iter(
     }
 }
 
-/// Multiplies a sparse vector by a dense vector. The dense vector should be
-/// duplicated in memory to precompute cyclic shifts. The results are stored in
-/// the provided output buffer.
-/// Adapted from Valentin Vasseur's QC-MDPC decoder implementation.
-#[cfg(all(
-    any(target_arch = "x86", target_arch = "x86_64"),
-    target_feature = "avx2"
-))]
-fn multiply_avx2(output: &mut [u8], sparse: &[Index], dense: &[bool]) {
-    use safe_arch::{add_i8_m256i, zeroed_m256i};
-    const AVX_BUFF_LEN: usize = 8;
-    let dense: &[u8] = bytemuck::cast_slice(dense);
-    let block_length = dense.len() / 2;
-    // assertions to eliminate the need for bounds checking in the inner loop
-    assert_eq!(dense.len() % (64 * AVX_BUFF_LEN), 0);
-    assert!(output.len() >= block_length);
-    assert!(sparse.iter().all(|idx| (*idx as usize) < block_length));
-    // initialize buffer array of 256-bit integers
-    let mut buffer = [zeroed_m256i(); AVX_BUFF_LEN];
-    for i in (0..block_length / 32).step_by(AVX_BUFF_LEN) {
-        // reset buffer to zero
-        buffer.iter_mut().for_each(|x| *x = zeroed_m256i());
-        for offset in sparse.iter().map(|idx| *idx as usize + 32 * i) {
-            // SAFETY: upper bound <= idx + 32*(i + AVX_BUFF_LEN) <= idx + block_length
-            // < dense.len() due to the above assertions. Also, 0 <= lower bound < upper bound.
-            let dense_slice = unsafe { dense.get_unchecked(offset..offset + 32 * AVX_BUFF_LEN) };
-            for k in 0..AVX_BUFF_LEN {
-                let addend = bytemuck::pod_read_unaligned(&dense_slice[32 * k..32 * k + 32]);
-                // add offset block of dense vector to buffer
-                buffer[k] = add_i8_m256i(buffer[k], addend);
+/// Same as `bf_iter`, but takes the current unsatisfied parity checks as input and
+/// updates them incrementally as bits are flipped instead of recomputing them.
+pub fn bf_iter_incremental(
+    key: &Key,
+    s: &mut Syndrome,
+    e_out: &mut ErrorVector,
+    upc: &mut [[u8; BLOCK_LENGTH]; 2],
+    thr: u8,
+) -> ([Vec<usize>; 2], [Vec<usize>; 2]) {
+    // Flip decisions are based on the counts at the start of the iteration
+    let upc_start = *upc;
+    let gray_thr = thr.saturating_sub(GRAY_THRESHOLD_DIFF);
+    let mut black = [
+        Vec::with_capacity(BLOCK_LENGTH),
+        Vec::with_capacity(BLOCK_LENGTH),
+    ];
+    let mut gray = [
+        Vec::with_capacity(BLOCK_LENGTH),
+        Vec::with_capacity(BLOCK_LENGTH),
+    ];
+    for (k, upc_k) in upc_start.iter().enumerate() {
+        for (i, upc_ki) in upc_k
+            .iter()
+            .enumerate()
+            .filter(|&(_, upc_ki)| *upc_ki >= gray_thr)
+        {
+            if *upc_ki >= thr {
+                e_out.flip(i + k * BLOCK_LENGTH);
+                s.recompute_flipped_bit_with_upc(key, k, i, upc);
+                black[k].push(i);
+            } else {
+                gray[k].push(i);
+            }
+        }
+    }
+    (black, gray)
+}
+
+/// Same as `bf_iter_no_mask`, but updates the unsatisfied parity checks incrementally.
+pub fn bf_iter_no_mask_incremental(
+    key: &Key,
+    s: &mut Syndrome,
+    e_out: &mut ErrorVector,
+    upc: &mut [[u8; BLOCK_LENGTH]; 2],
+    thr: u8,
+) {
+    let upc_start = *upc;
+    for (k, upc_k) in upc_start.iter().enumerate() {
+        for (i, _) in upc_k
+            .iter()
+            .enumerate()
+            .filter(|&(_, upc_ki)| *upc_ki >= thr)
+        {
+            e_out.flip(i + k * BLOCK_LENGTH);
+            s.recompute_flipped_bit_with_upc(key, k, i, upc);
+        }
+    }
+}
+
+/// Same as `bf_masked_iter`, but updates the unsatisfied parity checks incrementally.
+pub fn bf_masked_iter_incremental(
+    key: &Key,
+    s: &mut Syndrome,
+    e_out: &mut ErrorVector,
+    upc: &mut [[u8; BLOCK_LENGTH]; 2],
+    mask: [Vec<usize>; 2],
+    thr: u8,
+) {
+    let upc_start = *upc;
+    for k in 0..2 {
+        for &i in mask[k].iter() {
+            if upc_start[k][i] >= thr {
+                e_out.flip(i + k * BLOCK_LENGTH);
+                s.recompute_flipped_bit_with_upc(key, k, i, upc);
+            }
+        }
+    }
+}
+
+/// Multiplies a sparse vector by a dense vector. The dense vector should be
+/// duplicated in memory to precompute cyclic shifts. The results are stored in
+/// the provided output buffer.
+/// Adapted from Valentin Vasseur's QC-MDPC decoder implementation.
+#[cfg(all(
+    any(target_arch = "x86", target_arch = "x86_64"),
+    target_feature = "avx2"
+))]
+fn multiply_avx2(output: &mut [u8], sparse: &[Index], dense: &[bool]) {
+    use safe_arch::{add_i8_m256i, zeroed_m256i};
+    const AVX_BUFF_LEN: usize = 8;
+    let dense: &[u8] = bytemuck::cast_slice(dense);
+    let block_length = dense.len() / 2;
+    // assertions to eliminate the need for bounds checking in the inner loop
+    assert_eq!(dense.len() % (64 * AVX_BUFF_LEN), 0);
+    assert!(output.len() >= block_length);
+    assert!(sparse.iter().all(|idx| (*idx as usize) < block_length));
+    // initialize buffer array of 256-bit integers
+    let mut buffer = [zeroed_m256i(); AVX_BUFF_LEN];
+    for i in (0..block_length / 32).step_by(AVX_BUFF_LEN) {
+        // reset buffer to zero
+        buffer.iter_mut().for_each(|x| *x = zeroed_m256i());
+        for offset in sparse.iter().map(|idx| *idx as usize + 32 * i) {
+            // SAFETY: upper bound <= idx + 32*(i + AVX_BUFF_LEN) <= idx + block_length
+            // < dense.len() due to the above assertions. Also, 0 <= lower bound < upper bound.
+            let dense_slice = unsafe { dense.get_unchecked(offset..offset + 32 * AVX_BUFF_LEN) };
+            for k in 0..AVX_BUFF_LEN {
+                let addend = bytemuck::pod_read_unaligned(&dense_slice[32 * k..32 * k + 32]);
+                // add offset block of dense vector to buffer
+                buffer[k] = add_i8_m256i(buffer[k], addend);
             }
         }
         // SAFETY: upper bound = 32*(i + AVX_BUFF_LEN) <= block_length <= output.len()
@@ -401,12 +1343,502 @@ fn multiply_avx2(output: &mut [u8], sparse: &[Index], dense: &[bool]) {
     }
 }
 
+/// Multiplies a sparse vector by a dense vector using 128-bit NEON registers. Has the
+/// same requirements as `multiply_avx2`: the dense vector should be duplicated in memory
+/// to precompute cyclic shifts, and the results are stored in the provided output buffer.
+#[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+fn multiply_neon(output: &mut [u8], sparse: &[Index], dense: &[bool]) {
+    use std::arch::aarch64::{vaddq_u8, vdupq_n_u8, vld1q_u8, vst1q_u8};
+    const NEON_BUFF_LEN: usize = 16;
+    let dense: &[u8] = bytemuck::cast_slice(dense);
+    let block_length = dense.len() / 2;
+    // assertions to ensure that all memory accesses in the inner loop are in bounds
+    assert_eq!(dense.len() % (32 * NEON_BUFF_LEN), 0);
+    assert!(output.len() >= block_length);
+    assert!(sparse.iter().all(|idx| (*idx as usize) < block_length));
+    for i in (0..block_length / 16).step_by(NEON_BUFF_LEN) {
+        // SAFETY: The NEON target feature is enabled. For each offset, the loads read
+        // dense[offset..offset + 16*NEON_BUFF_LEN], and the upper bound is at most
+        // idx + 16*(i + NEON_BUFF_LEN) <= idx + block_length < dense.len() due to the
+        // above assertions. Similarly, the stores write output[16*i..16*(i + NEON_BUFF_LEN)],
+        // and 16*(i + NEON_BUFF_LEN) <= block_length <= output.len().
+        unsafe {
+            // initialize buffer array of 128-bit integers
+            let mut buffer = [vdupq_n_u8(0); NEON_BUFF_LEN];
+            for offset in sparse.iter().map(|idx| *idx as usize + 16 * i) {
+                let dense_ptr = dense.as_ptr().add(offset);
+                for (k, buf) in buffer.iter_mut().enumerate() {
+                    // add offset block of dense vector to buffer
+                    *buf = vaddq_u8(*buf, vld1q_u8(dense_ptr.add(16 * k)));
+                }
+            }
+            // copy buffer contents to output slice
+            let output_ptr = output.as_mut_ptr().add(16 * i);
+            for (k, buf) in buffer.iter().enumerate() {
+                vst1q_u8(output_ptr.add(16 * k), *buf);
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     const TRIALS: usize = 1000;
 
+    // Makes the copy of the syndrome kept in its buffer stale, so that upc_computed can tell
+    // whether the unsatisfied parity checks, which refresh the copy, were computed since.
+    fn mark_buffer_stale(s: &mut Syndrome) {
+        s.flip(0);
+        s.duplicate_contents();
+        s.flip(0);
+    }
+
+    // Whether the unsatisfied parity checks were computed since mark_buffer_stale was called
+    // on s_before. Bits flipped after computing them only change the first copy.
+    fn upc_computed(s_before: &Syndrome, s: &Syndrome) -> bool {
+        s.contents_with_buffer()[BLOCK_LENGTH] == s_before.contents()[0]
+    }
+
+    // Same as RecomputeUpc, but counts the steps that compute the unsatisfied parity checks
+    struct CountUpc(usize);
+
+    impl CountUpc {
+        fn step<T>(&mut self, s: &mut Syndrome, f: impl FnOnce(&mut Syndrome) -> T) -> T {
+            mark_buffer_stale(s);
+            let s_before = s.clone();
+            let result = f(s);
+            self.0 += usize::from(upc_computed(&s_before, s));
+            result
+        }
+    }
+
+    impl BitFlipper for CountUpc {
+        fn iter_tau(
+            &mut self,
+            key: &Key,
+            s: &mut Syndrome,
+            e_out: &mut ErrorVector,
+            thr: u8,
+            tau: u8,
+        ) -> ([Vec<usize>; 2], [Vec<usize>; 2]) {
+            self.step(s, |s| bf_iter_tau(key, s, e_out, thr, tau))
+        }
+
+        fn masked_iter(
+            &mut self,
+            key: &Key,
+            s: &mut Syndrome,
+            e_out: &mut ErrorVector,
+            mask: [Vec<usize>; 2],
+            thr: u8,
+        ) {
+            self.step(s, |s| bf_masked_iter(key, s, e_out, mask, thr));
+        }
+
+        fn iter_no_mask(&mut self, key: &Key, s: &mut Syndrome, e_out: &mut ErrorVector, thr: u8) {
+            self.step(s, |s| bf_iter_no_mask(key, s, e_out, thr));
+        }
+    }
+
+    #[test]
+    fn runtime_thresholds_match() {
+        let cache = ThresholdCache::new(BLOCK_LENGTH, BLOCK_WEIGHT, ERROR_WEIGHT).unwrap();
+        assert_eq!(&cache, &*THRESHOLD_CACHE);
+        for ws in 0..=BLOCK_LENGTH {
+            assert_eq!(cache[ws], THRESHOLD_CACHE[ws]);
+        }
+    }
+
+    #[test]
+    fn decode_batch_matches() {
+        assert_eq!((BLOCK_LENGTH, BLOCK_WEIGHT, ERROR_WEIGHT), (587, 15, 18));
+        let mut rng = rand::thread_rng();
+        // Key and error vector from bgf_cycle_example, which is a decoding failure
+        let key = Key::from_support(
+            [
+                93, 99, 105, 121, 126, 141, 156, 193, 194, 197, 264, 301, 360, 400, 429,
+            ],
+            [
+                100, 117, 189, 191, 211, 325, 340, 386, 440, 461, 465, 474, 534, 565, 578,
+            ],
+        )
+        .unwrap();
+        let e_fail = SparseErrorVector::from_support([
+            16, 73, 89, 201, 346, 522, 547, 553, 574, 575, 613, 619, 637, 713, 955, 960, 983, 1008,
+        ])
+        .unwrap();
+        let mut errors: Vec<SparseErrorVector> = (0..TRIALS)
+            .map(|_| SparseErrorVector::random(&mut rng))
+            .collect();
+        // Check that the buffers are reset properly after a decoding failure
+        errors.insert(TRIALS / 2, e_fail.clone());
+        errors.push(e_fail);
+        let results = decode_batch(&key, &errors);
+        assert_eq!(results.len(), errors.len());
+        assert!(!results[TRIALS / 2] && !results[TRIALS + 1]);
+        for (e_in, success) in errors.iter().zip(results) {
+            let mut syn = Syndrome::from_sparse(&key, e_in);
+            let (e_out, _) = bgf_decoder(&key, &mut syn);
+            assert_eq!(success, e_out == e_in.dense());
+        }
+    }
+
+    #[test]
+    fn correction_radius_weight_one() {
+        let mut rng = rand::thread_rng();
+        let key = Key::random(&mut rng);
+        assert!(
+            verify_correction_radius::<BLOCK_WEIGHT, BLOCK_LENGTH, 0, ROW_LENGTH>(&key).is_none()
+        );
+        assert!(
+            verify_correction_radius::<BLOCK_WEIGHT, BLOCK_LENGTH, 1, ROW_LENGTH>(&key).is_none()
+        );
+    }
+
+    #[test]
+    fn correction_radius_small_code() {
+        // No two columns of this parity check matrix share more than one check, so a single
+        // error always has strictly more unsatisfied parity checks than any other bit.
+        let key = QuasiCyclic::<3, 13>::from_support([0, 1, 4], [0, 2, 7]).unwrap();
+        assert!(verify_correction_radius::<3, 13, 0, 26>(&key).is_none());
+        assert!(verify_correction_radius::<3, 13, 1, 26>(&key).is_none());
+        // Weight 2 is beyond the correction radius of such a small code
+        assert!(verify_correction_radius::<3, 13, 2, 26>(&key).is_some());
+    }
+
+    #[test]
+    fn threshold_rules() {
+        let mut rng = rand::thread_rng();
+        let cache = ThresholdCache::new(BLOCK_LENGTH, BLOCK_WEIGHT, ERROR_WEIGHT).unwrap();
+        for _ in 0..TRIALS {
+            let key = Key::random(&mut rng);
+            let e_in = SparseErrorVector::random(&mut rng);
+            let mut syn = Syndrome::from_sparse(&key, &e_in);
+            let mut syn_cached = syn.clone();
+            let mut syn_runtime = syn.clone();
+            let expected = bgf_decoder(&key, &mut syn);
+            assert_eq!(
+                bgf_decoder_with_rule(&key, &mut syn_cached, &CachedThreshold),
+                expected
+            );
+            assert_eq!(
+                bgf_decoder_with_rule(&key, &mut syn_runtime, &cache),
+                expected
+            );
+            assert_eq!(syn_cached, syn);
+            assert_eq!(syn_runtime, syn);
+        }
+        // If even the gray threshold is above the column weight, no bits are ever flipped
+        let key = Key::random(&mut rng);
+        let e_in = SparseErrorVector::random(&mut rng);
+        let mut syn = Syndrome::from_sparse(&key, &e_in);
+        let never_flip = crate::threshold::AffineThreshold {
+            slope: 0.0,
+            intercept: (BLOCK_WEIGHT + GRAY_THRESHOLD_DIFF as usize + 1) as f64,
+        };
+        let (e_out, success) = bgf_decoder_with_rule(&key, &mut syn, &never_flip);
+        assert!(!success);
+        assert_eq!(e_out.hamming_weight(), 0);
+    }
+
+    #[test]
+    fn benchmark_smoke() {
+        let mut rng = rand::thread_rng();
+        let key = Key::random(&mut rng);
+        let elapsed = benchmark(&key, 10, &mut rng);
+        assert!(elapsed > Duration::ZERO);
+        assert!(decodes_per_second(10, elapsed) > 0.0);
+        assert_eq!(decodes_per_second(10, Duration::ZERO), 0.0);
+        assert_eq!(decodes_per_second(0, Duration::ZERO), 0.0);
+        assert_eq!(decodes_per_second(10, Duration::from_secs(2)), 5.0);
+        assert_eq!(benchmark(&key, 0, &mut rng), Duration::ZERO);
+    }
+
+    #[test]
+    fn explain_failure_cycle() {
+        let key = Key::from_support(
+            [
+                93, 99, 105, 121, 126, 141, 156, 193, 194, 197, 264, 301, 360, 400, 429,
+            ],
+            [
+                100, 117, 189, 191, 211, 325, 340, 386, 440, 461, 465, 474, 534, 565, 578,
+            ],
+        )
+        .unwrap();
+        let e_in = SparseErrorVector::from_support([
+            16, 73, 89, 201, 346, 522, 547, 553, 574, 575, 613, 619, 637, 713, 955, 960, 983, 1008,
+        ])
+        .unwrap();
+        let expected = find_bgf_cycle(&key, &e_in, EXPLAIN_FAILURE_MAX_ITERS)
+            .cycle()
+            .unwrap();
+        assert_eq!(expected.length, 2);
+        assert_eq!(explain_failure(&key, &e_in), FailureReason::Cycle(expected));
+        let mut rng = rand::thread_rng();
+        for _ in 0..TRIALS / 10 {
+            let key = Key::random(&mut rng);
+            let e_in = SparseErrorVector::random(&mut rng);
+            let mut syn = Syndrome::from_sparse(&key, &e_in);
+            let (_, success, trajectory) = bgf_decoder_traced(&key, &mut syn);
+            if success {
+                // The trajectory has three entries for the first iteration
+                assert_eq!(
+                    explain_failure(&key, &e_in),
+                    FailureReason::Converged {
+                        iterations: trajectory.len() - 2
+                    }
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn warm_start_decoder() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..TRIALS {
+            let key = Key::random(&mut rng);
+            let e_in = SparseErrorVector::random(&mut rng);
+            let syn = Syndrome::from_sparse(&key, &e_in);
+            // Starting from zero is the same as the usual decoder
+            let (mut syn_zero, mut syn_default) = (syn.clone(), syn.clone());
+            assert_eq!(
+                bgf_decoder_from(&key, &mut syn_zero, &ErrorVector::zero()),
+                bgf_decoder(&key, &mut syn_default)
+            );
+            assert_eq!(syn_zero, syn_default);
+            // Starting from the correct answer succeeds immediately
+            let mut syn_correct = syn.clone();
+            let (e_out, success) = bgf_decoder_from(&key, &mut syn_correct, &e_in.dense());
+            assert!(success);
+            assert_eq!(e_out, e_in.dense());
+            assert_eq!(syn_correct, Syndrome::zero());
+        }
+    }
+
+    #[test]
+    fn decoder_deltas_accumulate() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..TRIALS {
+            let key = Key::random(&mut rng);
+            let e_in = SparseErrorVector::random(&mut rng);
+            let mut syn = Syndrome::from_sparse(&key, &e_in);
+            let mut syn_deltas = syn.clone();
+            let (e_out, success) = bgf_decoder(&key, &mut syn);
+            let (e_out_deltas, success_deltas, deltas) = bgf_decoder_deltas(&key, &mut syn_deltas);
+            assert_eq!((&e_out, success), (&e_out_deltas, success_deltas));
+            assert!(!deltas.is_empty() && deltas.len() <= NB_ITER);
+            assert!(success || deltas.len() == NB_ITER);
+            let mut e_acc = ErrorVector::zero();
+            for delta in deltas {
+                for idx in delta {
+                    e_acc.flip(idx as usize);
+                }
+            }
+            assert_eq!(e_acc.support(), e_out.support());
+        }
+    }
+
+    #[test]
+    fn decoder_traced_matches() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..TRIALS {
+            let key = Key::random(&mut rng);
+            let e_in = SparseErrorVector::random(&mut rng);
+            let mut syn = Syndrome::from_sparse(&key, &e_in);
+            let mut syn_traced = syn.clone();
+            let (e_out, success) = bgf_decoder(&key, &mut syn);
+            let (e_out_traced, success_traced, trajectory) =
+                bgf_decoder_traced(&key, &mut syn_traced);
+            assert_eq!((&e_out, success), (&e_out_traced, success_traced));
+            assert_eq!(syn, syn_traced);
+            assert!(trajectory.len() >= 3 && trajectory.len() <= NB_ITER + 2);
+            assert_eq!(*trajectory.last().unwrap(), syn.hamming_weight());
+            assert!(success || trajectory.len() == NB_ITER + 2);
+        }
+    }
+
+    #[test]
+    fn fixed_thresholds_match_cache() {
+        // Thresholds the standard decoder uses: iteration 0 uses the initial syndrome weight,
+        // and iteration i >= 1 the syndrome weight after iteration i - 1.
+        fn cache_thresholds(key: &Key, e_in: &SparseErrorVector) -> Vec<u8> {
+            let mut syn = Syndrome::from_sparse(key, e_in);
+            let initial_ws = syn.hamming_weight();
+            let (_, _, trajectory) = bgf_decoder_traced(key, &mut syn);
+            std::iter::once(initial_ws)
+                .chain(trajectory[2..].iter().copied())
+                .take(NB_ITER)
+                .map(|ws| THRESHOLD_CACHE[ws])
+                .collect()
+        }
+        let key = Key::from_support(
+            [
+                93, 99, 105, 121, 126, 141, 156, 193, 194, 197, 264, 301, 360, 400, 429,
+            ],
+            [
+                100, 117, 189, 191, 211, 325, 340, 386, 440, 461, 465, 474, 534, 565, 578,
+            ],
+        )
+        .unwrap();
+        let e_in = SparseErrorVector::from_support([
+            16, 73, 89, 201, 346, 522, 547, 553, 574, 575, 613, 619, 637, 713, 955, 960, 983, 1008,
+        ])
+        .unwrap();
+        let mut rng = rand::thread_rng();
+        let samples = std::iter::once((key, e_in)).chain(
+            (0..TRIALS).map(|_| (Key::random(&mut rng), SparseErrorVector::random(&mut rng))),
+        );
+        for (key, e_in) in samples {
+            let thresholds = cache_thresholds(&key, &e_in);
+            let mut syn = Syndrome::from_sparse(&key, &e_in);
+            let mut syn_fixed = syn.clone();
+            let expected = bgf_decoder(&key, &mut syn);
+            assert_eq!(
+                bgf_decoder_fixed_thresholds(&key, &mut syn_fixed, &thresholds),
+                expected
+            );
+            assert_eq!(syn, syn_fixed);
+        }
+        let key = Key::random(&mut rng);
+        let mut syn = Syndrome::from_sparse(&key, &SparseErrorVector::random(&mut rng));
+        let (e_out, success) = bgf_decoder_fixed_thresholds(&key, &mut syn, &[]);
+        assert_eq!((e_out.hamming_weight(), success), (0, false));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn tracing_iteration_events() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+        use tracing::{span, Event, Metadata, Subscriber};
+
+        // Counts the events recording a decoder iteration
+        struct IterationCounter(Arc<AtomicUsize>);
+
+        impl Subscriber for IterationCounter {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, _: &span::Attributes<'_>) -> span::Id {
+                span::Id::from_u64(1)
+            }
+            fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+            fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+            fn event(&self, event: &Event<'_>) {
+                if event.metadata().fields().field("iteration").is_some() {
+                    self.0.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            fn enter(&self, _: &span::Id) {}
+            fn exit(&self, _: &span::Id) {}
+        }
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..TRIALS / 10 {
+            let key = Key::random(&mut rng);
+            let mut syn = Syndrome::from_sparse(&key, &SparseErrorVector::random(&mut rng));
+            let (_, _, deltas) = bgf_decoder_deltas(&key, &mut syn.clone());
+            let count = Arc::new(AtomicUsize::new(0));
+            tracing::subscriber::with_default(IterationCounter(count.clone()), || {
+                bgf_decoder(&key, &mut syn);
+            });
+            assert_eq!(count.load(Ordering::Relaxed), deltas.len());
+        }
+    }
+
+    #[test]
+    fn masked_iter_empty_mask() {
+        const BF_MASKED_THRESHOLD: u8 = bf_masked_threshold(BLOCK_WEIGHT);
+        let mut rng = rand::thread_rng();
+        let mut skipped = 0;
+        for trial in 0..TRIALS {
+            let key = Key::random(&mut rng);
+            // Use low weight error vectors some of the time, which often produce empty masks
+            let mut e_in = ErrorVector::zero();
+            let weight = if trial % 2 == 0 {
+                trial % 3
+            } else {
+                ERROR_WEIGHT
+            };
+            for idx in SparseErrorVector::random(&mut rng)
+                .support()
+                .iter()
+                .take(weight)
+            {
+                e_in.flip(*idx as usize);
+            }
+            let mut syn = Syndrome::from_dense(&key, &e_in);
+            let mut e_out = ErrorVector::zero();
+            let thr = THRESHOLD_CACHE[syn.hamming_weight()];
+            let (black, gray) = bf_iter(&key, &mut syn, &mut e_out, thr);
+            for mask in [black, gray] {
+                let (syn_before, e_out_before) = (syn.clone(), e_out.clone());
+                let upc = unsatisfied_parity_checks(&key, &mut syn);
+                let expected_flips = (0..2)
+                    .flat_map(|k| mask[k].iter().map(move |&i| (k, i)))
+                    .filter(|&(k, i)| upc[k][i] >= BF_MASKED_THRESHOLD)
+                    .count();
+                let mask_empty = mask.iter().all(Vec::is_empty);
+                let mut counter = CountUpc(0);
+                counter.step(&mut syn, |syn| {
+                    bf_masked_iter(&key, syn, &mut e_out, mask, BF_MASKED_THRESHOLD)
+                });
+                assert_eq!(counter.0, usize::from(!mask_empty));
+                skipped += 1 - counter.0;
+                let e_diff = e_out.clone().add_mod2(e_out_before);
+                assert_eq!(e_diff.support().len(), expected_flips);
+                if expected_flips == 0 {
+                    assert_eq!(syn, syn_before);
+                }
+            }
+        }
+        // Both masks are empty whenever the error vector is zero
+        assert!(skipped >= 2 * TRIALS / 6);
+    }
+
+    #[test]
+    fn bgf_decoder_skips_upc_for_empty_masks() {
+        let mut rng = rand::thread_rng();
+        for trial in 0..TRIALS {
+            let key = Key::random(&mut rng);
+            let mut e_in = ErrorVector::zero();
+            for idx in SparseErrorVector::random(&mut rng)
+                .support()
+                .iter()
+                .take(trial % (ERROR_WEIGHT + 1))
+            {
+                e_in.flip(*idx as usize);
+            }
+            let mut syn = Syndrome::from_dense(&key, &e_in);
+            let (mut syn_ref, mut e_ref) = (syn.clone(), ErrorVector::zero());
+            let thr = THRESHOLD_CACHE[syn_ref.hamming_weight()];
+            let (black, gray) = bf_iter(&key, &mut syn_ref, &mut e_ref, thr);
+            let nonempty_masks = [&black, &gray]
+                .iter()
+                .filter(|mask| mask.iter().any(|v| !v.is_empty()))
+                .count();
+            let mut counter = CountUpc(0);
+            let mut e_out = ErrorVector::zero();
+            let mut later_iterations = 0;
+            bgf_decoder_loop(
+                &key,
+                &mut syn,
+                &mut e_out,
+                NB_ITER,
+                GRAY_THRESHOLD_DIFF,
+                &mut counter,
+                cached_threshold,
+                |step, _, _| later_iterations += usize::from(matches!(step, BgfStep::Iteration(_))),
+            );
+            // One UPC computation per unmasked iteration, plus one per nonempty mask
+            assert_eq!(counter.0, 1 + later_iterations + nonempty_masks);
+        }
+    }
+
     #[test]
     fn upc_all_ones() {
         let mut rng = rand::thread_rng();
@@ -419,6 +1851,282 @@ mod tests {
         }
     }
 
+    #[test]
+    fn upc_histogram_totals() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..TRIALS {
+            let key = Key::random(&mut rng);
+            let mut syn = Syndrome::from_sparse(&key, &SparseErrorVector::random(&mut rng));
+            let histogram = upc_histogram(&key, &mut syn);
+            assert_eq!(histogram.iter().sum::<usize>(), 2 * BLOCK_LENGTH);
+            let thr = THRESHOLD_CACHE[syn.hamming_weight()];
+            let mut e_out = ErrorVector::zero();
+            bf_iter_no_mask(&key, &mut syn, &mut e_out, thr);
+            assert_eq!(
+                histogram[thr as usize..].iter().sum::<usize>(),
+                e_out.hamming_weight()
+            );
+            let mut syn = Syndrome::new([true; BLOCK_LENGTH]);
+            let mut expected = [0; BLOCK_WEIGHT + 1];
+            expected[BLOCK_WEIGHT] = 2 * BLOCK_LENGTH;
+            assert_eq!(upc_histogram(&key, &mut syn), expected);
+        }
+    }
+
+    #[test]
+    fn upc_scalar_matches() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..TRIALS {
+            let key = Key::random(&mut rng);
+            let e_in = SparseErrorVector::random(&mut rng);
+            let mut syn = Syndrome::from_sparse(&key, &e_in);
+            assert_eq!(
+                unsatisfied_parity_checks(&key, &mut syn),
+                unsatisfied_parity_checks_scalar(&key, &mut syn)
+            );
+        }
+    }
+
+    #[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+    #[test]
+    fn upc_neon_matches() {
+        assert!(simd_enabled());
+        let mut rng = rand::thread_rng();
+        for _ in 0..TRIALS {
+            let key = Key::random(&mut rng);
+            let e_in = SparseErrorVector::random(&mut rng);
+            let mut syn = Syndrome::from_sparse(&key, &e_in);
+            syn.duplicate_contents();
+            let mut upc = [[0u8; SIZE_AVX]; 2];
+            multiply_neon(&mut upc[0], key.h0().support(), syn.contents_with_buffer());
+            multiply_neon(&mut upc[1], key.h1().support(), syn.contents_with_buffer());
+            assert_eq!(
+                [truncate_buffer(upc[0]), truncate_buffer(upc[1])],
+                unsatisfied_parity_checks_scalar(&key, &mut syn)
+            );
+        }
+    }
+
+    #[test]
+    fn upc_incremental_matches() {
+        const BF_MASKED_THRESHOLD: u8 = bf_masked_threshold(BLOCK_WEIGHT);
+        let mut rng = rand::thread_rng();
+        for _ in 0..TRIALS / 10 {
+            let key = Key::random(&mut rng);
+            let e_in = SparseErrorVector::random(&mut rng);
+            let mut syn = Syndrome::from_sparse(&key, &e_in);
+            let mut syn_full = syn.clone();
+            let mut e_out = ErrorVector::zero();
+            let mut e_out_full = ErrorVector::zero();
+            let mut upc = unsatisfied_parity_checks(&key, &mut syn);
+            let thr = THRESHOLD_CACHE[syn.hamming_weight()];
+            let (black, gray) = bf_iter_incremental(&key, &mut syn, &mut e_out, &mut upc, thr);
+            let (black_full, gray_full) = bf_iter(&key, &mut syn_full, &mut e_out_full, thr);
+            assert_eq!((&black, &gray), (&black_full, &gray_full));
+            assert_eq!(upc, unsatisfied_parity_checks(&key, &mut syn));
+            for (mask, mask_full) in [(black, black_full), (gray, gray_full)] {
+                let thr = BF_MASKED_THRESHOLD;
+                bf_masked_iter_incremental(&key, &mut syn, &mut e_out, &mut upc, mask, thr);
+                bf_masked_iter(&key, &mut syn_full, &mut e_out_full, mask_full, thr);
+                assert_eq!(upc, unsatisfied_parity_checks(&key, &mut syn));
+            }
+            for _ in 1..NB_ITER {
+                let thr = THRESHOLD_CACHE[syn.hamming_weight()];
+                bf_iter_no_mask_incremental(&key, &mut syn, &mut e_out, &mut upc, thr);
+                bf_iter_no_mask(&key, &mut syn_full, &mut e_out_full, thr);
+                assert_eq!(upc, unsatisfied_parity_checks(&key, &mut syn));
+                assert_eq!(syn, syn_full);
+                assert_eq!(e_out, e_out_full);
+            }
+            let mut syn = Syndrome::from_sparse(&key, &e_in);
+            let mut syn_full = syn.clone();
+            assert_eq!(
+                bgf_decoder_incremental(&key, &mut syn),
+                bgf_decoder(&key, &mut syn_full)
+            );
+        }
+    }
+
+    #[test]
+    fn upc_incremental_low_threshold() {
+        let mut rng = rand::thread_rng();
+        for thr in 0..=GRAY_THRESHOLD_DIFF {
+            let key = Key::random(&mut rng);
+            let e_in = SparseErrorVector::random(&mut rng);
+            let mut syn = Syndrome::from_sparse(&key, &e_in);
+            let mut syn_full = syn.clone();
+            let mut e_out = ErrorVector::zero();
+            let mut e_out_full = ErrorVector::zero();
+            let mut upc = unsatisfied_parity_checks(&key, &mut syn);
+            assert_eq!(
+                bf_iter_incremental(&key, &mut syn, &mut e_out, &mut upc, thr),
+                bf_iter(&key, &mut syn_full, &mut e_out_full, thr)
+            );
+            assert_eq!(upc, unsatisfied_parity_checks(&key, &mut syn));
+            assert_eq!(e_out, e_out_full);
+        }
+    }
+
+    #[test]
+    fn bf_iter_tau_matches() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..TRIALS / 10 {
+            let key = Key::random(&mut rng);
+            let e_in = SparseErrorVector::random(&mut rng);
+            let mut syn = Syndrome::from_sparse(&key, &e_in);
+            let mut syn_tau = syn.clone();
+            let mut e_out = ErrorVector::zero();
+            let mut e_out_tau = ErrorVector::zero();
+            let thr = THRESHOLD_CACHE[syn.hamming_weight()];
+            let masks = bf_iter(&key, &mut syn, &mut e_out, thr);
+            let masks_tau =
+                bf_iter_tau(&key, &mut syn_tau, &mut e_out_tau, thr, GRAY_THRESHOLD_DIFF);
+            assert_eq!(masks, masks_tau);
+            assert_eq!((syn, e_out), (syn_tau, e_out_tau));
+            // With tau = 0 the gray mask is empty, but the black mask is unchanged
+            let mut syn = Syndrome::from_sparse(&key, &e_in);
+            let mut e_out = ErrorVector::zero();
+            let (black, gray) = bf_iter_tau(&key, &mut syn, &mut e_out, thr, 0);
+            assert_eq!(black, masks.0);
+            assert!(gray.iter().all(Vec::is_empty));
+            let mut syn = Syndrome::from_sparse(&key, &e_in);
+            let mut syn_params = syn.clone();
+            assert_eq!(
+                bgf_decoder(&key, &mut syn),
+                bgf_decoder_with_params(&key, &mut syn_params, NB_ITER, GRAY_THRESHOLD_DIFF)
+            );
+        }
+    }
+
+    #[test]
+    fn bf_iter_dense_matches() {
+        let mut rng = rand::thread_rng();
+        let mut black_dense = [[true; BLOCK_LENGTH]; 2];
+        let mut gray_dense = [[true; BLOCK_LENGTH]; 2];
+        for _ in 0..TRIALS / 10 {
+            let key = Key::random(&mut rng);
+            let e_in = SparseErrorVector::random(&mut rng);
+            let mut syn = Syndrome::from_sparse(&key, &e_in);
+            let mut syn_dense = syn.clone();
+            let mut e_out = ErrorVector::zero();
+            let mut e_out_dense = ErrorVector::zero();
+            let thr = THRESHOLD_CACHE[syn.hamming_weight()];
+            let (black, gray) = bf_iter(&key, &mut syn, &mut e_out, thr);
+            bf_iter_dense(
+                &key,
+                &mut syn_dense,
+                &mut e_out_dense,
+                thr,
+                &mut black_dense,
+                &mut gray_dense,
+            );
+            let positions = |mask: &[[bool; BLOCK_LENGTH]; 2]| {
+                mask.map(|mask_k| (0..BLOCK_LENGTH).filter(|&i| mask_k[i]).collect::<Vec<_>>())
+            };
+            assert_eq!(positions(&black_dense), black);
+            assert_eq!(positions(&gray_dense), gray);
+            assert_eq!((syn, e_out), (syn_dense, e_out_dense));
+        }
+    }
+
+    #[test]
+    fn trajectory_length() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..TRIALS {
+            let key = Key::random(&mut rng);
+            let e_in = SparseErrorVector::random(&mut rng);
+            let trajectory = trajectory_subgraphs(&key, &e_in, NB_ITER);
+            assert!(!trajectory.is_empty() && trajectory.len() <= NB_ITER);
+            let mut syn = Syndrome::from_sparse(&key, &e_in);
+            let (_, success) = bgf_decoder(&key, &mut syn);
+            let last = trajectory.last().unwrap();
+            // Decoding stops early exactly when the syndrome becomes zero
+            if trajectory.len() < NB_ITER {
+                assert!(last.is_empty());
+            }
+            assert!(trajectory[..trajectory.len() - 1]
+                .iter()
+                .all(|checks| !checks.is_empty()));
+            assert_eq!(last.is_empty(), success);
+            assert!(last.iter().map(|&idx| idx as usize).eq(syn.support()));
+        }
+        assert!(trajectory_subgraphs(
+            &Key::random(&mut rng),
+            &SparseErrorVector::random(&mut rng),
+            0
+        )
+        .is_empty());
+    }
+
+    // Key and error vector of bgf_cycle_example
+    fn cycle_example() -> (Key, SparseErrorVector) {
+        let key = Key::from_support(
+            [
+                93, 99, 105, 121, 126, 141, 156, 193, 194, 197, 264, 301, 360, 400, 429,
+            ],
+            [
+                100, 117, 189, 191, 211, 325, 340, 386, 440, 461, 465, 474, 534, 565, 578,
+            ],
+        )
+        .unwrap();
+        let e_in = SparseErrorVector::from_support([
+            16, 73, 89, 201, 346, 522, 547, 553, 574, 575, 613, 619, 637, 713, 955, 960, 983, 1008,
+        ])
+        .unwrap();
+        (key, e_in)
+    }
+
+    #[test]
+    fn bgf_decoder_with_iters_example() {
+        let (key, e_in) = cycle_example();
+        let mut syn = Syndrome::from_sparse(&key, &e_in);
+        let mut syn_iters = syn.clone();
+        let (e_out, success) = bgf_decoder(&key, &mut syn);
+        assert!(!success);
+        assert_eq!(
+            bgf_decoder_with_iters(&key, &mut syn_iters, NB_ITER),
+            (e_out, success)
+        );
+        assert_eq!(syn, syn_iters);
+    }
+
+    #[test]
+    fn trajectory_subgraphs_example() {
+        let (key, e_in) = cycle_example();
+        let trajectory = trajectory_subgraphs(&key, &e_in, 100);
+        let mut syn = Syndrome::from_sparse(&key, &e_in);
+        bgf_decoder_with_iters(&key, &mut syn, 100);
+        assert_eq!(syn.hamming_weight(), trajectory.last().unwrap().len());
+        let mut syn = Syndrome::from_sparse(&key, &e_in);
+        bgf_decoder_with_iters(&key, &mut syn, 0);
+        assert_eq!(syn.hamming_weight(), trajectory[0].len());
+        assert_eq!(trajectory.len(), 100);
+        // The decoder enters a 2-cycle after iteration 25
+        assert_eq!(trajectory[27], trajectory[25]);
+        assert_eq!(trajectory.last().unwrap().len(), 101);
+    }
+
+    #[test]
+    fn bgf_decoder_history_example() {
+        let (key, e_in) = cycle_example();
+        let mut syn = Syndrome::from_sparse(&key, &e_in);
+        let history = bgf_decoder_history_with_iters(&key, &mut syn, 100);
+        assert_eq!(history.len(), 102);
+        assert_eq!(syn.hamming_weight(), 101);
+        // Iteration i >= 1 is history[i + 2]. The decoder enters a 2-cycle after
+        // iteration 25, so from iteration 26 on it flips the same set of bits every time.
+        assert_ne!(history[27], history[28]);
+        assert!(!history[28].is_empty());
+        assert!(history[28..].iter().all(|flips| flips == &history[28]));
+        let mut e_acc = ErrorVector::zero();
+        for &idx in history[..28].iter().flatten() {
+            e_acc.flip(idx);
+        }
+        assert_eq!(e_acc.support(), find_bgf_cycle(&key, &e_in, 100).e_out);
+        let mut syn = Syndrome::from_sparse(&key, &e_in);
+        assert_eq!(bgf_decoder_history(&key, &mut syn), history[..NB_ITER + 2]);
+    }
+
     #[test]
     fn bgf_cycle_example() {
         assert_eq!((BLOCK_LENGTH, BLOCK_WEIGHT, ERROR_WEIGHT), (587, 15, 18));
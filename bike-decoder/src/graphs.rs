@@ -7,6 +7,7 @@ use crate::{
 use getset::Getters;
 use petgraph::graph::{NodeIndex, UnGraph};
 use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, fmt::Write};
 
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum Node {
@@ -38,17 +39,6 @@ impl From<CheckNode> for usize {
     }
 }
 
-impl From<Node> for NodeIndex {
-    fn from(node: Node) -> Self {
-        Self::new(match node {
-            Node::Check(CheckNode(idx)) => idx as usize,
-            // Bitwise negate the indices for variable nodes so they don't overlap
-            // with check node indices.
-            Node::Variable(VariableNode(idx)) => !(idx as usize),
-        })
-    }
-}
-
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct TannerGraphEdges<const WEIGHT: usize, const LENGTH: usize>(
     Vec<[(VariableNode, CheckNode); WEIGHT]>,
@@ -78,16 +68,140 @@ pub fn tanner_graph_edges<const WEIGHT: usize, const LENGTH: usize>(
     edges
 }
 
+/// Index of a node in the graph returned by `tanner_graph`: check nodes come
+/// first, followed by the variable nodes.
+#[inline]
+pub fn tanner_node_index<const LENGTH: usize>(node: Node) -> NodeIndex {
+    NodeIndex::new(match node {
+        Node::Check(CheckNode(idx)) => idx as usize,
+        Node::Variable(VariableNode(idx)) => LENGTH + idx as usize,
+    })
+}
+
 pub fn tanner_graph<const WEIGHT: usize, const LENGTH: usize>(
     key: &QuasiCyclic<WEIGHT, LENGTH>,
 ) -> UnGraph<(), ()> {
     let edges = tanner_graph_edges(key)
         .into_iter()
         .flatten()
-        .map(|(var, check)| (Node::from(var), Node::from(check)));
+        .map(|(var, check)| {
+            (
+                tanner_node_index::<LENGTH>(var.into()),
+                tanner_node_index::<LENGTH>(check.into()),
+            )
+        });
     UnGraph::<(), ()>::from_edges(edges)
 }
 
+/// Renders the Tanner graph of the key in GraphViz DOT format, with variable nodes `v0`,
+/// `v1`, ... drawn as circles and check nodes `c0`, `c1`, ... drawn as boxes. The variable
+/// nodes in `highlight` (e.g., the support of an absorbing set) are filled in red.
+pub fn to_dot<const WEIGHT: usize, const LENGTH: usize>(
+    key: &QuasiCyclic<WEIGHT, LENGTH>,
+    highlight: &[Index],
+) -> String {
+    let mut dot = String::from("graph tanner {\n");
+    for var in 0..2 * LENGTH as Index {
+        let style = if highlight.contains(&var) {
+            ", style=filled, fillcolor=red"
+        } else {
+            ""
+        };
+        writeln!(dot, "    v{var} [label=\"v{var}\", shape=circle{style}];")
+            .expect("Writing to a String should not fail");
+    }
+    for check in 0..LENGTH {
+        writeln!(dot, "    c{check} [label=\"c{check}\", shape=box];")
+            .expect("Writing to a String should not fail");
+    }
+    for (VariableNode(var), CheckNode(check)) in tanner_graph_edges(key).into_iter().flatten() {
+        writeln!(dot, "    v{var} -- c{check};").expect("Writing to a String should not fail");
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/// Computes the girth (length of the shortest cycle) of the Tanner graph of the
+/// key, or returns `None` if the Tanner graph has no cycles.
+pub fn girth<const WEIGHT: usize, const LENGTH: usize>(
+    key: &QuasiCyclic<WEIGHT, LENGTH>,
+) -> Option<usize> {
+    let graph = tanner_graph(key);
+    // Every cycle passes through a variable node, and the Tanner graph is invariant
+    // under simultaneous cyclic shifts of both blocks, so it suffices to search for
+    // cycles through the first variable node of each block.
+    [VariableNode(0), VariableNode(LENGTH as Index)]
+        .into_iter()
+        .filter_map(|var| shortest_cycle_from(&graph, tanner_node_index::<LENGTH>(var.into())))
+        .min()
+}
+
+// Breadth-first search for the shortest cycle through the source node
+fn shortest_cycle_from(graph: &UnGraph<(), ()>, source: NodeIndex) -> Option<usize> {
+    let mut dist = vec![usize::MAX; graph.node_count()];
+    let mut parent = vec![source; graph.node_count()];
+    let mut queue = VecDeque::from([source]);
+    let mut shortest: Option<usize> = None;
+    dist[source.index()] = 0;
+    while let Some(node) = queue.pop_front() {
+        if matches!(shortest, Some(len) if 2 * dist[node.index()] + 1 >= len) {
+            break;
+        }
+        for next in graph.neighbors(node) {
+            if dist[next.index()] == usize::MAX {
+                dist[next.index()] = dist[node.index()] + 1;
+                parent[next.index()] = node;
+                queue.push_back(next);
+            } else if parent[node.index()] != next {
+                let len = dist[node.index()] + dist[next.index()] + 1;
+                shortest = Some(shortest.map_or(len, |shortest| shortest.min(len)));
+            }
+        }
+    }
+    shortest
+}
+
+/// Counts the cycles of the given length in the Tanner graph of the key.
+pub fn count_cycles_of_length<const WEIGHT: usize, const LENGTH: usize>(
+    key: &QuasiCyclic<WEIGHT, LENGTH>,
+    len: usize,
+) -> usize {
+    // The Tanner graph is bipartite with no repeated edges, so all cycles have even length >= 4
+    if len < 4 || len % 2 == 1 {
+        return 0;
+    }
+    let graph = tanner_graph(key);
+    let mut path = Vec::with_capacity(len);
+    let count: usize = graph
+        .node_indices()
+        .map(|start| {
+            path.clear();
+            path.push(start);
+            count_closed_paths(&graph, &mut path, len)
+        })
+        .sum();
+    // Each cycle is traversed once in each direction
+    count / 2
+}
+
+// Counts the paths of the given length that extend the current path and return to
+// its starting node, only passing through nodes with larger index than the start.
+fn count_closed_paths(graph: &UnGraph<(), ()>, path: &mut Vec<NodeIndex>, len: usize) -> usize {
+    let start = path[0];
+    let current = path[path.len() - 1];
+    let mut count = 0;
+    for next in graph.neighbors(current) {
+        if path.len() == len {
+            count += usize::from(next == start);
+        } else if next > start && !path.contains(&next) {
+            path.push(next);
+            count += count_closed_paths(graph, path, len);
+            path.pop();
+        }
+    }
+    count
+}
+
 #[inline]
 fn subgraph_from_support<const WEIGHT: usize, const LENGTH: usize>(
     edges: &TannerGraphEdges<WEIGHT, LENGTH>,
@@ -119,6 +233,18 @@ pub fn odd_check_node_neighbors<const WEIGHT: usize, const LENGTH: usize>(
     (subgraph, check_node_degrees, odd_check_nodes)
 }
 
+/// Check nodes of the failure subgraph induced by `supp`, i.e., the check nodes with an odd
+/// number of neighbors in `supp`, in increasing order. If `supp` is the support of
+/// `e_in + e_out` for a decoder input `e_in` and error estimate `e_out`, these are the
+/// unsatisfied parity checks.
+pub fn failure_subgraph<const WEIGHT: usize, const LENGTH: usize>(
+    edges: &TannerGraphEdges<WEIGHT, LENGTH>,
+    supp: &[Index],
+) -> Vec<Index> {
+    let (_, _, odd_check_nodes) = odd_check_node_neighbors(edges, supp);
+    odd_check_nodes.into_iter().map(|check| check.0).collect()
+}
+
 pub fn is_absorbing_subgraph<const WEIGHT: usize, const LENGTH: usize>(
     edges: &TannerGraphEdges<WEIGHT, LENGTH>,
     supp: &[Index],
@@ -137,6 +263,52 @@ pub fn is_absorbing_subgraph<const WEIGHT: usize, const LENGTH: usize>(
     true
 }
 
+/// Partitions the variable nodes in `supp` into the connected components of the subgraph of
+/// the Tanner graph induced by `supp` and the neighboring check nodes, i.e., two variable
+/// nodes are in the same component if they're joined by a path of variable nodes in `supp`
+/// each sharing a check node with the next. Each component is sorted, and the components
+/// are ordered by their smallest index.
+pub fn support_components<const WEIGHT: usize, const LENGTH: usize>(
+    edges: &TannerGraphEdges<WEIGHT, LENGTH>,
+    supp: &[Index],
+) -> Vec<Vec<Index>> {
+    // Union-find over positions in supp, joining each variable node with the first variable
+    // node seen adjacent to each of its check nodes
+    fn find(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    let mut parent: Vec<usize> = (0..supp.len()).collect();
+    let mut first_neighbor = vec![usize::MAX; LENGTH];
+    for (pos, &var) in supp.iter().enumerate() {
+        for &(_, CheckNode(check)) in &edges.0[var as usize] {
+            let other = &mut first_neighbor[check as usize];
+            if *other == usize::MAX {
+                *other = pos;
+            } else {
+                let (a, b) = (find(&mut parent, pos), find(&mut parent, *other));
+                parent[a.max(b)] = a.min(b);
+            }
+        }
+    }
+    let mut components: Vec<Vec<Index>> = Vec::new();
+    let mut component_of_root = vec![usize::MAX; supp.len()];
+    let mut order: Vec<usize> = (0..supp.len()).collect();
+    order.sort_unstable_by_key(|&pos| supp[pos]);
+    for pos in order {
+        let root = find(&mut parent, pos);
+        if component_of_root[root] == usize::MAX {
+            component_of_root[root] = components.len();
+            components.push(Vec::new());
+        }
+        components[component_of_root[root]].push(supp[pos]);
+    }
+    components
+}
+
 /// Given an r-by-2r parity check matrix specified by circulant blocks, and a
 /// vector D specified by the support of the vector, this function determines
 /// if the vector D defines an absorbing set.
@@ -149,6 +321,144 @@ pub fn is_absorbing<const WEIGHT: usize, const LENGTH: usize>(
     is_absorbing_subgraph(&edges, supp)
 }
 
+/// Returns the (a, b) parameters of the absorbing set with the given support, where a is
+/// the number of variable nodes in the set and b is the number of check nodes with an odd
+/// number of neighbors in the set, or `None` if the support doesn't define an absorbing set.
+/// Takes the edges of the Tanner graph of the key, so they can be reused for many supports.
+pub fn absorbing_parameters<const WEIGHT: usize, const LENGTH: usize>(
+    edges: &TannerGraphEdges<WEIGHT, LENGTH>,
+    supp: &[Index],
+) -> Option<(usize, usize)> {
+    if is_absorbing_subgraph(edges, supp) {
+        let (_, _, odd_check_nodes) = odd_check_node_neighbors(edges, supp);
+        Some((supp.len(), odd_check_nodes.len()))
+    } else {
+        None
+    }
+}
+
+/// Enumerates the supports of all absorbing sets of the given weight for the key, in
+/// lexicographic order.
+pub fn enumerate_absorbing_sets<const WEIGHT: usize, const LENGTH: usize>(
+    key: &QuasiCyclic<WEIGHT, LENGTH>,
+    weight: usize,
+) -> Vec<Vec<Index>> {
+    let total = binomial(2 * LENGTH as u128, weight as u128);
+    enumerate_absorbing_sets_from(key, weight, 0, total)
+}
+
+/// Same as `enumerate_absorbing_sets`, but only checks the `count` candidate supports starting
+/// from the one with lexicographic rank `start_rank`. Splitting the range of ranks into pieces
+/// allows an enumeration to be sharded or resumed.
+pub fn enumerate_absorbing_sets_from<const WEIGHT: usize, const LENGTH: usize>(
+    key: &QuasiCyclic<WEIGHT, LENGTH>,
+    weight: usize,
+    start_rank: u128,
+    count: u128,
+) -> Vec<Vec<Index>> {
+    let edges = TannerGraphEdges::new(key);
+    combinations_from(2 * LENGTH as Index, weight, start_rank, count)
+        .filter(|supp| is_absorbing_subgraph(&edges, supp))
+        .collect()
+}
+
+/// Iterates over the `count` subsets of `0..n` of size `weight` in lexicographic order, starting
+/// from the one with rank `start_rank`. Stops early if the last subset is reached.
+pub fn combinations_from(n: Index, weight: usize, start_rank: u128, count: u128) -> Combinations {
+    let next = if count == 0 {
+        None
+    } else {
+        unrank_combination(n, weight, start_rank)
+    };
+    Combinations {
+        next,
+        n,
+        remaining: count,
+    }
+}
+
+/// Iterator returned by `combinations_from`.
+#[derive(Clone, Debug)]
+pub struct Combinations {
+    next: Option<Vec<Index>>,
+    n: Index,
+    remaining: u128,
+}
+
+impl Iterator for Combinations {
+    type Item = Vec<Index>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let supp = self.next.take()?;
+        self.remaining -= 1;
+        if self.remaining > 0 {
+            let mut next = supp.clone();
+            if next_combination(&mut next, self.n) {
+                self.next = Some(next);
+            }
+        }
+        Some(supp)
+    }
+}
+
+/// Binomial coefficient, saturating at `u128::MAX`.
+pub fn binomial(n: u128, k: u128) -> u128 {
+    if k > n {
+        return 0;
+    }
+    let k = k.min(n - k);
+    let mut result: u128 = 1;
+    for i in 0..k {
+        // result * (n - i) is always divisible by i + 1
+        match result.checked_mul(n - i) {
+            Some(product) => result = product / (i + 1),
+            None => return u128::MAX,
+        }
+    }
+    result
+}
+
+/// Returns the `weight`-element subset of `0..n` with the given rank in lexicographic order,
+/// or `None` if the rank is out of range.
+fn unrank_combination(n: Index, weight: usize, mut rank: u128) -> Option<Vec<Index>> {
+    let mut supp = Vec::with_capacity(weight);
+    let mut next = 0;
+    for i in 0..weight {
+        let remaining = (weight - i - 1) as u128;
+        loop {
+            if next >= n {
+                return None;
+            }
+            // Number of combinations whose i-th element is `next`
+            let block = binomial(u128::from(n - next - 1), remaining);
+            if rank < block {
+                break;
+            }
+            rank -= block;
+            next += 1;
+        }
+        supp.push(next);
+        next += 1;
+    }
+    (rank == 0).then_some(supp)
+}
+
+/// Advances `supp` to the next subset of `0..n` of the same size in lexicographic order.
+/// Returns `false` if `supp` was already the last one.
+fn next_combination(supp: &mut [Index], n: Index) -> bool {
+    let k = supp.len();
+    for i in (0..k).rev() {
+        if supp[i] < n - (k - i) as Index {
+            supp[i] += 1;
+            for j in i + 1..k {
+                supp[j] = supp[j - 1] + 1;
+            }
+            return true;
+        }
+    }
+    false
+}
+
 #[derive(Debug, Clone, Getters, Serialize, Deserialize)]
 #[getset(get = "pub")]
 pub struct AbsorbingDecodingResult {
@@ -188,6 +498,96 @@ mod tests {
     use super::*;
     use crate::decoder::DecodingFailure;
 
+    #[test]
+    fn small_key_girth() {
+        let key =
+            QuasiCyclic::<5, 19>::from_support([2, 4, 13, 16, 17], [3, 4, 5, 13, 14]).unwrap();
+        assert_eq!(girth(&key), Some(4));
+        assert_eq!(count_cycles_of_length(&key, 3), 0);
+        assert_eq!(count_cycles_of_length(&key, 4), 399);
+        assert_eq!(count_cycles_of_length(&key, 6), 8113);
+        let key = QuasiCyclic::<2, 7>::from_support([0, 1], [0, 2]).unwrap();
+        assert_eq!(girth(&key), Some(6));
+        assert_eq!(count_cycles_of_length(&key, 4), 0);
+        assert_eq!(count_cycles_of_length(&key, 6), 7);
+    }
+
+    #[test]
+    fn support_components_disjoint() {
+        // Variable node k of the first block is adjacent to check nodes k and k + 1, and
+        // variable node 7 + k of the second block to check nodes k and k + 2.
+        let key = QuasiCyclic::<2, 7>::from_support([0, 1], [0, 2]).unwrap();
+        let edges = TannerGraphEdges::new(&key);
+        assert_eq!(
+            support_components(&edges, &[11, 1, 3, 0]),
+            vec![vec![0, 1], vec![3, 11]]
+        );
+        assert_eq!(
+            support_components(&edges, &[0, 3, 12]),
+            vec![vec![0, 12], vec![3]]
+        );
+        assert_eq!(support_components(&edges, &[0, 3, 8]), vec![vec![0, 3, 8]]);
+        assert_eq!(support_components(&edges, &[2]), vec![vec![2]]);
+        assert!(support_components(&edges, &[]).is_empty());
+    }
+
+    #[test]
+    fn tanner_graph_dot() {
+        let key =
+            QuasiCyclic::<5, 19>::from_support([2, 4, 13, 16, 17], [3, 4, 5, 13, 14]).unwrap();
+        let dot = to_dot(&key, &[0, 20]);
+        assert!(dot.starts_with("graph tanner {") && dot.ends_with("}\n"));
+        assert_eq!(dot.matches(" -- ").count(), 2 * 19 * 5);
+        assert_eq!(dot.matches("shape=circle").count(), 2 * 19);
+        assert_eq!(dot.matches("shape=box").count(), 19);
+        assert_eq!(dot.matches("fillcolor=red").count(), 2);
+        assert!(dot.contains("v20 [label=\"v20\", shape=circle, style=filled"));
+        assert!(dot.contains("v0 -- c2;"));
+    }
+
+    #[test]
+    fn enumerate_absorbing_sets_resumable() {
+        let key = QuasiCyclic::<2, 7>::from_support([0, 1], [0, 2]).unwrap();
+        let weight = 4;
+        let total = binomial(14, 4);
+        assert_eq!(total, 1001);
+        let full = enumerate_absorbing_sets(&key, weight);
+        assert!(!full.is_empty());
+        let half = total / 2;
+        let mut resumed = enumerate_absorbing_sets_from(&key, weight, 0, half);
+        resumed.extend(enumerate_absorbing_sets_from(
+            &key,
+            weight,
+            half,
+            total - half,
+        ));
+        assert_eq!(resumed, full);
+        assert!(enumerate_absorbing_sets_from(&key, weight, total, 1).is_empty());
+        for supp in &full {
+            assert!(supp.windows(2).all(|w| w[0] < w[1]));
+            assert!(is_absorbing(&key, supp));
+        }
+        assert!(full.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn combination_ranges() {
+        let total = binomial(9, 3);
+        assert_eq!(total, 84);
+        let all: Vec<_> = combinations_from(9, 3, 0, total).collect();
+        assert_eq!(all.len(), 84);
+        assert_eq!(all[0], vec![0, 1, 2]);
+        assert_eq!(all[83], vec![6, 7, 8]);
+        assert!(all.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(combinations_from(9, 3, 80, 10).count(), 4);
+        assert_eq!(combinations_from(9, 3, 10, 0).count(), 0);
+        let chunked: Vec<_> = (0..total)
+            .step_by(10)
+            .flat_map(|start| combinations_from(9, 3, start, 10))
+            .collect();
+        assert_eq!(chunked, all);
+    }
+
     #[test]
     fn absorbing_example() {
         let df: DecodingFailure = serde_json::from_str(
@@ -196,7 +596,15 @@ mod tests {
             481,527,558,662,724,772,1008,1011,1038,1072]}"#,
         )
         .unwrap();
-        AbsorbingDecodingResult::new(df.into()).unwrap();
+        let edges = TannerGraphEdges::new(df.key());
+        let result = AbsorbingDecodingResult::new(df.into()).unwrap();
+        let params = absorbing_parameters(&edges, result.supp());
+        assert_eq!(
+            params,
+            Some((result.supp().len(), result.odd_check_nodes().len()))
+        );
+        assert_eq!(params, Some((4, 28)));
+        assert_eq!(absorbing_parameters(&edges, &[0]), None);
     }
 
     #[test]
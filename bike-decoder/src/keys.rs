@@ -1,9 +1,12 @@
 use crate::parameters::*;
-use crate::vectors::{Index, InvalidSupport, SparseVector};
+use crate::vectors::{DenseVector, Index, InvalidSupport, SparseVector};
 use getset::Getters;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
-use std::fmt;
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+};
 use thiserror::Error;
 
 pub type CyclicBlock<const WEIGHT: usize, const LENGTH: usize> = SparseVector<WEIGHT, LENGTH>;
@@ -34,6 +37,21 @@ impl<const WEIGHT: usize, const LENGTH: usize> QuasiCyclic<WEIGHT, LENGTH> {
         })
     }
 
+    /// Parses a key from hex strings encoding the coefficients of the polynomials h0(x) and
+    /// h1(x) as bitstrings, with the least significant bit of the first byte being the
+    /// coefficient of x^0.
+    pub fn from_hex(h0_hex: &str, h1_hex: &str) -> Result<Self, KeyFromHexError> {
+        Ok(Self {
+            h0: block_from_hex(h0_hex)?,
+            h1: block_from_hex(h1_hex)?,
+        })
+    }
+
+    /// Encodes the key as a pair of hex strings in the format accepted by `from_hex`.
+    pub fn to_hex(&self) -> (String, String) {
+        (block_to_hex(&self.h0), block_to_hex(&self.h1))
+    }
+
     #[inline]
     pub fn take_blocks(self) -> (CyclicBlock<WEIGHT, LENGTH>, CyclicBlock<WEIGHT, LENGTH>) {
         (self.h0, self.h1)
@@ -68,6 +86,123 @@ impl<const WEIGHT: usize, const LENGTH: usize> QuasiCyclic<WEIGHT, LENGTH> {
         self
     }
 
+    /// Returns the key obtained by cyclically shifting both blocks by `delta`. The shifted key
+    /// is equivalent to the original one, since the decoder's behavior on an error vector
+    /// with the original key is the same as on the blockwise shifted vector with the
+    /// shifted key.
+    #[inline]
+    pub fn shifted(&self, delta: Index) -> Self {
+        Self {
+            h0: self.h0.shifted(delta),
+            h1: self.h1.shifted(delta),
+        }
+    }
+
+    /// Returns the key with the coefficients of both blocks in reverse order, for importing
+    /// keys from tools that index circulant coefficients the other way around.
+    #[inline]
+    pub fn reversed(&self) -> Self {
+        Self {
+            h0: self.h0.reversed(),
+            h1: self.h1.reversed(),
+        }
+    }
+
+    /// Returns the canonical representative of the key's equivalence class under
+    /// simultaneous cyclic shifts of both blocks: the shift whose sorted supports
+    /// `(h0, h1)` are lexicographically minimal. Its h0 support always starts with 0.
+    pub fn canonical(&self) -> Self {
+        let length = self.block_length();
+        self.h0
+            .support()
+            .iter()
+            .map(|&idx| self.shifted((length - idx) % length).sorted())
+            .min_by(|key1, key2| {
+                (key1.h0.support(), key1.h1.support()).cmp(&(key2.h0.support(), key2.h1.support()))
+            })
+            .unwrap_or_else(|| self.clone().sorted())
+    }
+
+    /// Returns true if the two keys are related by a simultaneous cyclic shift of both blocks.
+    #[inline]
+    pub fn is_shift_equivalent(&self, other: &Self) -> bool {
+        self.canonical() == other.canonical()
+    }
+
+    /// Returns the total weight of the key, which is always `2 * WEIGHT` for a valid key.
+    #[inline]
+    pub fn total_weight(&self) -> usize {
+        debug_assert!(self.validate().is_ok(), "key should be valid");
+        self.h0.weight() + self.h1.weight()
+    }
+
+    /// Returns for each block the circular gaps between consecutive elements of its sorted
+    /// support, starting with the gap after the smallest element and ending with the gap
+    /// wrapping around from the largest element back to the smallest. The gaps in each
+    /// block sum to `LENGTH`.
+    pub fn block_gaps(&self) -> [Vec<Index>; 2] {
+        [&self.h0, &self.h1].map(|block| {
+            let supp = block.clone().sorted();
+            let supp = supp.support();
+            let length = LENGTH as Index;
+            (0..WEIGHT)
+                .map(|i| {
+                    let next = supp[(i + 1) % WEIGHT];
+                    (next + length - supp[i] - 1) % length + 1
+                })
+                .collect()
+        })
+    }
+
+    /// Returns the Hamming weight of the codeword (h1, h0), which lies in the kernel of the
+    /// parity check matrix (h0, h1). This is the pattern used for the near-codeword class C,
+    /// and is equal to `2 * WEIGHT` for a valid key.
+    pub fn codeword_weight(&self) -> usize {
+        self.h1.dense().hamming_weight() + self.h0.dense().hamming_weight()
+    }
+
+    /// Returns true if the circulant block h0 is invertible, i.e., if h0(x) is a unit
+    /// in the ring GF(2)[x]/(x^r - 1).
+    #[inline]
+    pub fn h0_is_invertible(&self) -> bool {
+        self.h0_inverse().is_some()
+    }
+
+    /// Computes the inverse of the circulant block h0 in GF(2)[x]/(x^r - 1) as a dense
+    /// polynomial, or returns `None` if h0 is singular.
+    pub fn h0_inverse(&self) -> Option<DenseVector<LENGTH>> {
+        // Extended Euclidean algorithm applied to x^r - 1 and h0(x), keeping track of the
+        // coefficients of h0(x) only.
+        let mut modulus = vec![false; LENGTH + 1];
+        modulus[0] = true;
+        modulus[LENGTH] = true;
+        let mut h0 = vec![false; LENGTH];
+        for &idx in self.h0.support() {
+            h0[idx as usize] ^= true;
+        }
+        let (mut r0, mut r1) = (modulus, h0);
+        let (mut t0, mut t1) = (vec![false; LENGTH + 1], vec![false; LENGTH + 1]);
+        t1[0] = true;
+        while let Some(deg_r1) = poly_degree(&r1) {
+            while let Some(deg_r0) = poly_degree(&r0).filter(|&deg_r0| deg_r0 >= deg_r1) {
+                let shift = deg_r0 - deg_r1;
+                poly_add_shifted(&mut r0, &r1, shift);
+                poly_add_shifted(&mut t0, &t1, shift);
+            }
+            std::mem::swap(&mut r0, &mut r1);
+            std::mem::swap(&mut t0, &mut t1);
+        }
+        // r0 is now gcd(x^r - 1, h0(x)), and t0 * h0(x) = r0 mod x^r - 1
+        if poly_degree(&r0) != Some(0) {
+            return None;
+        }
+        let mut inverse = DenseVector::zero();
+        for (idx, _) in t0.iter().enumerate().filter(|(_, &bit)| bit) {
+            inverse.flip(idx % LENGTH);
+        }
+        Some(inverse)
+    }
+
     pub fn matches_filter(&self, key_filter: KeyFilter) -> bool {
         match key_filter {
             KeyFilter::Any => true,
@@ -88,6 +223,18 @@ impl<const WEIGHT: usize, const LENGTH: usize> QuasiCyclic<WEIGHT, LENGTH> {
         self.h0.shifts_above_threshold(threshold) || self.h1.shifts_above_threshold(threshold)
     }
 
+    /// Continuous measure of how close the key is to being a type 2 weak key: the amount by
+    /// which the largest bin of the distance spectra of `h0` and `h1` falls short of
+    /// `threshold`, or 0 if the key is already weak of type 2.
+    pub fn weak_margin_type2(&self, threshold: u8) -> usize {
+        let max_bin = [&self.h0, &self.h1]
+            .into_iter()
+            .flat_map(|block| block.distance_spectrum())
+            .max()
+            .unwrap_or(0);
+        usize::from(threshold).saturating_sub(max_bin as usize)
+    }
+
     pub fn is_weak_type3(&self, threshold: u8) -> bool {
         self.h0.max_shifted_product_weight_geq(&self.h1, threshold)
     }
@@ -96,6 +243,16 @@ impl<const WEIGHT: usize, const LENGTH: usize> QuasiCyclic<WEIGHT, LENGTH> {
         self.is_weak_type2(threshold) || self.is_weak_type3(threshold)
     }
 
+    /// Determines which types of weak key (other than type 1) this key is, with respect
+    /// to the given threshold.
+    pub fn classify_weak(&self, threshold: u8) -> WeakKeyClassification {
+        WeakKeyClassification {
+            threshold,
+            type2: self.is_weak_type2(threshold),
+            type3: self.is_weak_type3(threshold),
+        }
+    }
+
     pub fn random_filtered<R: Rng + ?Sized>(key_filter: KeyFilter, rng: &mut R) -> Self {
         match key_filter {
             KeyFilter::Any => Self::random(rng),
@@ -148,31 +305,139 @@ impl<const WEIGHT: usize, const LENGTH: usize> QuasiCyclic<WEIGHT, LENGTH> {
         }
     }
 
+    #[inline]
     pub fn random_weak_type2<R>(thresh: u8, rng: &mut R) -> Self
     where
         R: Rng + ?Sized,
     {
-        let random_block = CyclicBlock::random(rng);
-        let weak_block = CyclicBlock::random_weak_type2(thresh, rng);
-        if rng.gen::<bool>() {
-            Self {
-                h0: weak_block,
-                h1: random_block,
-            }
+        Self::random_weak_type2_with_params(thresh, WeakKeyParams::default(), rng)
+    }
+
+    /// Same as `random_weak_type2`, but with the position and tightness of the weak
+    /// structure controlled by `params`.
+    pub fn random_weak_type2_with_params<R>(thresh: u8, params: WeakKeyParams, rng: &mut R) -> Self
+    where
+        R: Rng + ?Sized,
+    {
+        let (weak_block, other_block) = if params.exact {
+            // CyclicBlock::random_weak_type2(thresh - 1) has some distance with multiplicity
+            // at least thresh; reject blocks where it's more than thresh.
+            let weak_block = loop {
+                let block = CyclicBlock::random_weak_type2(thresh.saturating_sub(1), rng);
+                if !block.shifts_above_threshold(thresh.saturating_add(1)) {
+                    break block;
+                }
+            };
+            (weak_block, CyclicBlock::random_non_weak_type2(thresh, rng))
         } else {
-            Self {
-                h0: random_block,
-                h1: weak_block,
+            let random_block = CyclicBlock::random(rng);
+            (CyclicBlock::random_weak_type2(thresh, rng), random_block)
+        };
+        let position = params.weak_block.unwrap_or_else(|| {
+            if rng.gen::<bool>() {
+                WeakBlock::H0
+            } else {
+                WeakBlock::H1
             }
+        });
+        match position {
+            WeakBlock::H0 => Self {
+                h0: weak_block,
+                h1: other_block,
+            },
+            WeakBlock::H1 => Self {
+                h0: other_block,
+                h1: weak_block,
+            },
         }
     }
 
+    #[inline]
     pub fn random_weak_type3<R>(thresh: u8, rng: &mut R) -> Self
     where
         R: Rng + ?Sized,
     {
-        let (h0, h1) = CyclicBlock::random_weak_type3(thresh, rng);
-        Self { h0, h1 }
+        Self::random_weak_type3_with_params(thresh, WeakKeyParams::default(), rng)
+    }
+
+    /// Same as `random_weak_type3`, but with the tightness of the weak structure controlled
+    /// by `params`. Since type 3 weakness involves both blocks, `params.weak_block` is ignored.
+    pub fn random_weak_type3_with_params<R>(thresh: u8, params: WeakKeyParams, rng: &mut R) -> Self
+    where
+        R: Rng + ?Sized,
+    {
+        loop {
+            let (h0, h1) = CyclicBlock::random_weak_type3(thresh, rng);
+            if !params.exact || !h0.max_shifted_product_weight_geq(&h1, thresh.saturating_add(1)) {
+                return Self { h0, h1 };
+            }
+        }
+    }
+}
+
+fn block_from_hex<const WEIGHT: usize, const LENGTH: usize>(
+    hex_str: &str,
+) -> Result<CyclicBlock<WEIGHT, LENGTH>, KeyFromHexError> {
+    let bytes = hex::decode(hex_str)?;
+    let mut supp = Vec::with_capacity(WEIGHT);
+    for (i, byte) in bytes.iter().enumerate() {
+        for j in (0..8).filter(|j| byte >> j & 1 == 1) {
+            let idx = 8 * i + j;
+            if idx >= LENGTH {
+                return Err(InvalidSupport::OutOfBounds(LENGTH).into());
+            }
+            supp.push(idx as Index);
+        }
+    }
+    let supp = <[Index; WEIGHT]>::try_from(supp).or(Err(InvalidSupport::WrongLength(WEIGHT)))?;
+    Ok(CyclicBlock::from_support(supp)?)
+}
+
+fn block_to_hex<const WEIGHT: usize, const LENGTH: usize>(
+    block: &CyclicBlock<WEIGHT, LENGTH>,
+) -> String {
+    let mut bytes = vec![0u8; (LENGTH + 7) / 8];
+    for &idx in block.support() {
+        bytes[idx as usize / 8] |= 1 << (idx % 8);
+    }
+    hex::encode(bytes)
+}
+
+// Degree of a polynomial over GF(2) given by its coefficients, or None for the zero polynomial
+#[inline]
+fn poly_degree(poly: &[bool]) -> Option<usize> {
+    poly.iter().rposition(|&bit| bit)
+}
+
+// Adds x^shift * other to poly, where both are polynomials over GF(2). The caller must
+// ensure that poly has room for all the coefficients of the result.
+fn poly_add_shifted(poly: &mut [bool], other: &[bool], shift: usize) {
+    if let Some(deg) = poly_degree(other) {
+        for (bit, &other_bit) in poly[shift..=shift + deg].iter_mut().zip(other) {
+            *bit ^= other_bit;
+        }
+    }
+}
+
+impl Key {
+    /// Generates random keys until one satisfies all the given constraints, returning an
+    /// error if none is found within the maximum number of attempts.
+    pub fn random_satisfying<R>(
+        constraints: &KeyConstraints,
+        rng: &mut R,
+    ) -> Result<Self, KeyConstraintsError>
+    where
+        R: Rng + ?Sized,
+    {
+        for _ in 0..constraints.max_attempts {
+            let key = Self::random(rng);
+            if constraints.is_satisfied_by(&key) {
+                return Ok(key);
+            }
+        }
+        Err(KeyConstraintsError::MaxAttemptsExceeded(
+            constraints.max_attempts,
+        ))
     }
 }
 
@@ -182,6 +447,65 @@ impl fmt::Display for Key {
     }
 }
 
+/// Wrapper around a key whose `PartialEq`, `Eq`, and `Hash` implementations compare keys up
+/// to simultaneous cyclic shifts of both blocks, using the shift-canonical form computed
+/// once by `QuasiCyclic::canonical`. For instance, collecting keys into a
+/// `HashSet<CanonicalKey>` keeps one key from each equivalence class.
+#[derive(Clone, Debug)]
+pub struct CanonicalKey<const WEIGHT: usize = BLOCK_WEIGHT, const LENGTH: usize = BLOCK_LENGTH> {
+    key: QuasiCyclic<WEIGHT, LENGTH>,
+    canonical: QuasiCyclic<WEIGHT, LENGTH>,
+}
+
+impl<const WEIGHT: usize, const LENGTH: usize> CanonicalKey<WEIGHT, LENGTH> {
+    pub fn new(key: QuasiCyclic<WEIGHT, LENGTH>) -> Self {
+        let canonical = key.canonical();
+        Self { key, canonical }
+    }
+
+    /// The key as originally given.
+    #[inline]
+    pub fn key(&self) -> &QuasiCyclic<WEIGHT, LENGTH> {
+        &self.key
+    }
+
+    /// The canonical representative of the key's equivalence class.
+    #[inline]
+    pub fn canonical(&self) -> &QuasiCyclic<WEIGHT, LENGTH> {
+        &self.canonical
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> QuasiCyclic<WEIGHT, LENGTH> {
+        self.key
+    }
+}
+
+impl<const WEIGHT: usize, const LENGTH: usize> From<QuasiCyclic<WEIGHT, LENGTH>>
+    for CanonicalKey<WEIGHT, LENGTH>
+{
+    #[inline]
+    fn from(key: QuasiCyclic<WEIGHT, LENGTH>) -> Self {
+        Self::new(key)
+    }
+}
+
+impl<const WEIGHT: usize, const LENGTH: usize> PartialEq for CanonicalKey<WEIGHT, LENGTH> {
+    fn eq(&self, other: &Self) -> bool {
+        self.canonical == other.canonical
+    }
+}
+
+impl<const WEIGHT: usize, const LENGTH: usize> Eq for CanonicalKey<WEIGHT, LENGTH> {}
+
+impl<const WEIGHT: usize, const LENGTH: usize> Hash for CanonicalKey<WEIGHT, LENGTH> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // The canonical form is sorted, so equal keys have equal supports
+        self.canonical.h0.support().hash(state);
+        self.canonical.h1.support().hash(state);
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 #[repr(u8)]
 pub enum WeakType {
@@ -190,6 +514,32 @@ pub enum WeakType {
     Type3 = 3,
 }
 
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct WeakKeyClassification {
+    pub threshold: u8,
+    pub type2: bool,
+    pub type3: bool,
+}
+
+/// Block of a key carrying the weak structure of a type 2 weak key.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum WeakBlock {
+    H0,
+    H1,
+}
+
+/// Parameters controlling the generation of random weak keys of type 2 or 3.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct WeakKeyParams {
+    /// Block carrying the weak structure, or `None` to choose one at random.
+    pub weak_block: Option<WeakBlock>,
+    /// If true, the key is weak exactly at the threshold rather than at least at the
+    /// threshold. For type 2, the weak block isn't weak at any higher threshold and the
+    /// other block isn't weak at the threshold; for type 3, no shift of h0 overlaps h1 in
+    /// more than `threshold` positions.
+    pub exact: bool,
+}
+
 #[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
 pub enum QuasiCyclicFilter<const WEIGHT: usize> {
     #[default]
@@ -219,6 +569,14 @@ impl<const WEIGHT: usize> QuasiCyclicFilter<WEIGHT> {
     }
 }
 
+#[derive(Clone, Copy, Debug, Error)]
+pub enum KeyFromHexError {
+    #[error("invalid hex string: {0}")]
+    Hex(#[from] hex::FromHexError),
+    #[error(transparent)]
+    InvalidSupport(#[from] InvalidSupport),
+}
+
 #[derive(Clone, Copy, Debug, Error)]
 pub enum FilterError {
     #[error("weak key filter must be in {{-1, 0, 1, 2, 3}}")]
@@ -227,9 +585,73 @@ pub enum FilterError {
     InvalidThreshold,
 }
 
+type KeyPredicate = Box<dyn Fn(&Key) -> bool + Send + Sync>;
+
+/// A conjunction of arbitrary predicates on keys, used to generate random keys by rejection
+/// sampling with `Key::random_satisfying`.
+pub struct KeyConstraints {
+    predicates: Vec<KeyPredicate>,
+    max_attempts: u64,
+}
+
+impl KeyConstraints {
+    pub const DEFAULT_MAX_ATTEMPTS: u64 = 1_000_000;
+
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            predicates: Vec::new(),
+            max_attempts: Self::DEFAULT_MAX_ATTEMPTS,
+        }
+    }
+
+    /// Adds a predicate that keys must satisfy.
+    pub fn with<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&Key) -> bool + Send + Sync + 'static,
+    {
+        self.predicates.push(Box::new(predicate));
+        self
+    }
+
+    /// Sets the maximum number of keys generated before giving up.
+    #[inline]
+    pub fn max_attempts(mut self, max_attempts: u64) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    #[inline]
+    pub fn is_satisfied_by(&self, key: &Key) -> bool {
+        self.predicates.iter().all(|predicate| predicate(key))
+    }
+}
+
+impl Default for KeyConstraints {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for KeyConstraints {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("KeyConstraints")
+            .field("predicates", &self.predicates.len())
+            .field("max_attempts", &self.max_attempts)
+            .finish()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Error)]
+pub enum KeyConstraintsError {
+    #[error("no key satisfying the constraints was found in {0} attempts")]
+    MaxAttemptsExceeded(u64),
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashSet;
     const TRIALS: usize = 1000;
 
     #[test]
@@ -245,6 +667,196 @@ mod tests {
         }
     }
 
+    #[test]
+    fn classify_weak_keys() {
+        let mut rng = rand::thread_rng();
+        for threshold in 3..=5 {
+            for _ in 0..10 {
+                let key = Key::random_weak_type3(threshold, &mut rng);
+                let classification = key.classify_weak(threshold);
+                assert_eq!(classification.threshold, threshold);
+                assert!(classification.type3);
+                assert_eq!(classification.type2, key.is_weak_type2(threshold));
+                let key = Key::random_weak_type2(threshold, &mut rng);
+                assert!(key.classify_weak(threshold).type2);
+            }
+        }
+    }
+
+    #[test]
+    fn codeword_weight() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..10 {
+            let key = Key::random(&mut rng);
+            let patterns = crate::ncw::ncw_patterns(&key, crate::ncw::NearCodewordClass::C);
+            assert_eq!(key.codeword_weight(), patterns[0].len());
+            assert_eq!(key.codeword_weight(), 2 * BLOCK_WEIGHT);
+        }
+        let key = QuasiCyclic::<3, 11>::from_support([0, 1, 4], [2, 5, 10]).unwrap();
+        assert_eq!(key.codeword_weight(), 6);
+    }
+
+    #[test]
+    fn canonical_shifts() {
+        let mut rng = rand::thread_rng();
+        let key = Key::random(&mut rng);
+        let canonical = key.canonical();
+        assert_eq!(canonical.h0().support()[0], 0);
+        assert!(canonical.is_shift_equivalent(&key));
+        for delta in 0..BLOCK_LENGTH as Index {
+            let shifted = key.shifted(delta);
+            assert_eq!(shifted.canonical(), canonical);
+            assert!(shifted.is_shift_equivalent(&key));
+        }
+        let other = Key::random(&mut rng);
+        assert!(!other.is_shift_equivalent(&key));
+        let small = QuasiCyclic::<3, 11>::from_support([3, 4, 7], [5, 8, 2]).unwrap();
+        assert_eq!(
+            small.canonical(),
+            QuasiCyclic::<3, 11>::from_support([0, 1, 4], [2, 5, 10]).unwrap()
+        );
+        let swapped = QuasiCyclic::new(small.h1().clone(), small.h0().clone());
+        assert!(!swapped.is_shift_equivalent(&small));
+    }
+
+    #[test]
+    fn canonical_key_set() {
+        let mut rng = rand::thread_rng();
+        let key = Key::random(&mut rng);
+        let mut keys: HashSet<CanonicalKey> = [0, 1, 17, 300, BLOCK_LENGTH as Index - 1]
+            .into_iter()
+            .map(|delta| CanonicalKey::new(key.shifted(delta)))
+            .collect();
+        assert_eq!(keys.len(), 1);
+        assert!(keys.contains(&CanonicalKey::from(key.clone())));
+        assert_eq!(keys.iter().next().unwrap().canonical(), &key.canonical());
+        keys.insert(CanonicalKey::new(Key::random(&mut rng)));
+        assert_eq!(keys.len(), 2);
+        let small =
+            CanonicalKey::new(QuasiCyclic::<3, 11>::from_support([3, 4, 7], [5, 8, 2]).unwrap());
+        assert_eq!(small.key().h0().support(), &[3, 4, 7]);
+        assert_eq!(
+            small,
+            CanonicalKey::new(QuasiCyclic::<3, 11>::from_support([0, 1, 4], [2, 5, 10]).unwrap())
+        );
+    }
+
+    #[test]
+    fn block_gaps() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let key = Key::random(&mut rng);
+            assert_eq!(key.total_weight(), 2 * BLOCK_WEIGHT);
+            for gaps in key.block_gaps() {
+                assert_eq!(gaps.len(), BLOCK_WEIGHT);
+                assert!(gaps.iter().all(|&gap| gap > 0));
+                assert_eq!(gaps.iter().sum::<Index>(), BLOCK_LENGTH as Index);
+            }
+        }
+        let key = QuasiCyclic::<3, 11>::from_support([4, 0, 1], [10, 2, 5]).unwrap();
+        assert_eq!(key.total_weight(), 6);
+        assert_eq!(key.block_gaps(), [vec![1, 3, 7], vec![3, 5, 3]]);
+        let single = QuasiCyclic::<1, 5>::from_support([3], [0]).unwrap();
+        assert_eq!(single.block_gaps(), [vec![5], vec![5]]);
+    }
+
+    #[test]
+    fn key_constraints() {
+        let mut rng = rand::thread_rng();
+        let constraints = KeyConstraints::new()
+            .with(|key| key.is_weak_type2(3))
+            .with(|key| key.h0_is_invertible());
+        for _ in 0..10 {
+            let key = Key::random_satisfying(&constraints, &mut rng).unwrap();
+            assert!(key.is_weak_type2(3) && key.h0_is_invertible());
+        }
+        let impossible = KeyConstraints::new().with(|_| false).max_attempts(100);
+        assert!(matches!(
+            Key::random_satisfying(&impossible, &mut rng),
+            Err(KeyConstraintsError::MaxAttemptsExceeded(100))
+        ));
+    }
+
+    #[test]
+    fn hex_roundtrip() {
+        // x^0 + x^9 + x^10 and x^3 + x^15 + x^16
+        let key = QuasiCyclic::<3, 17>::from_support([0, 9, 10], [3, 15, 16]).unwrap();
+        assert_eq!(key.to_hex(), ("010600".to_string(), "088001".to_string()));
+        assert_eq!(
+            QuasiCyclic::<3, 17>::from_hex("010600", "088001").unwrap(),
+            key
+        );
+        // Trailing bytes may be omitted, and uppercase hex is accepted
+        assert_eq!(
+            QuasiCyclic::<2, 17>::from_hex("03", "0A").unwrap(),
+            QuasiCyclic::<2, 17>::from_support([0, 1], [1, 3]).unwrap()
+        );
+        assert!(matches!(
+            QuasiCyclic::<3, 17>::from_hex("010200", "088001"),
+            Err(KeyFromHexError::InvalidSupport(
+                InvalidSupport::WrongLength(3)
+            ))
+        ));
+        assert!(matches!(
+            QuasiCyclic::<3, 17>::from_hex("010600", "080003"),
+            Err(KeyFromHexError::InvalidSupport(
+                InvalidSupport::OutOfBounds(17)
+            ))
+        ));
+        assert!(matches!(
+            QuasiCyclic::<3, 17>::from_hex("01060", "088001"),
+            Err(KeyFromHexError::Hex(_))
+        ));
+        let mut rng = rand::thread_rng();
+        for _ in 0..TRIALS {
+            let key = Key::random(&mut rng).sorted();
+            let (h0_hex, h1_hex) = key.to_hex();
+            assert_eq!(h0_hex.len(), 2 * ((BLOCK_LENGTH + 7) / 8));
+            assert_eq!(Key::from_hex(&h0_hex, &h1_hex).unwrap(), key);
+        }
+    }
+
+    #[test]
+    fn h0_invertibility() {
+        // x^7 - 1 = (x + 1)(x^3 + x + 1)(x^3 + x^2 + 1) over GF(2)
+        let singular = QuasiCyclic::<3, 7>::from_support([0, 1, 3], [0, 1, 2]).unwrap();
+        assert!(!singular.h0_is_invertible());
+        assert_eq!(singular.h0_inverse(), None);
+        // Even weight blocks are divisible by x + 1
+        let even_weight = QuasiCyclic::<2, 7>::from_support([2, 5], [0, 1]).unwrap();
+        assert!(!even_weight.h0_is_invertible());
+        let monomial = QuasiCyclic::<1, 7>::from_support([2], [0]).unwrap();
+        assert_eq!(
+            monomial.h0_inverse().map(|inv| inv.support()),
+            Some(vec![5])
+        );
+        // (1 + x + x^2)(1 + x^2 + x^3 + x^5 + x^6) = 1 mod x^7 - 1
+        let invertible = QuasiCyclic::<3, 7>::from_support([0, 1, 2], [0, 1, 3]).unwrap();
+        assert_eq!(
+            invertible.h0_inverse().map(|inv| inv.support()),
+            Some(vec![0, 2, 3, 5, 6])
+        );
+    }
+
+    #[test]
+    fn h0_inverse_product() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..TRIALS / 10 {
+            let key = Key::random(&mut rng);
+            // BLOCK_WEIGHT is odd, so h0 is not divisible by x + 1 and singular keys are rare
+            let Some(inverse) = key.h0_inverse() else {
+                continue;
+            };
+            let mut product = vec![false; BLOCK_LENGTH];
+            for &i in key.h0().support() {
+                for j in inverse.support() {
+                    product[(i + j) as usize % BLOCK_LENGTH] ^= true;
+                }
+            }
+            assert!(product[0] && product[1..].iter().all(|&bit| !bit));
+        }
+    }
+
     #[test]
     fn weak_keys_type1() {
         let mut rng = rand::thread_rng();
@@ -271,6 +883,58 @@ mod tests {
         }
     }
 
+    #[test]
+    fn weak_margin_type2() {
+        let mut rng = rand::thread_rng();
+        let weak_key_threshold = 7;
+        for _ in 0..TRIALS {
+            let key = Key::random_weak_type2(weak_key_threshold, &mut rng);
+            assert_eq!(key.weak_margin_type2(weak_key_threshold), 0);
+            let key = Key::random_non_weak(weak_key_threshold, &mut rng);
+            let margin = key.weak_margin_type2(weak_key_threshold);
+            assert!(margin >= 1);
+            assert!(!key.is_weak_type2(weak_key_threshold - margin as u8 + 1));
+            assert!(key.is_weak_type2(weak_key_threshold - margin as u8));
+        }
+    }
+
+    #[test]
+    fn weak_keys_type2_params() {
+        let mut rng = rand::thread_rng();
+        let weak_key_threshold = 5;
+        for weak_block in [WeakBlock::H0, WeakBlock::H1] {
+            let params = WeakKeyParams {
+                weak_block: Some(weak_block),
+                exact: true,
+            };
+            for _ in 0..TRIALS {
+                let key = Key::random_weak_type2_with_params(weak_key_threshold, params, &mut rng);
+                let (weak, other) = match weak_block {
+                    WeakBlock::H0 => (key.h0(), key.h1()),
+                    WeakBlock::H1 => (key.h1(), key.h0()),
+                };
+                assert!(weak.shifts_above_threshold(weak_key_threshold));
+                assert!(!weak.shifts_above_threshold(weak_key_threshold + 1));
+                assert!(!other.shifts_above_threshold(weak_key_threshold));
+            }
+        }
+    }
+
+    #[test]
+    fn weak_keys_type3_exact() {
+        let mut rng = rand::thread_rng();
+        let weak_key_threshold = 5;
+        let params = WeakKeyParams {
+            weak_block: None,
+            exact: true,
+        };
+        for _ in 0..TRIALS {
+            let key = Key::random_weak_type3_with_params(weak_key_threshold, params, &mut rng);
+            assert!(key.is_weak_type3(weak_key_threshold));
+            assert!(!key.is_weak_type3(weak_key_threshold + 1));
+        }
+    }
+
     #[test]
     fn weak_keys_type3() {
         let mut rng = rand::thread_rng();
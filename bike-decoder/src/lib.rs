@@ -3,6 +3,7 @@ pub mod graphs;
 pub mod keys;
 pub mod ncw;
 pub mod parameters;
+pub mod prelude;
 pub mod random;
 pub mod syndrome;
 pub mod threshold;
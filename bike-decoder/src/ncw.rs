@@ -1,19 +1,24 @@
 use crate::{
+    decoder::DecodingFailure,
     keys::{Key, QuasiCyclic},
     parameters::*,
     random::custom_thread_rng,
-    vectors::{Index, SparseErrorVector},
+    threshold::big_binomial,
+    vectors::{sample_from_complement, ErrorVector, Index, SparseErrorVector},
 };
 use getset::{CopyGetters, Getters};
+use malachite::{
+    num::conversion::traits::RoundingInto, rounding_modes::RoundingMode, Natural, Rational,
+};
 use rand::{
-    distributions::{Distribution, Uniform},
     seq::{IteratorRandom, SliceRandom},
     Rng,
 };
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use thiserror::Error;
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub enum NearCodewordClass {
     C,
     N,
@@ -55,7 +60,9 @@ impl clap::ValueEnum for NearCodewordClass {
     }
 }
 
-#[derive(Copy, Clone, CopyGetters, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(
+    Copy, Clone, CopyGetters, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize,
+)]
 #[getset(get_copy = "pub")]
 pub struct NearCodewordSet {
     class: NearCodewordClass,
@@ -63,7 +70,14 @@ pub struct NearCodewordSet {
     delta: usize,
 }
 
-#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+impl NearCodewordSet {
+    #[inline]
+    pub fn new(class: NearCodewordClass, l: usize, delta: usize) -> Self {
+        Self { class, l, delta }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum ErrorVectorSource {
     Random,
     NearCodeword(NearCodewordSet),
@@ -91,6 +105,13 @@ impl From<SparseErrorVector> for TaggedErrorVector {
 }
 
 impl TaggedErrorVector {
+    /// Attaches an arbitrary source label to an error vector, e.g. when importing
+    /// externally generated vectors whose provenance is known.
+    #[inline]
+    pub fn new(vector: SparseErrorVector, source: ErrorVectorSource) -> Self {
+        Self { vector, source }
+    }
+
     #[inline]
     pub fn take_vector(self) -> (SparseErrorVector, ErrorVectorSource) {
         (self.vector, self.source)
@@ -104,6 +125,18 @@ impl TaggedErrorVector {
         }
     }
 
+    /// Reverses the order of the coefficients within each half of the error vector (see
+    /// `reverse_blockwise`), keeping the source label.
+    pub fn reversed(&self) -> Self {
+        let mut supp = *self.vector.support();
+        reverse_blockwise::<BLOCK_LENGTH>(&mut supp);
+        Self {
+            vector: SparseErrorVector::from_support(supp)
+                .expect("reversed support should be valid"),
+            source: self.source,
+        }
+    }
+
     #[inline]
     pub fn from_other(vector: SparseErrorVector) -> Self {
         Self {
@@ -145,14 +178,12 @@ impl TaggedErrorVector {
             *slot = *idx;
         }
         // Fill remaining elements from complement of sample
-        let mut ctr = l;
-        let dist = Uniform::new(0, ROW_LENGTH as Index);
-        while ctr < ERROR_WEIGHT {
-            supp[ctr] = dist.sample(rng);
-            if !sample.contains(&supp[ctr]) && !supp[l..ctr].contains(&supp[ctr]) {
-                ctr += 1;
-            }
-        }
+        supp[l..].copy_from_slice(&sample_from_complement(
+            &sample,
+            ERROR_WEIGHT - l,
+            ROW_LENGTH as Index,
+            rng,
+        ));
         let shift = rng.gen_range(0..r);
         shift_blockwise::<BLOCK_LENGTH>(&mut supp, shift);
         Self {
@@ -273,6 +304,43 @@ fn patterns_2n<const WT: usize, const LEN: usize>(key: &QuasiCyclic<WT, LEN>) ->
     patterns
 }
 
+/// Generalizes the N and 2N patterns to k-fold sums: returns the symmetric differences of an N
+/// pattern with k - 1 blockwise-shifted N patterns. For k = 1 and k = 2 this gives the same
+/// patterns as `NearCodewordClass::N` and `NearCodewordClass::TwoN`, respectively. The number of
+/// patterns grows like `(2r)^(k-1)`, so this is only practical for small k.
+pub fn patterns_kn<const WT: usize, const LEN: usize>(
+    key: &QuasiCyclic<WT, LEN>,
+    k: usize,
+) -> Vec<Vec<Index>> {
+    if k == 0 {
+        return vec![Vec::new()];
+    }
+    let n_patterns = patterns_n(key);
+    let mut patterns = n_patterns.clone();
+    for _ in 1..k {
+        let mut next_patterns = Vec::with_capacity(patterns.len() * n_patterns.len() * LEN);
+        for supp1 in patterns.iter() {
+            for supp2 in n_patterns.iter() {
+                let mut supp2 = supp2.clone();
+                for _ in 0..LEN {
+                    shift_blockwise::<LEN>(&mut supp2, 1);
+                    let mut v = supp1.clone();
+                    for entry in supp2.iter() {
+                        if let Some(index) = v.iter().position(|item| *item == *entry) {
+                            v.swap_remove(index);
+                        } else {
+                            v.push(*entry);
+                        }
+                    }
+                    next_patterns.push(v);
+                }
+            }
+        }
+        patterns = next_patterns;
+    }
+    patterns
+}
+
 pub fn ncw_patterns<const WT: usize, const LEN: usize>(
     key: &QuasiCyclic<WT, LEN>,
     ncw_class: NearCodewordClass,
@@ -295,12 +363,122 @@ pub fn near_codeword_max_overlap<const LEN: usize>(
         .unwrap_or(0)
 }
 
+/// Same as `near_codeword_max_overlap`, but also identifies where the maximum is achieved.
+/// Returns `(pattern_index, shift, count)`, where shifting `patterns[pattern_index]` blockwise
+/// by `shift` (see `shift_blockwise`) gives a vector sharing `count` entries with `supp`.
+/// Ties are broken by the smallest pattern index, then the smallest shift. If `patterns` is
+/// empty, returns `(0, 0, 0)`.
+pub fn near_codeword_argmax<const LEN: usize>(
+    supp: &[Index],
+    patterns: &[Vec<Index>],
+) -> (usize, Index, usize) {
+    patterns
+        .iter()
+        .enumerate()
+        .map(|(i, pattern)| {
+            let (shift, count) = argmax_shifted_overlap_blockwise::<LEN>(supp, pattern);
+            (i, shift, count)
+        })
+        .reduce(|best, next| if next.2 > best.2 { next } else { best })
+        .unwrap_or((0, 0, 0))
+}
+
+/// Finds the near-codeword closest to `supp` across the C, N, and 2N classes, e.g. to
+/// classify the residual of a decoding failure. Returns `(class, shift, count)`, where `shift`
+/// and `count` are as in `near_codeword_argmax` for the patterns of `class`. Ties are broken
+/// in favor of C, then N, then 2N.
+pub fn nearest_near_codeword<const WT: usize, const LEN: usize>(
+    key: &QuasiCyclic<WT, LEN>,
+    supp: &[Index],
+) -> (NearCodewordClass, Index, usize) {
+    [
+        NearCodewordClass::C,
+        NearCodewordClass::N,
+        NearCodewordClass::TwoN,
+    ]
+    .into_iter()
+    .map(|class| {
+        let (_, shift, count) = near_codeword_argmax::<LEN>(supp, &ncw_patterns(key, class));
+        (class, shift, count)
+    })
+    .reduce(|best, next| if next.2 > best.2 { next } else { best })
+    .expect("There should be at least one near-codeword class")
+}
+
+/// Size of the near-codeword set A_{t,l}(S), i.e., the number of vectors of weight `t` and
+/// length `2 * LEN` with exactly `l` entries in common with some near-codeword, where the
+/// near-codewords are the blockwise shifts of `patterns` (see `ncw_patterns`).
+///
+/// Each near-codeword of weight w has C(w, l) * C(2 * LEN - w, t - l) such vectors, but a
+/// vector may be counted for several near-codewords. Two distinct near-codewords sharing m
+/// entries can only have such a vector in common if 2l - t <= m, so the counts are summed
+/// only if 2l - t exceeds the largest number of entries shared by distinct near-codewords
+/// (see `near_codeword_max_intersection`). Otherwise returns `None`.
+pub fn near_codeword_set_size<const LEN: usize>(
+    patterns: &[Vec<Index>],
+    l: usize,
+    t: usize,
+) -> Option<Natural> {
+    if l > t {
+        return Some(Natural::default());
+    }
+    let n = 2 * LEN;
+    let per_shift: Natural = patterns
+        .iter()
+        .map(|pattern| {
+            big_binomial(pattern.len(), l) * big_binomial(n.saturating_sub(pattern.len()), t - l)
+        })
+        .sum();
+    if per_shift != 0 && 2 * l <= t + near_codeword_max_intersection::<LEN>(patterns) {
+        return None;
+    }
+    Some(Natural::from(LEN) * per_shift)
+}
+
+/// Ratio of `near_codeword_set_size` to the number of vectors of weight `t` and length
+/// `2 * LEN`, i.e., the probability that a uniformly random error vector lies in
+/// A_{t,l}(S). Returns `None` if the size isn't known.
+pub fn near_codeword_set_fraction<const LEN: usize>(
+    patterns: &[Vec<Index>],
+    l: usize,
+    t: usize,
+) -> Option<f64> {
+    let size = near_codeword_set_size::<LEN>(patterns, l, t)?;
+    Some(
+        Rational::from_naturals(size, big_binomial(2 * LEN, t))
+            .rounding_into(RoundingMode::Nearest),
+    )
+}
+
+/// Largest number of entries shared by two distinct near-codewords, where the near-codewords
+/// are the blockwise shifts of `patterns`. Takes time quadratic in the number of patterns.
+pub fn near_codeword_max_intersection<const LEN: usize>(patterns: &[Vec<Index>]) -> usize {
+    let mut max_count = 0;
+    for (i, pattern1) in patterns.iter().enumerate() {
+        for pattern2 in &patterns[i..] {
+            let mut shift_counts = [0; LEN];
+            for shift in relative_shifts_blockwise::<LEN>(pattern1, pattern2) {
+                shift_counts[shift as usize] += 1;
+            }
+            // Shifting a pattern by 0 gives the same near-codeword
+            let skip = usize::from(std::ptr::eq(pattern1, pattern2));
+            max_count = shift_counts[skip..]
+                .iter()
+                .fold(max_count, |max, &count| max.max(count));
+        }
+    }
+    max_count
+}
+
 #[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
 pub struct NcwOverlaps {
     pub c: usize,
     pub n: usize,
     #[serde(rename = "2n")]
     pub two_n: usize,
+    /// Overlaps with the k-fold sums of N patterns, if computed (see `NcwOverlaps::with_kn`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kn: Option<KnOverlaps>,
 }
 
 impl NcwOverlaps {
@@ -308,21 +486,167 @@ impl NcwOverlaps {
         key: &QuasiCyclic<WT, LEN>,
         supp: &[Index],
     ) -> Self {
-        let patterns_c = ncw_patterns(key, NearCodewordClass::C);
-        let patterns_n = ncw_patterns(key, NearCodewordClass::N);
-        let patterns_2n = ncw_patterns(key, NearCodewordClass::TwoN);
+        NcwOverlapsBatch::new(key).overlaps_of(supp)
+    }
+
+    /// Same as `NcwOverlaps::new`, but also computes the overlaps with the k-fold sums of N
+    /// patterns for k up to `max_k` (see `KnOverlaps::new`).
+    pub fn with_kn<const WT: usize, const LEN: usize>(
+        key: &QuasiCyclic<WT, LEN>,
+        supp: &[Index],
+        max_k: usize,
+    ) -> Self {
+        let mut overlaps = Self::new(key, supp);
+        overlaps.kn = Some(KnOverlaps::new(key, supp, max_k, Some(overlaps)));
+        overlaps
+    }
+
+    /// Computes the overlaps for a decoding failure. If `e_out` is `None`, these are the
+    /// overlaps of the input error vector `e_in` (as in `ClassifiedVector`); otherwise they
+    /// are the overlaps of the residual `e_in + e_out`, where `e_out` should be the decoder
+    /// output recorded when the decoding failure was found (e.g., as returned by
+    /// `DecodingResult::from_key_vector_with_output`), so the decoder isn't rerun.
+    pub fn from_failure(df: &DecodingFailure, e_out: Option<&ErrorVector>) -> Self {
+        let key = df.key();
+        let e_in = df.vector().vector();
+        match e_out {
+            Some(e_out) => {
+                let mut residual = e_out.clone();
+                for &idx in e_in.support() {
+                    residual.flip(idx as usize);
+                }
+                Self::new(key, &residual.support())
+            }
+            None => Self::new(key, e_in.support()),
+        }
+    }
+}
+
+/// Largest k for which `KnOverlaps` can hold the overlaps with k-fold sums of N patterns.
+/// The number of patterns grows too quickly for larger k to be practical anyway.
+pub const MAX_KN: usize = 4;
+
+/// Maximum overlaps of a support with the k-fold sums of N patterns (see `patterns_kn`),
+/// indexed by k, so the overlaps for k = 1 and k = 2 are the `n` and `two_n` fields of
+/// `NcwOverlaps`, and the overlap for k = 0 is always 0. Serialized as a list.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(into = "Vec<usize>", try_from = "Vec<usize>")]
+pub struct KnOverlaps {
+    len: usize,
+    overlaps: [usize; MAX_KN + 1],
+}
+
+impl KnOverlaps {
+    /// Computes the overlaps for k = 0, ..., `max_k`, reusing the N and 2N overlaps if
+    /// they've already been computed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_k` is greater than `MAX_KN`.
+    pub fn new<const WT: usize, const LEN: usize>(
+        key: &QuasiCyclic<WT, LEN>,
+        supp: &[Index],
+        max_k: usize,
+        overlaps: Option<NcwOverlaps>,
+    ) -> Self {
+        assert!(
+            max_k <= MAX_KN,
+            "k-fold overlaps are only stored for k <= {MAX_KN}"
+        );
+        let mut kn = Self {
+            len: max_k + 1,
+            overlaps: [0; MAX_KN + 1],
+        };
+        for k in 1..=max_k {
+            kn.overlaps[k] = match (k, overlaps) {
+                (1, Some(overlaps)) => overlaps.n,
+                (2, Some(overlaps)) => overlaps.two_n,
+                _ => near_codeword_max_overlap::<LEN>(supp, &patterns_kn(key, k)),
+            };
+        }
+        kn
+    }
+
+    /// Overlap with the k-fold sums of N patterns, if it was computed.
+    #[inline]
+    pub fn get(&self, k: usize) -> Option<usize> {
+        self.as_slice().get(k).copied()
+    }
+
+    /// Largest k for which the overlap was computed.
+    #[inline]
+    pub fn max_k(&self) -> usize {
+        self.len.saturating_sub(1)
+    }
+
+    #[inline]
+    pub fn as_slice(&self) -> &[usize] {
+        &self.overlaps[..self.len]
+    }
+}
+
+impl From<KnOverlaps> for Vec<usize> {
+    fn from(kn: KnOverlaps) -> Self {
+        kn.as_slice().to_vec()
+    }
+}
+
+impl TryFrom<Vec<usize>> for KnOverlaps {
+    type Error = KnOverlapsLengthError;
+
+    fn try_from(overlaps: Vec<usize>) -> Result<Self, KnOverlapsLengthError> {
+        let mut kn = Self {
+            len: overlaps.len(),
+            overlaps: [0; MAX_KN + 1],
+        };
+        kn.overlaps
+            .get_mut(..overlaps.len())
+            .ok_or(KnOverlapsLengthError(overlaps.len()))?
+            .copy_from_slice(&overlaps);
+        Ok(kn)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Error)]
+#[error("k-fold overlaps are only stored for k <= {MAX_KN}, got {0} overlaps")]
+pub struct KnOverlapsLengthError(usize);
+
+/// The C, N, and 2N near-codeword patterns of a key, computed once so that the overlaps of
+/// many vectors with the same key can be computed without rebuilding the patterns.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NcwOverlapsBatch<const WT: usize, const LEN: usize> {
+    patterns_c: Vec<Vec<Index>>,
+    patterns_n: Vec<Vec<Index>>,
+    patterns_2n: Vec<Vec<Index>>,
+}
+
+impl<const WT: usize, const LEN: usize> NcwOverlapsBatch<WT, LEN> {
+    pub fn new(key: &QuasiCyclic<WT, LEN>) -> Self {
         Self {
-            c: near_codeword_max_overlap::<LEN>(supp, &patterns_c),
-            n: near_codeword_max_overlap::<LEN>(supp, &patterns_n),
-            two_n: near_codeword_max_overlap::<LEN>(supp, &patterns_2n),
+            patterns_c: ncw_patterns(key, NearCodewordClass::C),
+            patterns_n: ncw_patterns(key, NearCodewordClass::N),
+            patterns_2n: ncw_patterns(key, NearCodewordClass::TwoN),
+        }
+    }
+
+    /// Same as `NcwOverlaps::new` with the key the patterns were computed from.
+    pub fn overlaps_of(&self, supp: &[Index]) -> NcwOverlaps {
+        NcwOverlaps {
+            c: near_codeword_max_overlap::<LEN>(supp, &self.patterns_c),
+            n: near_codeword_max_overlap::<LEN>(supp, &self.patterns_n),
+            two_n: near_codeword_max_overlap::<LEN>(supp, &self.patterns_2n),
+            kn: None,
         }
     }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, CopyGetters, Debug, Getters, Serialize, Deserialize)]
 pub struct ClassifiedVector<const WT: usize, const LEN: usize> {
+    #[getset(get = "pub")]
     key: QuasiCyclic<WT, LEN>,
+    #[getset(get = "pub")]
     supp: Vec<Index>,
+    #[getset(get_copy = "pub")]
     overlaps: NcwOverlaps,
 }
 
@@ -338,6 +662,21 @@ impl<const WT: usize, const LEN: usize> ClassifiedVector<WT, LEN> {
         }
     }
 
+    /// Reconstructs a classified vector from previously computed overlaps, e.g. when
+    /// loading stored data. The overlaps are not recomputed.
+    pub fn from_overlaps(
+        key: QuasiCyclic<WT, LEN>,
+        mut supp: Vec<Index>,
+        overlaps: NcwOverlaps,
+    ) -> Self {
+        supp.sort_unstable();
+        Self {
+            key,
+            supp,
+            overlaps,
+        }
+    }
+
     pub fn random(key: &QuasiCyclic<WT, LEN>, supp_weight: usize) -> Self {
         let n = 2 * LEN as Index;
         let mut supp = (0..n).choose_multiple(&mut custom_thread_rng(), supp_weight);
@@ -349,6 +688,32 @@ impl<const WT: usize, const LEN: usize> ClassifiedVector<WT, LEN> {
             overlaps,
         }
     }
+
+    /// Draws `samples` uniformly random supports of weight `supp_weight` and keeps those
+    /// whose maximum overlap with the near-codewords of the given class is at least
+    /// `min_overlap`, which focuses further analysis on the tail of the overlap distribution.
+    /// Only the overlap with the given class is computed for rejected supports.
+    pub fn sample_biased<R>(
+        key: &QuasiCyclic<WT, LEN>,
+        supp_weight: usize,
+        samples: usize,
+        min_overlap: usize,
+        class: NearCodewordClass,
+        rng: &mut R,
+    ) -> Vec<Self>
+    where
+        R: Rng + ?Sized,
+    {
+        let n = 2 * LEN as Index;
+        let patterns = ncw_patterns(key, class);
+        (0..samples)
+            .filter_map(|_| {
+                let supp = (0..n).choose_multiple(rng, supp_weight);
+                (near_codeword_max_overlap::<LEN>(&supp, &patterns) >= min_overlap)
+                    .then(|| Self::new(key.clone(), &supp))
+            })
+            .collect()
+    }
 }
 
 /// Cyclically shifts support of vector by shift in blocks of length block_length.
@@ -359,6 +724,15 @@ pub fn shift_blockwise<const LEN: usize>(supp: &mut [Index], shift: Index) {
     }
 }
 
+/// Reverses the order of the coefficients within each block of length LEN, i.e., replaces
+/// index `i` of a block with index `LEN - 1 - i` of the same block.
+pub fn reverse_blockwise<const LEN: usize>(supp: &mut [Index]) {
+    let block_length = LEN as Index;
+    for idx in supp.iter_mut() {
+        *idx = (*idx / block_length) * block_length + (block_length - 1 - *idx % block_length);
+    }
+}
+
 pub fn relative_shifts_blockwise<const LEN: usize>(supp1: &[Index], supp2: &[Index]) -> Vec<Index> {
     let block_length = LEN as Index;
     let mut shifts = Vec::with_capacity(supp1.len() * supp2.len());
@@ -390,9 +764,33 @@ pub fn max_shifted_overlap_blockwise<const LEN: usize>(supp1: &[Index], supp2: &
     max_shift_count
 }
 
+/// Returns `(shift, count)` where `count` is as in `max_shifted_overlap_blockwise` and `shift`
+/// is the smallest blockwise shift of `supp2` achieving it.
+pub fn argmax_shifted_overlap_blockwise<const LEN: usize>(
+    supp1: &[Index],
+    supp2: &[Index],
+) -> (Index, usize) {
+    let shifts = relative_shifts_blockwise::<LEN>(supp1, supp2);
+    let mut shift_counts = [0; LEN];
+    for shift in shifts {
+        shift_counts[shift as usize] += 1;
+    }
+    shift_counts
+        .iter()
+        .enumerate()
+        .fold((0, 0), |(best_shift, best_count), (shift, &count)| {
+            if count > best_count {
+                (shift as Index, count)
+            } else {
+                (best_shift, best_count)
+            }
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{decoder::DecodingResult, vectors::SparseVector};
 
     #[test]
     fn blockwise_shift() {
@@ -401,6 +799,36 @@ mod tests {
         assert_eq!(supp, [6, 0, 2, 11, 8, 10, 14, 16]);
     }
 
+    #[test]
+    fn overlaps_from_failure() {
+        let key = Key::from_support(
+            [
+                93, 99, 105, 121, 126, 141, 156, 193, 194, 197, 264, 301, 360, 400, 429,
+            ],
+            [
+                100, 117, 189, 191, 211, 325, 340, 386, 440, 461, 465, 474, 534, 565, 578,
+            ],
+        )
+        .unwrap();
+        let e_in = SparseErrorVector::from_support([
+            16, 73, 89, 201, 346, 522, 547, 553, 574, 575, 613, 619, 637, 713, 955, 960, 983, 1008,
+        ])
+        .unwrap();
+        let (result, _, e_out) = DecodingResult::from_key_vector_with_output(
+            key.clone(),
+            e_in.clone().into(),
+            NB_ITER,
+            GRAY_THRESHOLD_DIFF,
+        );
+        let df = DecodingFailure::try_from(result).unwrap();
+        let overlaps_in = NcwOverlaps::from_failure(&df, None);
+        assert_eq!(overlaps_in, NcwOverlaps::new(&key, e_in.support()));
+        let overlaps_diff = NcwOverlaps::from_failure(&df, Some(&e_out));
+        let residual = e_out.add_mod2(e_in.dense());
+        assert_eq!(overlaps_diff, NcwOverlaps::new(&key, &residual.support()));
+        assert_ne!(overlaps_in, overlaps_diff);
+    }
+
     #[test]
     fn small_key_overlaps() {
         let key =
@@ -411,9 +839,248 @@ mod tests {
             NcwOverlaps {
                 c: 2,
                 n: 2,
-                two_n: 3
+                two_n: 3,
+                kn: None,
+            }
+        );
+    }
+
+    #[test]
+    fn biased_sampling() {
+        let mut rng = rand::thread_rng();
+        let key =
+            QuasiCyclic::<5, 19>::from_support([2, 4, 13, 16, 17], [3, 4, 5, 13, 14]).unwrap();
+        for class in [
+            NearCodewordClass::C,
+            NearCodewordClass::N,
+            NearCodewordClass::TwoN,
+        ] {
+            let vectors = ClassifiedVector::sample_biased(&key, 8, 200, 4, class, &mut rng);
+            assert!(!vectors.is_empty());
+            for vector in &vectors {
+                assert_eq!(vector.supp().len(), 8);
+                let overlap = match class {
+                    NearCodewordClass::C => vector.overlaps().c,
+                    NearCodewordClass::N => vector.overlaps().n,
+                    NearCodewordClass::TwoN => vector.overlaps().two_n,
+                };
+                assert!(overlap >= 4);
             }
+            assert!(ClassifiedVector::sample_biased(&key, 8, 100, 9, class, &mut rng).is_empty());
+        }
+    }
+
+    #[test]
+    fn batched_overlaps() {
+        let mut rng = rand::thread_rng();
+        let key = Key::random(&mut rng);
+        let batch = NcwOverlapsBatch::new(&key);
+        let patterns = [
+            NearCodewordClass::C,
+            NearCodewordClass::N,
+            NearCodewordClass::TwoN,
+        ]
+        .map(|class| ncw_patterns(&key, class));
+        for _ in 0..20 {
+            let supp = SparseErrorVector::random(&mut rng);
+            let overlaps = batch.overlaps_of(supp.support());
+            assert_eq!(
+                [overlaps.c, overlaps.n, overlaps.two_n],
+                [&patterns[0], &patterns[1], &patterns[2]].map(|patterns| {
+                    near_codeword_max_overlap::<BLOCK_LENGTH>(supp.support(), patterns)
+                })
+            );
+            assert_eq!(overlaps, NcwOverlaps::new(&key, supp.support()));
+        }
+        let key =
+            QuasiCyclic::<5, 19>::from_support([2, 4, 13, 16, 17], [3, 4, 5, 13, 14]).unwrap();
+        let batch = NcwOverlapsBatch::new(&key);
+        for vector in
+            ClassifiedVector::sample_biased(&key, 6, 20, 0, NearCodewordClass::C, &mut rng)
+        {
+            assert_eq!(batch.overlaps_of(vector.supp()), vector.overlaps());
+        }
+    }
+
+    #[test]
+    fn kn_overlaps() {
+        let key =
+            QuasiCyclic::<5, 19>::from_support([2, 4, 13, 16, 17], [3, 4, 5, 13, 14]).unwrap();
+        assert_eq!(
+            patterns_kn(&key, 1),
+            ncw_patterns(&key, NearCodewordClass::N)
         );
+        assert_eq!(
+            patterns_kn(&key, 2),
+            ncw_patterns(&key, NearCodewordClass::TwoN)
+        );
+        let mut rng = rand::thread_rng();
+        for _ in 0..10 {
+            let supp = SparseVector::<8, 38>::random(&mut rng);
+            let overlaps = NcwOverlaps::with_kn(&key, supp.support(), 3);
+            let kn = overlaps.kn.unwrap();
+            assert_eq!(kn.max_k(), 3);
+            assert_eq!(kn.get(0), Some(0));
+            assert_eq!(kn.get(1), Some(overlaps.n));
+            assert_eq!(kn.get(2), Some(overlaps.two_n));
+            assert!(kn.get(3).unwrap() <= 8);
+            assert_eq!(kn.get(4), None);
+            assert_eq!(KnOverlaps::new(&key, supp.support(), 3, None), kn);
+            let without_kn = NcwOverlaps::new(&key, supp.support());
+            assert_eq!(
+                NcwOverlaps {
+                    kn: None,
+                    ..overlaps
+                },
+                without_kn
+            );
+            // Records without the k-fold overlaps still deserialize
+            let json = serde_json::to_value(without_kn).unwrap();
+            assert!(json.get("kn").is_none());
+            assert_eq!(
+                serde_json::from_value::<NcwOverlaps>(json).unwrap(),
+                without_kn
+            );
+            let json = serde_json::to_value(overlaps).unwrap();
+            assert_eq!(json["kn"], serde_json::to_value(kn.as_slice()).unwrap());
+            assert_eq!(
+                serde_json::from_value::<NcwOverlaps>(json).unwrap(),
+                overlaps
+            );
+        }
+        assert!(serde_json::from_str::<KnOverlaps>("[0, 1, 2, 3, 4, 5]").is_err());
+    }
+
+    #[test]
+    fn classified_vector_getters() {
+        // Note: ClassifiedVector::random isn't used here since custom_thread_rng
+        // would interfere with the thread count checked in random::tests.
+        let mut rng = rand::thread_rng();
+        let key = Key::random(&mut rng);
+        let e_supp = SparseErrorVector::random(&mut rng);
+        let classified = ClassifiedVector::new(key.clone(), e_supp.support());
+        assert_eq!(classified.key(), &key);
+        assert_eq!(classified.supp().len(), ERROR_WEIGHT);
+        assert!(classified.supp().windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(
+            classified.overlaps(),
+            NcwOverlaps::new(&key, classified.supp())
+        );
+        let mut supp = classified.supp().clone();
+        supp.reverse();
+        let reconstructed = ClassifiedVector::from_overlaps(key, supp, classified.overlaps());
+        assert_eq!(reconstructed.key(), classified.key());
+        assert_eq!(reconstructed.supp(), classified.supp());
+        assert_eq!(reconstructed.overlaps(), classified.overlaps());
+    }
+
+    #[test]
+    fn tagged_vector_source_roundtrip() {
+        let mut rng = rand::thread_rng();
+        let sources = [
+            ErrorVectorSource::Random,
+            ErrorVectorSource::NearCodeword(NearCodewordSet::new(NearCodewordClass::TwoN, 7, 20)),
+            ErrorVectorSource::Other,
+            ErrorVectorSource::Unknown,
+        ];
+        for source in sources {
+            let tagged = TaggedErrorVector::new(SparseErrorVector::random(&mut rng), source);
+            assert_eq!(tagged.source(), &source);
+            let json = serde_json::to_string(&tagged).unwrap();
+            let deserialized: TaggedErrorVector = serde_json::from_str(&json).unwrap();
+            assert_eq!(deserialized, tagged);
+            assert_eq!(deserialized.source(), &source);
+        }
+    }
+
+    #[test]
+    fn near_codeword_set_size_brute_force() {
+        use crate::graphs::combinations_from;
+        use std::collections::HashSet;
+        const LEN: usize = 11;
+        let key = QuasiCyclic::<3, LEN>::from_support([0, 1, 3], [0, 2, 7]).unwrap();
+        let mut exact_below_t = 0;
+        for class in [NearCodewordClass::C, NearCodewordClass::N] {
+            let patterns = ncw_patterns(&key, class);
+            let near_codewords: Vec<Vec<Index>> = (0..LEN as Index)
+                .flat_map(|shift| {
+                    patterns.iter().map(move |pattern| {
+                        let mut v = pattern.clone();
+                        shift_blockwise::<LEN>(&mut v, shift);
+                        v
+                    })
+                })
+                .collect();
+            let max_intersection = near_codewords
+                .iter()
+                .enumerate()
+                .flat_map(|(i, v1)| {
+                    near_codewords[i + 1..]
+                        .iter()
+                        .map(move |v2| v1.iter().filter(|idx| v2.contains(idx)).count())
+                })
+                .max()
+                .unwrap();
+            assert_eq!(
+                near_codeword_max_intersection::<LEN>(&patterns),
+                max_intersection
+            );
+            for t in 2..=5 {
+                for l in 0..=t {
+                    let vectors: HashSet<Vec<Index>> =
+                        combinations_from(2 * LEN as Index, t, 0, u128::MAX)
+                            .filter(|e| {
+                                near_codewords
+                                    .iter()
+                                    .any(|v| e.iter().filter(|idx| v.contains(idx)).count() == l)
+                            })
+                            .collect();
+                    let size = near_codeword_set_size::<LEN>(&patterns, l, t);
+                    if let Some(size) = size {
+                        assert_eq!(
+                            size,
+                            Natural::from(vectors.len()),
+                            "class {class}, t = {t}, l = {l}"
+                        );
+                        exact_below_t += usize::from(l < t && !vectors.is_empty());
+                    } else {
+                        assert!(2 * l <= t + max_intersection);
+                    }
+                }
+            }
+        }
+        assert!(exact_below_t > 0);
+        let patterns = vec![vec![0, 1, 2, 3, 4, 5]];
+        assert_eq!(near_codeword_max_intersection::<LEN>(&patterns), 5);
+        assert_eq!(near_codeword_set_fraction::<LEN>(&patterns, 2, 2), None);
+        assert_eq!(
+            near_codeword_set_fraction::<LEN>(&patterns, 6, 6),
+            Some(11.0 / 74613.0)
+        );
+    }
+
+    #[test]
+    fn near_codeword_set_fraction_bounds() {
+        let mut rng = rand::thread_rng();
+        for class in [NearCodewordClass::C, NearCodewordClass::N] {
+            let patterns = ncw_patterns(&Key::random(&mut rng), class);
+            for t in [ERROR_WEIGHT, 2 * BLOCK_WEIGHT] {
+                for l in 0..=t + 1 {
+                    if let Some(fraction) =
+                        near_codeword_set_fraction::<BLOCK_LENGTH>(&patterns, l, t)
+                    {
+                        assert!(
+                            (0.0..=1.0).contains(&fraction),
+                            "class {class}, t = {t}, l = {l}"
+                        );
+                    }
+                }
+                assert_eq!(
+                    near_codeword_set_fraction::<BLOCK_LENGTH>(&patterns, 0, t),
+                    None
+                );
+            }
+        }
     }
 
     #[test]
@@ -441,6 +1108,12 @@ mod tests {
         );
         let max_overlap = near_codeword_max_overlap::<587>(&supp, &patterns);
         assert_eq!(max_overlap, 1);
+        let (pattern_index, shift, count) = near_codeword_argmax::<587>(&supp, &patterns);
+        assert_eq!(count, max_overlap);
+        let mut shifted = patterns[pattern_index].clone();
+        shift_blockwise::<587>(&mut shifted, shift);
+        let overlap = supp.iter().filter(|idx| shifted.contains(idx)).count();
+        assert_eq!(overlap, count);
     }
 
     #[test]
@@ -462,8 +1135,14 @@ mod tests {
             NcwOverlaps {
                 c: 4,
                 n: 6,
-                two_n: 8
+                two_n: 8,
+                kn: None,
             }
         );
+        let (class, shift, count) = nearest_near_codeword(&key, &supp);
+        assert_eq!((class, count), (NearCodewordClass::TwoN, 8));
+        let patterns = ncw_patterns(&key, NearCodewordClass::TwoN);
+        let (_, argmax_shift, _) = near_codeword_argmax::<587>(&supp, &patterns);
+        assert_eq!(shift, argmax_shift);
     }
 }
@@ -1,3 +1,7 @@
+use crate::threshold::bf_threshold_min;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
 // Can change these parameters at compile-time using environment variables
 pub const BLOCK_LENGTH: usize = env_or_usize!("BIKE_BLOCK_LENGTH", 587);
 pub const BLOCK_WEIGHT: usize = env_or_usize!("BIKE_BLOCK_WEIGHT", 15);
@@ -33,6 +37,85 @@ const fn _compile_time_assertions() {
     const _: () = assert!(NB_ITER >= 1, "NB_ITER must be positive");
 }
 
+/// A set of decoder parameters: block length `r`, block weight `d`, error weight `t`, number
+/// of iterations `nb_iter`, and gray threshold difference `tau`. The compile-time parameters
+/// are checked by `_compile_time_assertions`; `ParamSet::validate` performs the same checks
+/// at runtime, for parameters that aren't known at compile time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParamSet {
+    pub r: usize,
+    pub d: usize,
+    pub t: usize,
+    pub nb_iter: usize,
+    pub tau: u8,
+}
+
+impl ParamSet {
+    /// The parameters set at compile time.
+    pub const fn compiled() -> Self {
+        Self {
+            r: BLOCK_LENGTH,
+            d: BLOCK_WEIGHT,
+            t: ERROR_WEIGHT,
+            nb_iter: NB_ITER,
+            tau: GRAY_THRESHOLD_DIFF,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), ParamError> {
+        if self.r % 2 == 0 {
+            return Err(ParamError::EvenBlockLength(self.r));
+        }
+        if 2 * self.r > u32::MAX as usize {
+            return Err(ParamError::BlockLengthTooLarge(self.r));
+        }
+        if !(0 < self.d && self.d < self.r) {
+            return Err(ParamError::BlockWeightOutOfRange(self.d, self.r));
+        }
+        if self.d > u8::MAX as usize {
+            return Err(ParamError::BlockWeightTooLarge(self.d));
+        }
+        if !(0 < self.t && self.t < 2 * self.r) {
+            return Err(ParamError::ErrorWeightOutOfRange(self.t, 2 * self.r));
+        }
+        if self.nb_iter == 0 {
+            return Err(ParamError::NoIterations);
+        }
+        let threshold_min = bf_threshold_min(self.d);
+        if self.tau >= threshold_min {
+            return Err(ParamError::GrayThresholdDiffTooLarge(
+                self.tau,
+                threshold_min,
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Default for ParamSet {
+    fn default() -> Self {
+        Self::compiled()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Error, PartialEq, Eq)]
+pub enum ParamError {
+    #[error("block length r = {0} must be odd")]
+    EvenBlockLength(usize),
+    #[error("block length r = {0} too large (2r must fit in u32)")]
+    BlockLengthTooLarge(usize),
+    #[error("block weight d = {0} must be positive and less than r = {1}")]
+    BlockWeightOutOfRange(usize, usize),
+    #[error("block weight d = {0} > 255 not supported")]
+    BlockWeightTooLarge(usize),
+    #[error("error weight t = {0} must be positive and less than 2r = {1}")]
+    ErrorWeightOutOfRange(usize, usize),
+    #[error("number of iterations must be positive")]
+    NoIterations,
+    #[error("gray threshold difference tau = {0} must be less than the minimum threshold {1}")]
+    GrayThresholdDiffTooLarge(u8, u8),
+}
+
 #[doc(hidden)]
 pub mod __macro {
     pub use ::core::option::Option;
@@ -66,6 +149,65 @@ use env_or_usize;
 mod tests {
     use super::*;
 
+    #[test]
+    fn param_set_validation() {
+        let params = ParamSet {
+            r: 587,
+            d: 15,
+            t: 18,
+            nb_iter: 7,
+            tau: 3,
+        };
+        assert_eq!(params.validate(), Ok(()));
+        assert_eq!(ParamSet::compiled().validate(), Ok(()));
+        for (invalid, err) in [
+            (
+                ParamSet { r: 588, ..params },
+                ParamError::EvenBlockLength(588),
+            ),
+            (
+                ParamSet {
+                    r: 1 << 31 | 1,
+                    ..params
+                },
+                ParamError::BlockLengthTooLarge(1 << 31 | 1),
+            ),
+            (
+                ParamSet { d: 0, ..params },
+                ParamError::BlockWeightOutOfRange(0, 587),
+            ),
+            (
+                ParamSet { d: 587, ..params },
+                ParamError::BlockWeightOutOfRange(587, 587),
+            ),
+            (
+                ParamSet { d: 256, ..params },
+                ParamError::BlockWeightTooLarge(256),
+            ),
+            (
+                ParamSet { t: 0, ..params },
+                ParamError::ErrorWeightOutOfRange(0, 1174),
+            ),
+            (
+                ParamSet { t: 1174, ..params },
+                ParamError::ErrorWeightOutOfRange(1174, 1174),
+            ),
+            (
+                ParamSet {
+                    nb_iter: 0,
+                    ..params
+                },
+                ParamError::NoIterations,
+            ),
+            (
+                ParamSet { tau: 8, ..params },
+                ParamError::GrayThresholdDiffTooLarge(8, 8),
+            ),
+        ] {
+            assert_eq!(invalid.validate(), Err(err));
+        }
+    }
+
     #[test]
     fn env_usize_default_value() {
         const N: usize = env_or_usize!("ENV_USIZE_TEST_VAR", 42);
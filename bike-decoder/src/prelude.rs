@@ -0,0 +1,11 @@
+//! Re-exports of the items intended for external use, so that downstream code can
+//! import them all with `use bike_decoder::prelude::*`.
+
+pub use crate::{
+    decoder::{bgf_decoder, DecodingResult},
+    keys::Key,
+    ncw::{NcwOverlaps, TaggedErrorVector},
+    random::Seed,
+    syndrome::Syndrome,
+    vectors::{ErrorVector, SparseErrorVector},
+};
@@ -8,10 +8,13 @@ use once_cell::sync::OnceCell as OnceLock;
 use rand::{rngs::OsRng, RngCore, SeedableRng};
 use rand_xoshiro::Xoshiro256PlusPlus;
 use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
 use std::{
     cell::UnsafeCell,
     fmt,
+    num::ParseIntError,
     rc::Rc,
+    str::FromStr,
     sync::atomic::{AtomicU32, Ordering},
     thread_local,
 };
@@ -39,6 +42,24 @@ pub fn get_rng_from_seed(seed: Seed, jumps: u32) -> Xoshiro256PlusPlus {
     rng
 }
 
+/// Same as `get_rng_from_seed`, but takes the seed and number of jumps as an `RngState`.
+#[inline]
+pub fn get_rng_from_state(state: RngState) -> Xoshiro256PlusPlus {
+    get_rng_from_seed(state.seed, state.index)
+}
+
+/// Derives a child RNG from `rng` whose output stream is disjoint from both the parent's
+/// subsequent outputs and the per-thread streams obtained with `jump`. The child starts one
+/// `long_jump` ahead of the parent, and the parent is then advanced by a second `long_jump`,
+/// so repeated splits give distinct children. The result depends only on the parent's
+/// state, so splitting is reproducible.
+pub fn split_rng(rng: &mut Xoshiro256PlusPlus) -> Xoshiro256PlusPlus {
+    rng.long_jump();
+    let child = rng.clone();
+    rng.long_jump();
+    child
+}
+
 pub fn global_seed() -> Option<Seed> {
     GLOBAL_SEED.get().copied()
 }
@@ -78,6 +99,23 @@ pub fn custom_thread_rng() -> CustomThreadRng {
     }
 }
 
+/// Replaces the state of the current thread's `custom_thread_rng` with that of
+/// `get_rng_from_seed(seed, jumps)`. Existing `CustomThreadRng` handles on this thread share
+/// the replaced state, so they also continue from the new seed, while other threads are
+/// unaffected.
+///
+/// Results are then no longer determined by the global seed alone: to reproduce them, the
+/// seeds and jumps passed to this function, and the points at which it was called, have to
+/// be recorded separately.
+pub fn reseed_current_thread(seed: Seed, jumps: u32) {
+    CUSTOM_THREAD_RNG_KEY.with(|t| {
+        // SAFETY: The RNG is thread-local, and CustomThreadRng only borrows it for the
+        // duration of a single method call, so no other references to it exist here.
+        let rng = unsafe { &mut *t.get() };
+        *rng = get_rng_from_seed(seed, jumps);
+    });
+}
+
 // Note: Debug implementation intentionally leaks internal state.
 #[derive(Clone, Debug)]
 pub struct CustomThreadRng {
@@ -123,6 +161,7 @@ impl RngCore for CustomThreadRng {
 }
 
 type SeedInner = [u8; 32];
+const SEED_HEX_LENGTH: usize = 2 * std::mem::size_of::<SeedInner>();
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Seed(#[serde(with = "hex::serde")] SeedInner);
@@ -137,8 +176,20 @@ impl Seed {
         OsRng.fill_bytes(&mut buf);
         Seed(buf)
     }
+
+    /// Derives a seed from a passphrase as the SHA3-256 hash of the passphrase with a fixed
+    /// domain separation prefix, so experiments can be shared by name rather than by hex seed.
+    /// This isn't meant to make the seed hard to guess.
+    pub fn from_passphrase(phrase: &str) -> Self {
+        let mut hasher = Sha3_256::new();
+        hasher.update(SEED_PASSPHRASE_DOMAIN);
+        hasher.update(phrase.as_bytes());
+        Self(hasher.finalize().into())
+    }
 }
 
+const SEED_PASSPHRASE_DOMAIN: &[u8] = b"rust_bike_decoder seed passphrase v1\0";
+
 impl From<Seed> for SeedInner {
     #[inline]
     fn from(seed: Seed) -> Self {
@@ -149,10 +200,22 @@ impl From<Seed> for SeedInner {
 impl FromHex for Seed {
     type Error = SeedFromHexError;
     fn from_hex<T: AsRef<[u8]>>(hex: T) -> Result<Self, Self::Error> {
+        if hex.as_ref().len() != SEED_HEX_LENGTH {
+            return Err(hex::FromHexError::InvalidStringLength.into());
+        }
         Ok(Self(SeedInner::from_hex(hex)?))
     }
 }
 
+impl FromStr for Seed {
+    type Err = SeedFromHexError;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_hex(s)
+    }
+}
+
 impl ToHex for Seed {
     fn encode_hex<T: FromIterator<char>>(&self) -> T {
         self.0.encode_hex()
@@ -170,13 +233,79 @@ impl fmt::Display for Seed {
 }
 
 #[derive(Debug, Error)]
-#[error("PRNG seed must be given by 256-bit hex string")]
+#[error("PRNG seed must be given by 256-bit hex string (expected 64 hex chars): {0}")]
 pub struct SeedFromHexError(#[from] pub hex::FromHexError);
 
+/// A PRNG seed together with a thread index, which together determine the state of the
+/// PRNG used by that thread. Parsed from and displayed as a string of the form
+/// `<hex seed>:<index>`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RngState {
+    seed: Seed,
+    index: u32,
+}
+
+impl RngState {
+    #[inline]
+    pub fn new(seed: Seed, index: u32) -> Self {
+        Self { seed, index }
+    }
+
+    #[inline]
+    pub fn seed(&self) -> Seed {
+        self.seed
+    }
+
+    #[inline]
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+}
+
+impl FromStr for RngState {
+    type Err = RngStateParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (seed, index) = s.split_once(':').ok_or(RngStateParseError::MissingIndex)?;
+        Ok(Self {
+            seed: seed.parse()?,
+            index: index.parse()?,
+        })
+    }
+}
+
+impl fmt::Display for RngState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.seed, self.index)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum RngStateParseError {
+    #[error("PRNG state must have the form <seed>:<index>")]
+    MissingIndex,
+    #[error(transparent)]
+    Seed(#[from] SeedFromHexError),
+    #[error("PRNG state index must be an integer in the range 0..2^32: {0}")]
+    Index(#[from] ParseIntError),
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn seed_from_passphrase() {
+        let seed = Seed::from_passphrase("bike-lvl1-sweep-3");
+        assert_eq!(seed, Seed::from_passphrase("bike-lvl1-sweep-3"));
+        assert_ne!(seed, Seed::from_passphrase("bike-lvl1-sweep-4"));
+        assert_ne!(seed, Seed::from_passphrase(""));
+        assert_ne!(
+            Seed::from_passphrase("bike-lvl1-sweep-3 "),
+            Seed::from_passphrase("bike-lvl1-sweep-3")
+        );
+    }
+
     #[test]
     fn thread_rng_seeds() {
         let mut rng = custom_thread_rng();
@@ -196,4 +325,94 @@ mod tests {
         assert_eq!(x, y);
         assert_eq!(global_thread_count(), 2);
     }
+
+    #[test]
+    fn split_rng_reproducible() {
+        let mut parent = Xoshiro256PlusPlus::from_seed(Seed::from_entropy().into());
+        let mut parent_copy = parent.clone();
+        let mut child = split_rng(&mut parent);
+        let mut child_copy = split_rng(&mut parent_copy);
+        let child_outputs: Vec<u64> = (0..16).map(|_| child.next_u64()).collect();
+        let child_copy_outputs: Vec<u64> = (0..16).map(|_| child_copy.next_u64()).collect();
+        assert_eq!(child_outputs, child_copy_outputs);
+        let parent_outputs: Vec<u64> = (0..16).map(|_| parent.next_u64()).collect();
+        assert_ne!(child_outputs, parent_outputs);
+        let mut second_child = split_rng(&mut parent_copy);
+        let second_child_outputs: Vec<u64> = (0..16).map(|_| second_child.next_u64()).collect();
+        assert_ne!(child_outputs, second_child_outputs);
+    }
+
+    #[test]
+    fn seed_display_parse_roundtrip() {
+        for _ in 0..10 {
+            let seed = Seed::from_entropy();
+            let seed_str = seed.to_string();
+            assert_eq!(seed_str.len(), SEED_HEX_LENGTH);
+            assert_eq!(seed_str.parse::<Seed>().unwrap(), seed);
+            assert_eq!(Seed::from_hex(&seed_str).unwrap(), seed);
+        }
+        let zero = Seed::new([0; 32]);
+        assert_eq!(zero.to_string(), "0".repeat(SEED_HEX_LENGTH));
+        assert_eq!(zero.to_string().parse::<Seed>().unwrap(), zero);
+    }
+
+    #[test]
+    fn seed_parse_invalid() {
+        for too_short in ["", "0", "abc", &"0".repeat(SEED_HEX_LENGTH - 1)] {
+            assert!(matches!(
+                Seed::from_hex(too_short),
+                Err(SeedFromHexError(hex::FromHexError::InvalidStringLength))
+            ));
+        }
+        let too_long = "0".repeat(SEED_HEX_LENGTH + 2);
+        assert!(matches!(
+            too_long.parse::<Seed>(),
+            Err(SeedFromHexError(hex::FromHexError::InvalidStringLength))
+        ));
+        let non_hex = "g".repeat(SEED_HEX_LENGTH);
+        assert!(matches!(
+            non_hex.parse::<Seed>(),
+            Err(SeedFromHexError(hex::FromHexError::InvalidHexCharacter {
+                c: 'g',
+                index: 0
+            }))
+        ));
+    }
+
+    #[test]
+    fn rng_state_parse() {
+        let seed = Seed::from_entropy();
+        let state: RngState = format!("{seed}:17").parse().unwrap();
+        assert_eq!(state, RngState::new(seed, 17));
+        assert_eq!(state.to_string().parse::<RngState>().unwrap(), state);
+        assert_eq!(
+            get_rng_from_state(state).next_u64(),
+            get_rng_from_seed(seed, 17).next_u64()
+        );
+        let max_index = format!("{seed}:{}", u32::MAX);
+        assert_eq!(max_index.parse::<RngState>().unwrap().index(), u32::MAX);
+    }
+
+    #[test]
+    fn rng_state_parse_invalid() {
+        let seed = Seed::from_entropy();
+        assert!(matches!(
+            seed.to_string().parse::<RngState>(),
+            Err(RngStateParseError::MissingIndex)
+        ));
+        assert!(matches!(
+            format!("{}:3", "g".repeat(SEED_HEX_LENGTH)).parse::<RngState>(),
+            Err(RngStateParseError::Seed(_))
+        ));
+        assert!(matches!(
+            "abc:3".parse::<RngState>(),
+            Err(RngStateParseError::Seed(_))
+        ));
+        for bad_index in ["", "-1", "x", "4294967296", "99999999999999999999"] {
+            assert!(matches!(
+                format!("{seed}:{bad_index}").parse::<RngState>(),
+                Err(RngStateParseError::Index(_))
+            ));
+        }
+    }
 }
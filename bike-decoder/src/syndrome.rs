@@ -1,7 +1,13 @@
 use crate::keys::Key;
 use crate::parameters::*;
-use crate::vectors::{DenseVector, ErrorVector, SparseErrorVector};
-use std::{fmt, ops::Add};
+use crate::vectors::{
+    DenseVector, DynErrorVector, ErrorVector, Index, InvalidSupport, SparseErrorVector,
+};
+use rand::Rng;
+use std::{
+    fmt,
+    ops::{Add, AddAssign, Sub, SubAssign},
+};
 
 // Note: syndromes are padded out to 2*SIZE_AVX so they can be passed to
 // code in decoder.rs that uses AVX2 instructions.
@@ -20,9 +26,46 @@ impl Syndrome {
         Self(DenseVector::new(v))
     }
 
+    /// Constructs the syndrome whose nonzero bits are at the given indices, which must be
+    /// distinct and less than `BLOCK_LENGTH`.
+    pub fn from_support(supp: &[usize]) -> Result<Self, InvalidSupport> {
+        let mut syn = Self::zero();
+        for &i in supp {
+            if i >= BLOCK_LENGTH {
+                return Err(InvalidSupport::OutOfBounds(BLOCK_LENGTH));
+            } else if syn.get(i) {
+                return Err(InvalidSupport::RepeatedIndex);
+            }
+            syn.set_one(i);
+        }
+        Ok(syn)
+    }
+
+    /// Generates a syndrome with uniformly random bits, not necessarily the syndrome of
+    /// any low-weight error vector.
+    pub fn random<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        let mut s = [false; BLOCK_LENGTH];
+        for bit in s.iter_mut() {
+            *bit = rng.gen();
+        }
+        Self::new(s)
+    }
+
+    #[inline]
     pub fn from_sparse(key: &Key, err: &SparseErrorVector) -> Self {
+        Self::from_error_support(key, err.support())
+    }
+
+    /// Computes the syndrome of an error vector whose weight may differ from `ERROR_WEIGHT`.
+    #[inline]
+    pub fn from_dyn_sparse(key: &Key, err: &DynErrorVector) -> Self {
+        debug_assert_eq!(err.length() as usize, ROW_LENGTH);
+        Self::from_error_support(key, err.support())
+    }
+
+    fn from_error_support(key: &Key, supp: &[Index]) -> Self {
         let mut s = [false; BLOCK_LENGTH];
-        for &i in err.support() {
+        for &i in supp {
             if i < BLOCK_LENGTH as u32 {
                 for &j in key.h0().support() {
                     s[(i + j) as usize % BLOCK_LENGTH] ^= true;
@@ -36,6 +79,16 @@ impl Syndrome {
         Self::new(s)
     }
 
+    /// Computes the syndrome of a dense error vector. This agrees with `from_sparse`:
+    ///
+    /// ```
+    /// use bike_decoder::{keys::Key, syndrome::Syndrome, vectors::SparseErrorVector};
+    ///
+    /// let mut rng = rand::thread_rng();
+    /// let key = Key::random(&mut rng);
+    /// let e = SparseErrorVector::random(&mut rng);
+    /// assert_eq!(Syndrome::from_sparse(&key, &e), Syndrome::from_dense(&key, &e.dense()));
+    /// ```
     pub fn from_dense(key: &Key, err: &ErrorVector) -> Self {
         let mut s = [false; BLOCK_LENGTH];
         for i in 0..BLOCK_LENGTH {
@@ -60,6 +113,15 @@ impl Syndrome {
         self.0.get(i)
     }
 
+    /// Indices of the nonzero bits of the syndrome, in increasing order.
+    pub fn support(&self) -> Vec<usize> {
+        self.contents()
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &bit)| bit.then_some(i))
+            .collect()
+    }
+
     #[inline]
     pub fn contents(&self) -> &[bool] {
         &self.0.contents()[..BLOCK_LENGTH]
@@ -95,6 +157,13 @@ impl Syndrome {
         bytecount::count(bytes, 1_u8)
     }
 
+    /// Adds `other` to this syndrome componentwise mod 2, in place.
+    #[inline]
+    pub fn xor_assign(&mut self, other: &Syndrome) {
+        self.0
+            .xor_with(other.contents_with_buffer().iter().copied());
+    }
+
     #[inline]
     pub fn duplicate_contents(&mut self) {
         self.0.duplicate_up_to(BLOCK_LENGTH);
@@ -111,8 +180,55 @@ impl Syndrome {
             }
         }
     }
+
+    /// Returns the change in the Hamming weight of the syndrome that would result from
+    /// flipping bit `pos` of block `block_idx` of the error vector, without modifying the
+    /// syndrome.
+    pub fn weight_delta_of_flip(&self, key: &Key, block_idx: usize, pos: usize) -> i32 {
+        let h_supp = [key.h0().support(), key.h1().support()];
+        h_supp[block_idx]
+            .iter()
+            .map(|&j| {
+                if self.get((pos + j as usize) % BLOCK_LENGTH) {
+                    -1
+                } else {
+                    1
+                }
+            })
+            .sum()
+    }
+
+    /// Updates the syndrome after flipping bit `pos` of block `block_idx` of the error
+    /// vector, and updates the unsatisfied parity check counts `upc` to match the new
+    /// syndrome. Only the counts adjacent to the affected parity checks are modified.
+    pub fn recompute_flipped_bit_with_upc(
+        &mut self,
+        key: &Key,
+        block_idx: usize,
+        pos: usize,
+        upc: &mut [[u8; BLOCK_LENGTH]; 2],
+    ) {
+        let h_supp = [key.h0().support(), key.h1().support()];
+        for &j in h_supp[block_idx] {
+            let check = (pos + j as usize) % BLOCK_LENGTH;
+            self.flip(check);
+            let unsatisfied = self.get(check);
+            for (upc_k, h_supp_k) in upc.iter_mut().zip(h_supp) {
+                for &l in h_supp_k {
+                    let upc_ki = &mut upc_k[(check + BLOCK_LENGTH - l as usize) % BLOCK_LENGTH];
+                    *upc_ki = if unsatisfied {
+                        upc_ki.saturating_add(1)
+                    } else {
+                        upc_ki.saturating_sub(1)
+                    };
+                }
+            }
+        }
+    }
 }
 
+// Addition and subtraction are both componentwise mod 2, so the syndrome of a sum of
+// error vectors is the sum of their syndromes.
 impl Add for Syndrome {
     type Output = Self;
     fn add(self, other: Self) -> Self {
@@ -120,6 +236,41 @@ impl Add for Syndrome {
     }
 }
 
+impl Sub for Syndrome {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        Self(self.0 - other.0)
+    }
+}
+
+impl AddAssign<&Syndrome> for Syndrome {
+    #[inline]
+    fn add_assign(&mut self, other: &Syndrome) {
+        self.xor_assign(other);
+    }
+}
+
+impl AddAssign for Syndrome {
+    #[inline]
+    fn add_assign(&mut self, other: Self) {
+        self.xor_assign(&other);
+    }
+}
+
+impl SubAssign<&Syndrome> for Syndrome {
+    #[inline]
+    fn sub_assign(&mut self, other: &Syndrome) {
+        self.xor_assign(other);
+    }
+}
+
+impl SubAssign for Syndrome {
+    #[inline]
+    fn sub_assign(&mut self, other: Self) {
+        self.xor_assign(&other);
+    }
+}
+
 impl PartialEq for Syndrome {
     // Equality ignores the extra buffer space
     fn eq(&self, other: &Self) -> bool {
@@ -140,10 +291,48 @@ impl fmt::Display for Syndrome {
     }
 }
 
+/// Samples uniformly random error vectors until one has syndrome weight `target_ws` with
+/// the key `(h0, h1)`, giving up after `max_attempts` samples. The result is uniformly
+/// distributed among error vectors with the target syndrome weight. Since each error bit
+/// flips `BLOCK_WEIGHT` syndrome bits, the syndrome weight always has the same parity as
+/// `ERROR_WEIGHT * BLOCK_WEIGHT`, and other targets are never reached.
+pub fn sample_error_for_syndrome_weight<R>(
+    key: &Key,
+    target_ws: usize,
+    rng: &mut R,
+    max_attempts: usize,
+) -> Option<SparseErrorVector>
+where
+    R: Rng + ?Sized,
+{
+    (0..max_attempts)
+        .map(|_| SparseErrorVector::random(rng))
+        .find(|e_in| Syndrome::from_sparse(key, e_in).hamming_weight() == target_ws)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn syndrome_weight_targeted_sampling() {
+        let mut rng = rand::thread_rng();
+        let key = Key::random(&mut rng);
+        for _ in 0..10 {
+            let target_ws =
+                Syndrome::from_sparse(&key, &SparseErrorVector::random(&mut rng)).hamming_weight();
+            let e_in = sample_error_for_syndrome_weight(&key, target_ws, &mut rng, 100_000)
+                .expect("Target syndrome weight should be reached");
+            assert_eq!(
+                Syndrome::from_sparse(&key, &e_in).hamming_weight(),
+                target_ws
+            );
+        }
+        let unreachable_ws = (ERROR_WEIGHT * BLOCK_WEIGHT + 1) % 2;
+        assert!(sample_error_for_syndrome_weight(&key, unreachable_ws, &mut rng, 100).is_none());
+        assert!(sample_error_for_syndrome_weight(&key, 0, &mut rng, 0).is_none());
+    }
+
     #[test]
     fn syndrome_weight() {
         let mut syn = Syndrome::zero();
@@ -153,4 +342,80 @@ mod tests {
         }
         assert_eq!(syn.hamming_weight(), supp.len() - 2);
     }
+
+    #[test]
+    fn syndrome_support_roundtrip() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let syn = Syndrome::random(&mut rng);
+            let supp = syn.support();
+            assert_eq!(supp.len(), syn.hamming_weight());
+            assert!(supp.iter().all(|&i| syn.get(i)));
+            assert_eq!(Syndrome::from_support(&supp).unwrap(), syn);
+        }
+        assert_eq!(Syndrome::from_support(&[]).unwrap(), Syndrome::zero());
+        assert!(matches!(
+            Syndrome::from_support(&[0, BLOCK_LENGTH]),
+            Err(InvalidSupport::OutOfBounds(BLOCK_LENGTH))
+        ));
+        assert!(matches!(
+            Syndrome::from_support(&[3, 1, 3]),
+            Err(InvalidSupport::RepeatedIndex)
+        ));
+    }
+
+    #[test]
+    fn syndrome_add_sub() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let key = Key::random(&mut rng);
+            let e1 = SparseErrorVector::random(&mut rng);
+            let e2 = SparseErrorVector::random(&mut rng);
+            let s1 = Syndrome::from_sparse(&key, &e1);
+            let s2 = Syndrome::from_sparse(&key, &e2);
+            let s_sum = Syndrome::from_dense(&key, &(e1.dense() + e2.dense()));
+            assert_eq!(s1.clone() + s2.clone(), s_sum);
+            assert_eq!(s1.clone() - s2.clone(), s_sum);
+            assert_eq!(s_sum - s2, s1);
+            assert_eq!(s1.clone() - s1, Syndrome::zero());
+        }
+    }
+
+    #[test]
+    fn syndrome_xor_assign() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let a = Syndrome::random(&mut rng);
+            let b = Syndrome::random(&mut rng);
+            let mut c = a.clone();
+            c.xor_assign(&b);
+            assert_eq!(c, a.clone() + b.clone());
+            c -= &b;
+            assert_eq!(c, a);
+            c += b.clone();
+            assert_eq!(c, a.clone() - b.clone());
+            c -= b;
+            assert_eq!(c, a);
+            c += &a;
+            assert_eq!(c, Syndrome::zero());
+        }
+    }
+
+    #[test]
+    fn weight_delta_matches_flip() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let key = Key::random(&mut rng);
+            let e_supp = SparseErrorVector::random(&mut rng);
+            let syn = Syndrome::from_sparse(&key, &e_supp);
+            let weight = syn.hamming_weight() as i32;
+            for block_idx in 0..2 {
+                let pos = rng.gen_range(0..BLOCK_LENGTH);
+                let delta = syn.weight_delta_of_flip(&key, block_idx, pos);
+                let mut flipped = syn.clone();
+                flipped.recompute_flipped_bit(&key, block_idx, pos);
+                assert_eq!(flipped.hamming_weight() as i32, weight + delta);
+            }
+        }
+    }
 }
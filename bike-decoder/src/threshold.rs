@@ -3,10 +3,93 @@ use malachite::{
     rounding_modes::RoundingMode,
     Natural, Rational,
 };
+use once_cell::sync::Lazy;
+use std::{collections::HashMap, fmt::Write, ops::Index, sync::Mutex};
 use thiserror::Error;
 
+type XCache = HashMap<(usize, usize, usize), f64>;
+
+// Values of compute_x already computed, keyed by (r, d, t)
+static X_CACHE: Lazy<Mutex<XCache>> = Lazy::new(Default::default);
+
+/// Cache of thresholds for every possible syndrome weight, for parameters `(r, d, t)`
+/// that may be chosen at runtime. Lookups are O(1), matching the static cache used
+/// by the decoder for the compile-time parameters.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ThresholdCache {
+    r: usize,
+    d: usize,
+    t: usize,
+    thresholds: Vec<u8>,
+}
+
+impl ThresholdCache {
+    pub fn new(r: usize, d: usize, t: usize) -> Result<Self, ThresholdError> {
+        Ok(Self {
+            r,
+            d,
+            t,
+            thresholds: build_threshold_cache(r, d, t)?,
+        })
+    }
+
+    #[inline]
+    pub fn params(&self) -> (usize, usize, usize) {
+        (self.r, self.d, self.t)
+    }
+
+    #[inline]
+    pub fn get(&self, ws: usize) -> Result<u8, ThresholdError> {
+        self.thresholds
+            .get(ws)
+            .copied()
+            .ok_or(ThresholdError::WeightError(ws, self.r))
+    }
+
+    #[inline]
+    pub fn thresholds(&self) -> &[u8] {
+        &self.thresholds
+    }
+}
+
+impl Index<usize> for ThresholdCache {
+    type Output = u8;
+
+    #[inline]
+    fn index(&self, ws: usize) -> &u8 {
+        &self.thresholds[ws]
+    }
+}
+
+/// Rule for choosing the bit-flipping threshold from the syndrome weight `ws`.
+pub trait ThresholdRule {
+    fn threshold(&self, ws: usize) -> u8;
+}
+
+impl ThresholdRule for ThresholdCache {
+    #[inline]
+    fn threshold(&self, ws: usize) -> u8 {
+        self[ws]
+    }
+}
+
+/// Threshold `slope * ws + intercept`, rounded up and clamped to the range of `u8`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AffineThreshold {
+    pub slope: f64,
+    pub intercept: f64,
+}
+
+impl ThresholdRule for AffineThreshold {
+    #[inline]
+    fn threshold(&self, ws: usize) -> u8 {
+        // Float to int casts saturate, so this clamps to 0..=255
+        (self.slope * ws as f64 + self.intercept).ceil() as u8
+    }
+}
+
 pub fn build_threshold_cache(r: usize, d: usize, t: usize) -> Result<Vec<u8>, ThresholdError> {
-    let x = compute_x(r, d, t);
+    let x = compute_x_cached(r, d, t);
     let mut threshold_cache: Vec<u8> = Vec::with_capacity(r + 1);
     for ws in 0..=r {
         threshold_cache.push(exact_threshold_ineq(ws, r, d, t, Some(x))?);
@@ -14,6 +97,23 @@ pub fn build_threshold_cache(r: usize, d: usize, t: usize) -> Result<Vec<u8>, Th
     Ok(threshold_cache)
 }
 
+/// Pairs `(ws, threshold)` for every syndrome weight `ws` in `0..=r`.
+pub fn threshold_table(r: usize, d: usize, t: usize) -> Result<Vec<(usize, u8)>, ThresholdError> {
+    Ok(build_threshold_cache(r, d, t)?
+        .into_iter()
+        .enumerate()
+        .collect())
+}
+
+/// Formats a table produced by `threshold_table` as CSV with a header row.
+pub fn threshold_table_csv(table: &[(usize, u8)]) -> String {
+    let mut csv = String::from("syndrome_weight,threshold\n");
+    for (ws, threshold) in table {
+        writeln!(csv, "{ws},{threshold}").expect("Writing to String should not fail");
+    }
+    csv
+}
+
 pub const fn bf_threshold_min(block_weight: usize) -> u8 {
     assert!(
         block_weight <= u8::MAX as usize,
@@ -30,7 +130,7 @@ pub const fn bf_masked_threshold(block_weight: usize) -> u8 {
     (block_weight as u8 + 1) / 2 + 1
 }
 
-fn big_binomial(n: usize, k: usize) -> Natural {
+pub(crate) fn big_binomial(n: usize, k: usize) -> Natural {
     Natural::binomial_coefficient(Natural::from(n), Natural::from(k))
 }
 
@@ -46,10 +146,30 @@ pub fn compute_x(r: usize, d: usize, t: usize) -> f64 {
         .rounding_into(RoundingMode::Nearest)
 }
 
+/// Same as `compute_x`, but memoizes the result for each `(r, d, t)`, so repeated calls
+/// with the same parameters (e.g., when sweeping over syndrome weights) are cheap.
+pub fn compute_x_cached(r: usize, d: usize, t: usize) -> f64 {
+    let cached = X_CACHE
+        .lock()
+        .expect("X_CACHE lock should not be poisoned")
+        .get(&(r, d, t))
+        .copied();
+    cached.unwrap_or_else(|| {
+        // Compute without holding the lock, since this may take a while. If another
+        // thread computes the same value in the meantime, the results are identical.
+        let x = compute_x(r, d, t);
+        X_CACHE
+            .lock()
+            .expect("X_CACHE lock should not be poisoned")
+            .insert((r, d, t), x);
+        x
+    })
+}
+
 fn threshold_constants(ws: usize, r: usize, d: usize, t: usize, x: Option<f64>) -> (f64, f64) {
     let n = 2 * r;
     let w = 2 * d;
-    let x = x.unwrap_or_else(|| compute_x(r, d, t));
+    let x = x.unwrap_or_else(|| compute_x_cached(r, d, t));
     let pi1 = (ws as f64 + x) / (t * d) as f64;
     let pi0 = ((w * ws) as f64 - x) / ((n - t) * d) as f64;
     (pi0, pi1)
@@ -127,6 +247,23 @@ mod tests {
     use super::*;
     use crate::parameters::{BLOCK_LENGTH, BLOCK_WEIGHT, ERROR_WEIGHT};
 
+    #[test]
+    fn affine_threshold() {
+        let rule = AffineThreshold {
+            slope: 0.0069722,
+            intercept: 13.530,
+        };
+        assert_eq!(rule.threshold(0), 14);
+        assert_eq!(rule.threshold(1000), 21);
+        let steep = AffineThreshold {
+            slope: -1.0,
+            intercept: 10.0,
+        };
+        assert_eq!(steep.threshold(5), 5);
+        assert_eq!(steep.threshold(20), 0);
+        assert_eq!(steep.threshold(usize::MAX), 0);
+    }
+
     #[test]
     fn known_x() {
         let (r, d, t) = (587, 15, 18);
@@ -135,6 +272,27 @@ mod tests {
         assert!((x_known - x_computed).abs() < 1e-9);
     }
 
+    #[test]
+    fn cached_x() {
+        let params = [(587, 15, 18), (1_000, 20, 30), (12_323, 71, 134)];
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                std::thread::spawn(move || {
+                    let (r, d, t) = params[i % params.len()];
+                    compute_x_cached(r, d, t)
+                })
+            })
+            .collect();
+        for (i, handle) in handles.into_iter().enumerate() {
+            let (r, d, t) = params[i % params.len()];
+            assert_eq!(handle.join().unwrap(), compute_x(r, d, t));
+        }
+        for (r, d, t) in params {
+            assert_eq!(compute_x_cached(r, d, t), compute_x(r, d, t));
+            assert!(X_CACHE.lock().unwrap().contains_key(&(r, d, t)));
+        }
+    }
+
     #[test]
     fn known_thresholds() {
         let cache = build_threshold_cache(BLOCK_LENGTH, BLOCK_WEIGHT, ERROR_WEIGHT).unwrap();
@@ -172,6 +330,39 @@ mod tests {
         }
     }
 
+    #[test]
+    fn threshold_table_entries() {
+        let (r, d, t) = (BLOCK_LENGTH, BLOCK_WEIGHT, ERROR_WEIGHT);
+        let table = threshold_table(r, d, t).unwrap();
+        assert_eq!(table.len(), r + 1);
+        // First entries of thresholds_no_min in known_thresholds, raised to the minimum 8
+        let known_no_min = [1, 2, 2, 3, 3, 3, 3, 3, 3, 4, 4, 4, 4, 4, 4, 4, 4, 4, 5, 5];
+        for (ws, &thresh) in known_no_min.iter().enumerate() {
+            assert_eq!(table[ws], (ws, thresh.max(bf_threshold_min(d))));
+        }
+        let csv = threshold_table_csv(&table[..3]);
+        assert_eq!(csv, "syndrome_weight,threshold\n0,8\n1,8\n2,8\n");
+        assert_eq!(threshold_table_csv(&table).lines().count(), table.len() + 1);
+    }
+
+    #[test]
+    fn runtime_threshold_cache() {
+        let (r, d, t) = (BLOCK_LENGTH, BLOCK_WEIGHT, ERROR_WEIGHT);
+        let cache = ThresholdCache::new(r, d, t).unwrap();
+        assert_eq!(cache.params(), (r, d, t));
+        assert_eq!(cache.thresholds().len(), r + 1);
+        let x = compute_x(r, d, t);
+        for ws in 0..=r {
+            let thresh = exact_threshold_ineq(ws, r, d, t, Some(x)).unwrap();
+            assert_eq!(cache.get(ws).unwrap(), thresh);
+            assert_eq!(cache[ws], thresh);
+        }
+        assert!(matches!(
+            cache.get(r + 1),
+            Err(ThresholdError::WeightError(ws, len)) if (ws, len) == (r + 1, r)
+        ));
+    }
+
     #[test]
     fn big_threshold_cache() {
         // BIKE security level 5 parameters
@@ -1,13 +1,14 @@
 use crate::parameters::*;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use rand::{
     distributions::{Distribution, Uniform},
     Rng,
 };
-use serde::{Deserialize, Serialize, Serializer};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use serde_with::serde_as;
 use std::{
     fmt,
-    ops::{Add, Sub},
+    ops::{Add, BitAnd, BitOr, Sub},
 };
 use thiserror::Error;
 
@@ -15,6 +16,8 @@ pub type Index = u32;
 
 pub type SparseErrorVector = SparseVector<ERROR_WEIGHT, ROW_LENGTH>;
 pub type ErrorVector = DenseVector<ROW_LENGTH>;
+// Error vector of length ROW_LENGTH whose weight is only known at runtime
+pub type DynErrorVector = DynSparseVector;
 
 #[derive(Copy, Clone, Debug, Error)]
 pub enum InvalidSupport {
@@ -39,7 +42,7 @@ impl<const WEIGHT: usize, const LENGTH: usize> TryFrom<&[Index]> for SparseVecto
     type Error = InvalidSupport;
     fn try_from(supp: &[Index]) -> Result<Self, Self::Error> {
         let Ok(supp) = <[Index; WEIGHT]>::try_from(supp) else {
-            return Err(InvalidSupport::WrongLength(WEIGHT))
+            return Err(InvalidSupport::WrongLength(WEIGHT));
         };
         Self::from_support(supp)
     }
@@ -52,6 +55,27 @@ impl<const WEIGHT: usize, const LENGTH: usize> SparseVector<WEIGHT, LENGTH> {
         Ok(v)
     }
 
+    /// Collects the support from an iterator of indices, which must yield exactly `WEIGHT`
+    /// distinct indices less than `LENGTH`.
+    pub fn try_from_iter<I>(iter: I) -> Result<Self, InvalidSupport>
+    where
+        I: IntoIterator<Item = Index>,
+    {
+        let mut supp = [0 as Index; WEIGHT];
+        let mut count = 0;
+        for idx in iter {
+            if count == WEIGHT {
+                return Err(InvalidSupport::WrongLength(WEIGHT));
+            }
+            supp[count] = idx;
+            count += 1;
+        }
+        if count < WEIGHT {
+            return Err(InvalidSupport::WrongLength(WEIGHT));
+        }
+        Self::from_support(supp)
+    }
+
     // Ensure that the support represents a valid vector of the specified weight and length
     pub fn validate(&self) -> Result<(), InvalidSupport> {
         for idx in self.0 {
@@ -105,6 +129,29 @@ impl<const WEIGHT: usize, const LENGTH: usize> SparseVector<WEIGHT, LENGTH> {
         self.0.contains(index)
     }
 
+    /// Returns the cyclic shift of the vector by `delta`, treating the vector as a single
+    /// circulant block of length `LENGTH`. (For vectors consisting of multiple blocks,
+    /// see `ncw::shift_blockwise`.)
+    pub fn shifted(&self, delta: Index) -> Self {
+        let length = LENGTH as u64;
+        let delta = u64::from(delta) % length;
+        let shifted = Self(
+            self.0
+                .map(|idx| ((u64::from(idx) + delta) % length) as Index),
+        );
+        debug_assert!(shifted.validate().is_ok());
+        shifted
+    }
+
+    /// Returns the vector with its coefficients in reverse order, i.e., with each index `i`
+    /// in the support replaced by `LENGTH - 1 - i`. (For vectors consisting of multiple
+    /// blocks, see `ncw::reverse_blockwise`.)
+    pub fn reversed(&self) -> Self {
+        let reversed = Self(self.0.map(|idx| LENGTH as Index - 1 - idx));
+        debug_assert!(reversed.validate().is_ok());
+        reversed
+    }
+
     pub fn random<R>(rng: &mut R) -> Self
     where
         R: Rng + ?Sized,
@@ -125,6 +172,26 @@ impl<const WEIGHT: usize, const LENGTH: usize> SparseVector<WEIGHT, LENGTH> {
         Self(supp)
     }
 
+    /// Generates a uniformly random vector using Floyd's combination sampling algorithm,
+    /// which needs exactly `WEIGHT` random draws with no rejection. This is faster than
+    /// `random` when `WEIGHT` is close to `LENGTH`. The support is not sorted, and its order
+    /// is not uniformly random.
+    pub fn random_floyd<R>(rng: &mut R) -> Self
+    where
+        R: Rng + ?Sized,
+    {
+        let mut supp = [0 as Index; WEIGHT];
+        for (ctr, j) in (LENGTH - WEIGHT..LENGTH).enumerate() {
+            let t = rng.gen_range(0..=j) as Index;
+            supp[ctr] = if supp[..ctr].contains(&t) {
+                j as Index
+            } else {
+                t
+            };
+        }
+        Self(supp)
+    }
+
     pub fn random_sorted<R: Rng + ?Sized>(rng: &mut R) -> Self {
         let mut supp = [0 as Index; WEIGHT];
         for i in 0..WEIGHT {
@@ -266,6 +333,21 @@ impl<const WEIGHT: usize, const LENGTH: usize> SparseVector<WEIGHT, LENGTH> {
         false
     }
 
+    /// Histogram of the cyclic distances between pairs of support elements: entry `delta`
+    /// is the number of pairs at cyclic distance `delta`, for `0 <= delta <= LENGTH / 2`.
+    pub fn distance_spectrum(&self) -> Vec<u32> {
+        let length = self.length();
+        let mut spectrum = vec![0; LENGTH / 2 + 1];
+        for (i, &self_i) in self.0.iter().enumerate() {
+            for &self_j in self.0[i + 1..].iter() {
+                let diff = self_j.abs_diff(self_i);
+                let delta = diff.min(length - diff);
+                spectrum[delta as usize] += 1;
+            }
+        }
+        spectrum
+    }
+
     pub fn shifts_above_threshold(&self, threshold: u8) -> bool {
         let length = self.length();
         let mut shift_counts = [0; LENGTH];
@@ -296,6 +378,47 @@ impl<const WEIGHT: usize, const LENGTH: usize> SparseVector<WEIGHT, LENGTH> {
     }
 }
 
+/// Samples `count` distinct indices uniformly at random from `0..range`, excluding those in
+/// `excluded`, which is tracked with a bitset so that each draw takes constant time.
+/// Indices are drawn one at a time and rejected if excluded or already drawn, so the order
+/// of the result is random.
+///
+/// # Panics
+///
+/// Panics if fewer than `count` indices in `0..range` aren't excluded.
+pub fn sample_from_complement<R>(
+    excluded: &[Index],
+    count: usize,
+    range: Index,
+    rng: &mut R,
+) -> Vec<Index>
+where
+    R: Rng + ?Sized,
+{
+    let mut taken = vec![false; range as usize];
+    let mut num_excluded = 0;
+    for &idx in excluded {
+        if let Some(slot) = taken.get_mut(idx as usize) {
+            num_excluded += usize::from(!*slot);
+            *slot = true;
+        }
+    }
+    assert!(
+        count + num_excluded <= range as usize,
+        "not enough indices outside the excluded set"
+    );
+    let dist = Uniform::new(0, range);
+    let mut sample = Vec::with_capacity(count);
+    while sample.len() < count {
+        let idx = dist.sample(rng);
+        if !taken[idx as usize] {
+            taken[idx as usize] = true;
+            sample.push(idx);
+        }
+    }
+    sample
+}
+
 // Sort support lists before serializing
 impl<const W: usize, const L: usize> Serialize for SparseVector<W, L> {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
@@ -324,6 +447,170 @@ impl<const W: usize, const L: usize> fmt::Display for SparseVector<W, L> {
     }
 }
 
+// Sparse vector over GF(2) with weight and length determined at runtime.
+// Slower than SparseVector, but usable where the weight isn't known at compile-time.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DynSparseVector {
+    supp: Vec<Index>,
+    length: Index,
+}
+
+impl<const WEIGHT: usize, const LENGTH: usize> From<SparseVector<WEIGHT, LENGTH>>
+    for DynSparseVector
+{
+    fn from(v: SparseVector<WEIGHT, LENGTH>) -> Self {
+        Self {
+            supp: v.0.to_vec(),
+            length: LENGTH as Index,
+        }
+    }
+}
+
+impl DynSparseVector {
+    pub fn from_support(supp: Vec<Index>, length: Index) -> Result<Self, InvalidSupport> {
+        let v = Self { supp, length };
+        v.validate()?;
+        Ok(v)
+    }
+
+    // Ensure that the support represents a valid vector of the specified length
+    pub fn validate(&self) -> Result<(), InvalidSupport> {
+        for &idx in &self.supp {
+            if idx >= self.length {
+                return Err(InvalidSupport::OutOfBounds(self.length as usize));
+            }
+        }
+        for i in 0..self.weight() {
+            for j in (i + 1)..self.weight() {
+                if self.get(i) == self.get(j) {
+                    return Err(InvalidSupport::RepeatedIndex);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn sort(&mut self) {
+        self.supp.sort_unstable()
+    }
+
+    #[inline]
+    pub fn sorted(mut self) -> Self {
+        self.sort();
+        self
+    }
+
+    #[inline]
+    pub fn weight(&self) -> usize {
+        self.supp.len()
+    }
+
+    #[inline]
+    pub fn length(&self) -> Index {
+        self.length
+    }
+
+    #[inline]
+    pub fn get(&self, i: usize) -> Index {
+        self.supp[i]
+    }
+
+    #[inline]
+    pub fn support(&self) -> &[Index] {
+        &self.supp
+    }
+
+    #[inline]
+    pub fn contains(&self, index: &Index) -> bool {
+        self.supp.contains(index)
+    }
+
+    pub fn random<R>(weight: usize, length: Index, rng: &mut R) -> Self
+    where
+        R: Rng + ?Sized,
+    {
+        assert!(weight <= length as usize, "weight must be <= length");
+        let mut supp = Vec::with_capacity(weight);
+        let dist = Uniform::new(0, length);
+        while supp.len() < weight {
+            // Randomly generate element in the appropriate range
+            let idx = dist.sample(rng);
+            if !supp.contains(&idx) {
+                supp.push(idx);
+            }
+        }
+        Self { supp, length }
+    }
+
+    pub fn dense(&self) -> Vec<bool> {
+        let mut v = vec![false; self.length as usize];
+        for &i in &self.supp {
+            v[i as usize] = true;
+        }
+        v
+    }
+
+    pub fn relative_shifts(&self, other: &Self) -> Vec<Vec<Index>> {
+        let length = self.length;
+        self.supp
+            .iter()
+            .map(|&self_i| {
+                other
+                    .supp
+                    .iter()
+                    .map(|&other_j| {
+                        // (self_i - other_j) % length, since 0 <= self_i, other_j < length
+                        if self_i < other_j {
+                            length + self_i - other_j
+                        } else {
+                            self_i - other_j
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    pub fn shifts_above_threshold(&self, threshold: u8) -> bool {
+        let length = self.length;
+        let mut shift_counts = vec![0u8; length as usize];
+        for (i, &self_i) in self.supp.iter().enumerate() {
+            for &self_j in self.supp[i + 1..].iter() {
+                let diff = self_j.abs_diff(self_i);
+                let delta = diff.min(length - diff);
+                let count = &mut shift_counts[delta as usize];
+                *count += 1;
+                if *count >= threshold {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+impl PartialEq for DynSparseVector {
+    // Supports may or may not be sorted, so we have to sort to test equality
+    fn eq(&self, other: &Self) -> bool {
+        self.length == other.length && self.clone().sorted().supp == other.clone().sorted().supp
+    }
+}
+
+impl Eq for DynSparseVector {}
+
+impl fmt::Display for DynSparseVector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let str_supp = self
+            .supp
+            .iter()
+            .map(|idx| idx.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(f, "[{str_supp}]")
+    }
+}
+
 // Dense vectors of fixed length over GF(2)
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[repr(transparent)]
@@ -335,6 +622,43 @@ impl<const LENGTH: usize> Default for DenseVector<LENGTH> {
     }
 }
 
+// Dense vectors are serialized as base64-encoded bitstrings of ceil(LENGTH / 8) bytes, with
+// the least significant bit of byte i holding entry 8i.
+impl<const LENGTH: usize> Serialize for DenseVector<LENGTH> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut bytes = vec![0u8; (LENGTH + 7) / 8];
+        for idx in self.iter_support() {
+            bytes[idx as usize / 8] |= 1 << (idx % 8);
+        }
+        serializer.serialize_str(&BASE64.encode(bytes))
+    }
+}
+
+impl<'de, const LENGTH: usize> Deserialize<'de> for DenseVector<LENGTH> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        let bytes = BASE64.decode(encoded).map_err(de::Error::custom)?;
+        let num_bytes = (LENGTH + 7) / 8;
+        if bytes.len() != num_bytes {
+            return Err(de::Error::invalid_length(
+                bytes.len(),
+                &format!("{num_bytes} bytes").as_str(),
+            ));
+        }
+        let mut v = Self::zero();
+        for (i, byte) in bytes.iter().enumerate() {
+            for j in (0..8).filter(|j| byte >> j & 1 == 1) {
+                let idx = 8 * i + j;
+                if idx >= LENGTH {
+                    return Err(de::Error::custom(InvalidSupport::OutOfBounds(LENGTH)));
+                }
+                v.set_one(idx);
+            }
+        }
+        Ok(v)
+    }
+}
+
 impl<const LENGTH: usize> TryFrom<&[Index]> for DenseVector<LENGTH> {
     type Error = InvalidSupport;
     fn try_from(supp: &[Index]) -> Result<Self, Self::Error> {
@@ -360,6 +684,20 @@ impl<const LENGTH: usize> DenseVector<LENGTH> {
         Self(list)
     }
 
+    /// Creates the vector with the given support, which may have any weight. Unlike the
+    /// `TryFrom<&[Index]>` implementation, repeated indices are set rather than flipped, so
+    /// they have no further effect.
+    pub fn from_support(supp: &[Index]) -> Result<Self, InvalidSupport> {
+        let mut v = Self::zero();
+        for &idx in supp {
+            if idx as usize >= LENGTH {
+                return Err(InvalidSupport::OutOfBounds(LENGTH));
+            }
+            v.set_one(idx as usize);
+        }
+        Ok(v)
+    }
+
     #[inline]
     pub fn get(&self, i: usize) -> bool {
         self.0[i]
@@ -391,11 +729,23 @@ impl<const LENGTH: usize> DenseVector<LENGTH> {
     }
 
     pub fn support(&self) -> Vec<Index> {
+        self.iter_support().collect()
+    }
+
+    /// Iterates over the indices of the nonzero entries in increasing order, without
+    /// allocating.
+    #[inline]
+    pub fn iter_support(&self) -> impl Iterator<Item = Index> + '_ {
         self.0
             .iter()
             .enumerate()
             .filter_map(|(idx, bit)| bit.then_some(idx as Index))
-            .collect()
+    }
+
+    /// Number of nonzero entries, computed without allocating the support.
+    #[inline]
+    pub fn hamming_weight(&self) -> usize {
+        self.0.iter().filter(|&&bit| bit).count()
     }
 
     pub fn duplicate_up_to(&mut self, length: usize) {
@@ -419,6 +769,16 @@ impl<const LENGTH: usize> DenseVector<LENGTH> {
         self.xor_with(other.0);
         self
     }
+
+    /// Number of indices in the supports of both vectors, computed without allocating.
+    #[inline]
+    pub fn count_intersection(&self, other: &Self) -> usize {
+        self.0
+            .iter()
+            .zip(other.0.iter())
+            .filter(|(&a, &b)| a && b)
+            .count()
+    }
 }
 
 impl<const L: usize> Add for DenseVector<L> {
@@ -437,6 +797,31 @@ impl<const L: usize> Sub for DenseVector<L> {
     }
 }
 
+// Componentwise AND and OR, whose supports are the intersection and union of the supports
+impl<const L: usize> BitAnd for DenseVector<L> {
+    type Output = Self;
+
+    fn bitand(mut self, other: Self) -> Self {
+        self.0
+            .iter_mut()
+            .zip(other.0)
+            .for_each(|(bit, other_bit)| *bit &= other_bit);
+        self
+    }
+}
+
+impl<const L: usize> BitOr for DenseVector<L> {
+    type Output = Self;
+
+    fn bitor(mut self, other: Self) -> Self {
+        self.0
+            .iter_mut()
+            .zip(other.0)
+            .for_each(|(bit, other_bit)| *bit |= other_bit);
+        self
+    }
+}
+
 fn insert_sorted_noinc<T: Ord + Copy>(array: &mut [T], value: T, max_i: usize) -> T {
     // Find index to insert the element in order
     let mut idx = 0;
@@ -480,6 +865,51 @@ mod tests {
 
     const TRIALS: usize = 1000;
 
+    #[test]
+    fn distance_spectrum_small() {
+        // Distances: 0-1: 1, 0-3: 3, 0-7: 3, 1-3: 2, 1-7: 4, 3-7: 4
+        let v = SparseVector::<4, 10>::from_support([0, 1, 3, 7]).unwrap();
+        let spectrum = v.distance_spectrum();
+        assert_eq!(spectrum, vec![0, 1, 1, 2, 2, 0]);
+        assert!(v.shifts_above_threshold(2));
+        assert!(!v.shifts_above_threshold(3));
+        let mut rng = rand::thread_rng();
+        for _ in 0..TRIALS / 10 {
+            let v = SparseVector::<BLOCK_WEIGHT, BLOCK_LENGTH>::random(&mut rng);
+            let spectrum = v.distance_spectrum();
+            assert_eq!(spectrum.len(), BLOCK_LENGTH / 2 + 1);
+            assert_eq!(
+                spectrum.iter().sum::<u32>() as usize,
+                BLOCK_WEIGHT * (BLOCK_WEIGHT - 1) / 2
+            );
+            let max_bin = *spectrum.iter().max().unwrap();
+            assert!(v.shifts_above_threshold(max_bin as u8));
+            assert!(!v.shifts_above_threshold(max_bin as u8 + 1));
+        }
+    }
+
+    #[test]
+    fn shifted_cyclic() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..TRIALS / 10 {
+            let v = SparseVector::<BLOCK_WEIGHT, BLOCK_LENGTH>::random(&mut rng);
+            assert_eq!(v.shifted(BLOCK_LENGTH as Index), v);
+            assert_eq!(v.shifted(0), v);
+            let mut w = v.clone();
+            for _ in 0..BLOCK_LENGTH {
+                w = w.shifted(1);
+            }
+            assert_eq!(w, v);
+            let delta = rng.gen_range(0..BLOCK_LENGTH as Index);
+            assert_eq!(
+                v.shifted(delta + 2 * BLOCK_LENGTH as Index),
+                v.shifted(delta)
+            );
+        }
+        let v = SparseVector::<3, 7>::from_support([0, 4, 6]).unwrap();
+        assert_eq!(v.shifted(2).support(), &[2, 6, 1]);
+    }
+
     #[test]
     fn validate_random() {
         let mut rng = rand::thread_rng();
@@ -501,6 +931,241 @@ mod tests {
         }
     }
 
+    #[test]
+    fn random_floyd_valid() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..TRIALS {
+            SparseVector::<ERROR_WEIGHT, ROW_LENGTH>::random_floyd(&mut rng)
+                .validate()
+                .expect("Random vector should validate");
+            SparseVector::<19, 19>::random_floyd(&mut rng)
+                .validate()
+                .expect("Random vector should validate");
+        }
+        assert_eq!(SparseVector::<0, 19>::random_floyd(&mut rng).weight(), 0);
+    }
+
+    #[test]
+    fn random_floyd_uniform() {
+        const SAMPLES: usize = 19000;
+        let mut rng = rand::thread_rng();
+        let mut counts = [0usize; 19];
+        for _ in 0..SAMPLES {
+            for idx in SparseVector::<5, 19>::random_floyd(&mut rng).support() {
+                counts[*idx as usize] += 1;
+            }
+        }
+        // Each index has probability 5/19 of appearing, so the expected count is 5000
+        // with standard deviation about 61.
+        for count in counts {
+            assert!((4500..=5500).contains(&count), "count = {count}");
+        }
+    }
+
+    #[test]
+    fn dense_serde_roundtrip() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..TRIALS {
+            let mut v = ErrorVector::zero();
+            for idx in 0..ROW_LENGTH {
+                if rng.gen::<bool>() {
+                    v.set_one(idx);
+                }
+            }
+            let json = serde_json::to_string(&v).unwrap();
+            // Quoted base64 string encoding ceil(ROW_LENGTH / 8) bytes
+            assert_eq!(json.len(), 4 * (((ROW_LENGTH + 7) / 8 + 2) / 3) + 2);
+            let v_de: ErrorVector = serde_json::from_str(&json).unwrap();
+            assert_eq!(v_de, v);
+        }
+        // Bytes [0x01, 0x04], i.e., bit 0 of byte 0 and bit 2 of byte 1
+        let v: DenseVector<12> = serde_json::from_str(r#""AQQ=""#).unwrap();
+        assert_eq!(v.support(), vec![0, 10]);
+        assert_eq!(serde_json::to_string(&v).unwrap(), r#""AQQ=""#);
+        // Entry out of bounds, wrong number of bytes, and invalid base64
+        assert!(serde_json::from_str::<DenseVector<10>>(r#""AQQ=""#).is_err());
+        assert!(serde_json::from_str::<DenseVector<12>>(r#""AQ==""#).is_err());
+        assert!(serde_json::from_str::<DenseVector<12>>(r#""AQQ""#).is_err());
+    }
+
+    #[test]
+    fn dense_weight_iter_support() {
+        let mut rng = rand::thread_rng();
+        assert_eq!(ErrorVector::zero().hamming_weight(), 0);
+        assert_eq!(ErrorVector::zero().iter_support().next(), None);
+        for _ in 0..TRIALS {
+            let mut v = ErrorVector::zero();
+            for i in 0..ROW_LENGTH {
+                if rng.gen_bool(0.1) {
+                    v.set_one(i);
+                }
+            }
+            let supp = v.support();
+            assert_eq!(v.hamming_weight(), supp.len());
+            assert!(v.iter_support().eq(supp.iter().copied()));
+        }
+    }
+
+    #[test]
+    fn dense_from_support() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..TRIALS {
+            let supp = DynSparseVector::random(
+                rng.gen_range(0..ROW_LENGTH),
+                ROW_LENGTH as Index,
+                &mut rng,
+            )
+            .sorted();
+            let v = ErrorVector::from_support(supp.support()).unwrap();
+            assert_eq!(v.support(), supp.support());
+        }
+        let v = DenseVector::<7>::from_support(&[6, 2, 2, 0, 6]).unwrap();
+        assert_eq!(v.support(), vec![0, 2, 6]);
+        assert_eq!(
+            DenseVector::<7>::from_support(&[]).unwrap(),
+            DenseVector::zero()
+        );
+        assert!(matches!(
+            DenseVector::<7>::from_support(&[1, 7]),
+            Err(InvalidSupport::OutOfBounds(7))
+        ));
+    }
+
+    #[test]
+    fn dense_intersection_union() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..TRIALS {
+            let mut u = ErrorVector::zero();
+            let mut v = ErrorVector::zero();
+            for i in 0..ROW_LENGTH {
+                if rng.gen_bool(0.1) {
+                    u.set_one(i);
+                }
+                if rng.gen_bool(0.1) {
+                    v.set_one(i);
+                }
+            }
+            let (wt_u, wt_v) = (u.hamming_weight(), v.hamming_weight());
+            let intersection = u.clone() & v.clone();
+            let union = u.clone() | v.clone();
+            assert_eq!(u.count_intersection(&v), intersection.hamming_weight());
+            assert!(intersection.hamming_weight() <= wt_u.min(wt_v));
+            assert!(union.hamming_weight() >= wt_u.max(wt_v));
+            assert_eq!(
+                intersection.hamming_weight() + union.hamming_weight(),
+                wt_u + wt_v
+            );
+            assert!(intersection
+                .iter_support()
+                .all(|idx| u.get(idx as usize) && v.get(idx as usize)));
+            assert_eq!(union.clone() - intersection, u + v);
+        }
+        let u = DenseVector::<7>::from_support(&[0, 2, 3]).unwrap();
+        let v = DenseVector::<7>::from_support(&[2, 3, 6]).unwrap();
+        assert_eq!((u.clone() & v.clone()).support(), vec![2, 3]);
+        assert_eq!((u | v).support(), vec![0, 2, 3, 6]);
+    }
+
+    #[test]
+    fn complement_sample() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..TRIALS {
+            let excluded = DynSparseVector::random(rng.gen_range(0..=20), 40, &mut rng)
+                .support()
+                .to_vec();
+            let count = rng.gen_range(0..=40 - excluded.len());
+            let sample = sample_from_complement(&excluded, count, 40, &mut rng);
+            assert_eq!(sample.len(), count);
+            assert!(sample
+                .iter()
+                .all(|idx| *idx < 40 && !excluded.contains(idx)));
+            let sample = DynSparseVector::from_support(sample, 40).unwrap();
+            assert!(sample.validate().is_ok());
+        }
+        // Repeated and out-of-range excluded indices are ignored
+        let mut sample = sample_from_complement(&[0, 2, 2, 9], 2, 4, &mut rng);
+        sample.sort_unstable();
+        assert_eq!(sample, vec![1, 3]);
+    }
+
+    #[test]
+    fn try_from_iter() {
+        type Vector = SparseVector<3, 7>;
+        let v = Vector::try_from_iter((0..7).filter(|i| i % 3 == 0)).unwrap();
+        assert_eq!(v.support(), &[0, 3, 6]);
+        assert!(matches!(
+            Vector::try_from_iter([1, 2]),
+            Err(InvalidSupport::WrongLength(3))
+        ));
+        assert!(matches!(
+            Vector::try_from_iter(0..),
+            Err(InvalidSupport::WrongLength(3))
+        ));
+        assert!(matches!(
+            Vector::try_from_iter([4, 1, 4]),
+            Err(InvalidSupport::RepeatedIndex)
+        ));
+        assert!(matches!(
+            Vector::try_from_iter([0, 7, 2]),
+            Err(InvalidSupport::OutOfBounds(7))
+        ));
+    }
+
+    #[test]
+    fn validate_random_dyn() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..TRIALS {
+            let v = DynSparseVector::random(ERROR_WEIGHT, BLOCK_LENGTH as Index, &mut rng);
+            assert_eq!(v.weight(), ERROR_WEIGHT);
+            v.validate().expect("Random vector should validate");
+        }
+        let v = DynSparseVector {
+            supp: vec![1, 5, 1],
+            length: 7,
+        };
+        assert!(matches!(v.validate(), Err(InvalidSupport::RepeatedIndex)));
+        let v = DynSparseVector {
+            supp: vec![1, 7],
+            length: 7,
+        };
+        assert!(matches!(v.validate(), Err(InvalidSupport::OutOfBounds(7))));
+    }
+
+    #[test]
+    fn dense_support_dyn() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..TRIALS {
+            let sparse = SparseVector::<ERROR_WEIGHT, BLOCK_LENGTH>::random(&mut rng);
+            let dyn_sparse = DynSparseVector::from(sparse.clone());
+            assert_eq!(dyn_sparse.dense(), sparse.dense().contents());
+        }
+    }
+
+    // Checks that the dynamic and const-generic shift computations agree
+    #[test]
+    fn shifts_dyn() {
+        let mut rng = rand::thread_rng();
+        let thresh = 3;
+        for _ in 0..TRIALS {
+            let v1 = SparseVector::<BLOCK_WEIGHT, BLOCK_LENGTH>::random(&mut rng);
+            let v2 = SparseVector::<BLOCK_WEIGHT, BLOCK_LENGTH>::random(&mut rng);
+            let (w1, w2) = (
+                DynSparseVector::from(v1.clone()),
+                DynSparseVector::from(v2.clone()),
+            );
+            assert_eq!(
+                w1.relative_shifts(&w2),
+                v1.relative_shifts(&v2).map(Vec::from).to_vec()
+            );
+            assert_eq!(
+                w1.shifts_above_threshold(thresh),
+                v1.shifts_above_threshold(thresh)
+            );
+        }
+        let v = SparseVector::<BLOCK_WEIGHT, BLOCK_LENGTH>::random_weak_type2(thresh, &mut rng);
+        assert!(DynSparseVector::from(v).shifts_above_threshold(thresh));
+    }
+
     #[test]
     fn weak_type1() {
         let mut rng = rand::thread_rng();
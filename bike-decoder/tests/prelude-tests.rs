@@ -0,0 +1,25 @@
+use bike_decoder::prelude::*;
+use hex::FromHex;
+use rand::SeedableRng;
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+const TRIALS: usize = 1000;
+
+#[test]
+fn decode_with_prelude() {
+    let seed =
+        Seed::from_hex("5b2e4c8d1f0a3b6c9e7d2f4a1c3e5b7d9f0a2c4e6b8d1f3a5c7e9b0d2f4a6c8e").unwrap();
+    let mut rng = Xoshiro256PlusPlus::from_seed(seed.into());
+    for _ in 0..TRIALS {
+        let key = Key::random(&mut rng);
+        let e_in = SparseErrorVector::random(&mut rng);
+        let mut syn = Syndrome::from_sparse(&key, &e_in);
+        let (e_out, same_syndrome): (ErrorVector, bool) = bgf_decoder(&key, &mut syn);
+        let success = e_out == e_in.dense();
+        assert!(same_syndrome || !success);
+        let overlaps = NcwOverlaps::new(&key, e_in.support());
+        assert!(overlaps.c <= e_in.weight());
+        let result = DecodingResult::from_key_vector(key, TaggedErrorVector::from(e_in));
+        assert_eq!(result.success(), success);
+    }
+}
@@ -0,0 +1,17 @@
+// Kept in its own test binary, since reseeding replaces the state of the thread-local RNG
+use bike_decoder::random::{custom_thread_rng, get_rng_from_seed, reseed_current_thread, Seed};
+use rand::RngCore;
+
+#[test]
+fn reseed_matches_fresh_rng() {
+    let mut rng = custom_thread_rng();
+    rng.next_u64();
+    let seed = Seed::from_passphrase("reseed");
+    reseed_current_thread(seed, 3);
+    let mut fresh = get_rng_from_seed(seed, 3);
+    let mut other_handle = custom_thread_rng();
+    for _ in 0..8 {
+        assert_eq!(rng.next_u64(), fresh.next_u64());
+        assert_eq!(other_handle.next_u64(), fresh.next_u64());
+    }
+}
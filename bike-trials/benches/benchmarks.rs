@@ -1,11 +1,14 @@
 use bike_decoder::{
+    decoder::DecodingFailure,
     keys::KeyFilter,
     ncw::NearCodewordClass,
     parameters::*,
     random::{custom_thread_rng, global_seed},
 };
 use bike_trials::{
-    application::{self, decoding_failure_trial, handle_decoding_failure},
+    application::{
+        self, decoding_failure_trial, decoding_trial_with_weights, handle_decoding_failure,
+    },
     output::OutputTo,
     parallel,
     record::DataRecord,
@@ -59,6 +62,7 @@ pub fn group_record(c: &mut Criterion) {
             .build()
             .unwrap();
         let mut rng = custom_thread_rng();
+        let mut sampling_rng = custom_thread_rng();
         let mut data = DataRecord::new(
             settings.key_filter(),
             settings.fixed_key().cloned(),
@@ -68,15 +72,18 @@ pub fn group_record(c: &mut Criterion) {
             || {
                 let (tx, rx) = channel();
                 for _ in 0..10 {
-                    let df = decoding_failure_trial(settings.trial_settings(), &mut rng).unwrap();
-                    tx.send(df).ok();
+                    let (result, weights) =
+                        decoding_trial_with_weights(settings.trial_settings(), &mut rng);
+                    tx.send((DecodingFailure::try_from(result).unwrap(), weights))
+                        .ok();
                 }
                 drop(tx);
                 rx
             },
             |rx| {
-                rx.iter()
-                    .for_each(|result| handle_decoding_failure(result, &mut data, &settings))
+                rx.iter().for_each(|(df, weights)| {
+                    handle_decoding_failure(df, weights, &mut data, &settings, &mut sampling_rng)
+                })
             },
             BatchSize::SmallInput,
         )
@@ -0,0 +1,146 @@
+use crate::record::{DataRecord, DecodingFailureRatio};
+use bike_decoder::keys::{Key, KeyFilter};
+use getset::{CopyGetters, Getters};
+use std::fmt::Write;
+
+/// Parameters that must agree for the results of two records to be combined.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RecordConfig {
+    pub r: usize,
+    pub d: usize,
+    pub t: usize,
+    pub iterations: usize,
+    pub gray_threshold_diff: u8,
+    pub key_filter: KeyFilter,
+    pub fixed_key: Option<Key>,
+}
+
+impl From<&DataRecord> for RecordConfig {
+    fn from(record: &DataRecord) -> Self {
+        Self {
+            r: record.r(),
+            d: record.d(),
+            t: record.t(),
+            iterations: record.iterations(),
+            gray_threshold_diff: record.gray_threshold_diff(),
+            key_filter: record.key_filter(),
+            fixed_key: record.fixed_key().clone(),
+        }
+    }
+}
+
+/// Combined results of all records sharing the same configuration.
+#[derive(Clone, CopyGetters, Debug, Getters)]
+pub struct AggregateRow {
+    #[getset(get = "pub")]
+    config: RecordConfig,
+    #[getset(get_copy = "pub")]
+    num_records: usize,
+    #[getset(get = "pub")]
+    decoding_failure_ratio: DecodingFailureRatio,
+}
+
+impl AggregateRow {
+    /// Wilson score interval for the true DFR at 95% confidence.
+    pub fn dfr_interval(&self) -> (f64, f64) {
+        self.decoding_failure_ratio.confidence_interval(0.05)
+    }
+}
+
+/// Sums the numbers of failures and trials over all records with the same configuration.
+/// Rows are listed in the order their configurations first appear.
+pub fn aggregate<'a, I>(records: I) -> Vec<AggregateRow>
+where
+    I: IntoIterator<Item = &'a DataRecord>,
+{
+    let mut rows: Vec<AggregateRow> = Vec::new();
+    for record in records {
+        let config = RecordConfig::from(record);
+        let dfr = record.decoding_failure_ratio().clone();
+        if let Some(row) = rows.iter_mut().find(|row| row.config == config) {
+            row.num_records += 1;
+            row.decoding_failure_ratio += dfr;
+        } else {
+            rows.push(AggregateRow {
+                config,
+                num_records: 1,
+                decoding_failure_ratio: dfr,
+            });
+        }
+    }
+    rows
+}
+
+/// Formats the aggregated rows as a CSV table with a header line.
+pub fn csv_summary(rows: &[AggregateRow]) -> String {
+    let mut csv = String::from(
+        "r,d,t,iterations,gray_threshold_diff,key_filter,fixed_key,\
+        num_records,num_failures,num_trials,dfr,dfr_lower_95,dfr_upper_95\n",
+    );
+    for row in rows {
+        let config = &row.config;
+        let dfr = &row.decoding_failure_ratio;
+        let (lower, upper) = row.dfr_interval();
+        writeln!(
+            csv,
+            "{},{},{},{},{},\"{:?}\",\"{}\",{},{},{},{:e},{:e},{:e}",
+            config.r,
+            config.d,
+            config.t,
+            config.iterations,
+            config.gray_threshold_diff,
+            config.key_filter,
+            config
+                .fixed_key
+                .as_ref()
+                .map_or_else(String::new, |key| key.to_string()),
+            row.num_records,
+            dfr.num_failures(),
+            dfr.num_trials(),
+            dfr.as_f64(),
+            lower,
+            upper,
+        )
+        .expect("Writing to String should not fail");
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bike_decoder::{keys::WeakType, random::Seed};
+
+    fn record(key_filter: KeyFilter, num_failures: u64, num_trials: u64) -> DataRecord {
+        let mut record = DataRecord::new(key_filter, None, Seed::new([0; 32]));
+        record.add_results(DecodingFailureRatio::new(num_failures, num_trials).unwrap());
+        record
+    }
+
+    #[test]
+    fn aggregate_two_records() {
+        let records = [
+            record(KeyFilter::Any, 3, 1000),
+            record(KeyFilter::Weak(WeakType::Type2, 4), 5, 10),
+            record(KeyFilter::Any, 7, 4000),
+        ];
+        let rows = aggregate(&records);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].config().key_filter, KeyFilter::Any);
+        assert_eq!(rows[0].num_records(), 2);
+        let dfr = rows[0].decoding_failure_ratio();
+        assert_eq!((dfr.num_failures(), dfr.num_trials()), (10, 5000));
+        assert_eq!(dfr.as_f64(), 0.002);
+        let (lower, upper) = rows[0].dfr_interval();
+        assert!(lower < 0.002 && 0.002 < upper);
+        // Reference Wilson interval for 10 failures in 5000 trials
+        assert!((lower - 0.0010867).abs() < 1e-6);
+        assert!((upper - 0.0036779).abs() < 1e-6);
+        assert_eq!(rows[1].num_records(), 1);
+        let csv = csv_summary(&rows);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("587,15,18,7,3,\"Any\",\"\",2,10,5000,2e-3,"));
+        assert!(lines[2].contains("\"Weak(Type2, 4)\""));
+    }
+}
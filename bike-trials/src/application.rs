@@ -1,28 +1,40 @@
 use crate::{
-    output,
-    record::{DataRecord, DecodingFailureRatio},
-    settings::{Settings, TrialSettings},
+    output::{self, OutputError, SnapshotWriter},
+    record::{ncw_sampling_weight, DataRecord, DecodingFailureRatio, OutputWeights},
+    settings::{KeyOrder, Settings, TrialSettings},
 };
+use anyhow::{bail, Context};
 use bike_decoder::{
-    decoder::{DecodingFailure, DecodingResult},
+    decoder::{bgf_decoder_with_params, DecodingFailure, DecodingResult},
     keys::{Key, KeyFilter},
     ncw::TaggedErrorVector,
     parameters::*,
-    random::{current_thread_id, get_rng_from_seed, global_thread_count, Seed},
+    random::{current_thread_id, get_rng_from_seed, global_thread_count, split_rng, Seed},
+    syndrome::Syndrome,
+    vectors::{DynErrorVector, ErrorVector, Index},
 };
+use getset::{CopyGetters, Getters};
 use rand::Rng;
-use std::time::{Duration, Instant};
+use std::{
+    process,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
 
-pub fn decoding_trial<R>(settings: &TrialSettings, rng: &mut R) -> DecodingResult
+/// Generates the key and error vector used in a decoding trial.
+// Option::is_none_or requires Rust 1.82
+#[allow(clippy::unnecessary_map_or)]
+pub fn sample_key_vector<R>(settings: &TrialSettings, rng: &mut R) -> (Key, TaggedErrorVector)
 where
     R: Rng + ?Sized,
 {
-    // Use fixed_key if provided, otherwise generate random key using specified filter
-    let key = settings
-        .fixed_key()
-        .cloned()
-        .unwrap_or_else(|| Key::random_filtered(settings.key_filter(), rng));
-    let tagged_error_vector = if let Some(ncw_class) = settings.ncw_class() {
+    let key = sample_key(settings, rng);
+    let ncw_class = settings.ncw_class().filter(|_| {
+        settings
+            .ncw_fraction()
+            .map_or(true, |fraction| rng.gen_bool(fraction))
+    });
+    let tagged_error_vector = if let Some(ncw_class) = ncw_class {
         let l = settings
             .ncw_overlap()
             .unwrap_or_else(|| rng.gen_range(0..=ncw_class.max_l()));
@@ -30,7 +42,113 @@ where
     } else {
         TaggedErrorVector::random(rng)
     };
-    DecodingResult::from_key_vector(key, tagged_error_vector)
+    (key, tagged_error_vector)
+}
+
+/// Same as `sample_key_vector`, but with a uniformly random error vector of the given weight.
+pub fn sample_key_dyn_vector<R>(
+    settings: &TrialSettings,
+    weight: usize,
+    rng: &mut R,
+) -> (Key, DynErrorVector)
+where
+    R: Rng + ?Sized,
+{
+    let key = sample_key(settings, rng);
+    let e_supp = DynErrorVector::random(weight, ROW_LENGTH as Index, rng);
+    (key, e_supp)
+}
+
+fn sample_key<R>(settings: &TrialSettings, rng: &mut R) -> Key
+where
+    R: Rng + ?Sized,
+{
+    // Use fixed_key if provided, then a key from the key pool, otherwise generate random key
+    // using specified filter
+    if let Some(key) = settings.fixed_key() {
+        key.clone()
+    } else if let Some(pool) = settings.key_pool() {
+        pool.sample(rng).clone()
+    } else {
+        Key::random_filtered(settings.key_filter(), rng)
+    }
+}
+
+/// Result of a decoding trial with an error vector whose weight may differ from
+/// `ERROR_WEIGHT`.
+#[derive(Clone, CopyGetters, Debug, Getters)]
+pub struct WeightedDecodingResult {
+    #[getset(get = "pub")]
+    key: Key,
+    #[getset(get = "pub")]
+    e_supp: DynErrorVector,
+    #[getset(get_copy = "pub")]
+    success: bool,
+}
+
+/// Same as `decoding_trial`, but with a uniformly random error vector of the given weight.
+/// With `weight == ERROR_WEIGHT` and no near-codeword sampling, this draws the same key and
+/// error vector from `rng` as `decoding_trial`.
+pub fn decoding_trial_weighted<R>(
+    settings: &TrialSettings,
+    weight: usize,
+    rng: &mut R,
+) -> WeightedDecodingResult
+where
+    R: Rng + ?Sized,
+{
+    decoding_trial_weighted_with_weights(settings, weight, rng).0
+}
+
+/// Same as `decoding_trial_weighted`, but also returns the weights of the decoder output,
+/// which are added to the failure weight histograms if the trial is a decoding failure.
+pub fn decoding_trial_weighted_with_weights<R>(
+    settings: &TrialSettings,
+    weight: usize,
+    rng: &mut R,
+) -> (WeightedDecodingResult, OutputWeights)
+where
+    R: Rng + ?Sized,
+{
+    let (key, e_supp) = sample_key_dyn_vector(settings, weight, rng);
+    let mut syn = Syndrome::from_dyn_sparse(&key, &e_supp);
+    let (e_out, _) = bgf_decoder_with_params(&key, &mut syn, settings.iterations(), settings.tau());
+    let e_in = ErrorVector::try_from(e_supp.support())
+        .expect("Error vector support should be in range 0..ROW_LENGTH");
+    let weights = OutputWeights::from_support(e_supp.support(), &syn, &e_out);
+    let result = WeightedDecodingResult {
+        key,
+        e_supp,
+        success: e_in == e_out,
+    };
+    (result, weights)
+}
+
+pub fn decoding_trial<R>(settings: &TrialSettings, rng: &mut R) -> DecodingResult
+where
+    R: Rng + ?Sized,
+{
+    decoding_trial_with_weights(settings, rng).0
+}
+
+/// Same as `decoding_trial`, but also returns the weights of the decoder output, which are
+/// added to the failure weight histograms if the trial is a decoding failure.
+pub fn decoding_trial_with_weights<R>(
+    settings: &TrialSettings,
+    rng: &mut R,
+) -> (DecodingResult, OutputWeights)
+where
+    R: Rng + ?Sized,
+{
+    let (key, tagged_error_vector) = sample_key_vector(settings, rng);
+    let (result, syn, e_out) = DecodingResult::from_key_vector_with_output(
+        key,
+        tagged_error_vector,
+        settings.iterations(),
+        settings.tau(),
+    );
+    let weights = OutputWeights::new(result.vector().vector(), &syn, &e_out);
+    (result, weights)
 }
 
 #[inline]
@@ -41,11 +159,47 @@ where
     decoding_trial(settings, rng).try_into().ok()
 }
 
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Installs a handler for SIGINT and SIGTERM (or Ctrl-C on Windows) that stops any running
+/// trials early, as if the time budget had run out, so the results so far are still written
+/// and flushed to the output. A second signal exits immediately.
+pub fn stop_on_interrupt() -> Result<(), ctrlc::Error> {
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::Relaxed) {
+            process::exit(130);
+        }
+        eprintln!("Interrupted; stopping trials and writing results (interrupt again to exit)");
+    })
+}
+
+/// Whether the trials have been interrupted by a signal (see `stop_on_interrupt`).
+#[inline]
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+/// Whether the trials should stop early, either because the time budget has run out or
+/// because they've been interrupted.
+#[inline]
+pub(crate) fn should_stop(settings: &Settings, start_time: Instant) -> bool {
+    interrupted()
+        || settings
+            .time_budget()
+            .is_some_and(|budget| start_time.elapsed() >= budget)
+}
+
 pub(crate) fn start_message(settings: &Settings) -> String {
-    let num_trials = settings.num_trials();
+    let trials_message = match settings.time_budget() {
+        Some(budget) => format!("time budget = {:.3} s", budget.as_secs_f64()),
+        None => format!("N = {}", settings.num_trials()),
+    };
+    let iterations = settings.iterations();
+    let tau = settings.tau();
+    let t = settings.error_weight();
     let parameter_message = format!(
-        "    r = {BLOCK_LENGTH}, d = {BLOCK_WEIGHT}, t = {ERROR_WEIGHT}, \
-        iterations = {NB_ITER}, tau = {GRAY_THRESHOLD_DIFF}\n"
+        "    r = {BLOCK_LENGTH}, d = {BLOCK_WEIGHT}, t = {t}, \
+        iterations = {iterations}, tau = {tau}\n"
     );
     let weak_key_message = match settings.key_filter() {
         KeyFilter::Any => String::new(),
@@ -61,28 +215,52 @@ pub(crate) fn start_message(settings: &Settings) -> String {
         let l_str = settings
             .ncw_overlap()
             .map_or_else(|| "l".to_string(), |l| l.to_string());
-        format!("    Sampling error vectors from A_{{t,{l_str}}}({ncw_class})\n")
+        let fraction_str = settings
+            .trial_settings()
+            .ncw_fraction()
+            .map_or(String::new(), |fraction| {
+                format!(" in a fraction {fraction} of trials")
+            });
+        format!("    Sampling error vectors from A_{{t,{l_str}}}({ncw_class}){fraction_str}\n")
+    });
+    let fixed_key_message = settings.fixed_key().map_or(String::new(), |key| {
+        format!(
+            "    Using fixed key (codeword weight = {})\n",
+            key.codeword_weight()
+        )
     });
+    let key_pool_message = settings
+        .trial_settings()
+        .key_pool()
+        .map_or(String::new(), |pool| {
+            let order = match pool.order() {
+                KeyOrder::Random => "random",
+                KeyOrder::RoundRobin => "round-robin",
+            };
+            format!(
+                "    Using {} keys from key file ({order} order)\n",
+                pool.keys().len()
+            )
+        });
     let thread_message = if settings.parallel() {
-        let thread_count = if settings.threads() == 0 {
-            num_cpus::get()
-        } else {
-            settings.threads()
-        };
+        let thread_count = settings.thread_count();
         format!("[running with {thread_count} threads]\n")
     } else {
         String::new()
     };
     format!(
-        "Starting decoding trials (N = {num_trials}) with parameters:\n\
+        "Starting decoding trials ({trials_message}) with parameters:\n\
         {parameter_message}\
         {weak_key_message}\
         {ncw_message}\
+        {fixed_key_message}\
+        {key_pool_message}\
         {thread_message}"
     )
 }
 
-pub(crate) fn end_message(dfr: &DecodingFailureRatio, runtime: Duration) -> String {
+pub(crate) fn end_message(data: &DataRecord) -> String {
+    let (dfr, runtime) = (data.decoding_failure_ratio(), data.runtime());
     let avg_nanos = runtime.as_nanos() / u128::from(dfr.num_trials());
     let (avg_mcs, ns_rem) = (avg_nanos / 1000, avg_nanos % 1000);
     let avg_text = if avg_mcs >= 100 {
@@ -94,30 +272,95 @@ pub(crate) fn end_message(dfr: &DecodingFailureRatio, runtime: Duration) -> Stri
     } else {
         format!("{}.{:0width$} μs", avg_mcs, ns_rem, width = 3)
     };
+    let (lower, upper) = dfr.confidence_interval(0.05);
+    let weighted_message = data
+        .importance_weighted_dfr()
+        .map_or(String::new(), |weighted| {
+            format!("Importance-weighted DFR contribution of A_{{t,l}}(S): {weighted:.3e}\n")
+        });
     format!(
         "Trials: {}\n\
         Decoding failures: {}\n\
         log2(DFR): {:.2}\n\
+        DFR 95% confidence interval: [{:.3e}, {:.3e}]\n\
+        {}\
         Runtime: {:.3} s\n\
         Average: {}",
         dfr.num_trials(),
         dfr.num_failures(),
         dfr.as_f64().log2(),
+        lower,
+        upper,
+        weighted_message,
         runtime.as_secs_f64(),
         avg_text
     )
 }
 
-pub fn handle_decoding_failure(df: DecodingFailure, data: &mut DataRecord, settings: &Settings) {
-    if data.decoding_failures().len() < settings.record_max() {
-        if settings.verbose() >= 3 {
-            eprintln!("Decoding failure found!");
-            eprintln!("Key: {}\nError vector: {}", df.key(), df.vector());
-            if data.decoding_failures().len() + 1 == settings.record_max() {
-                eprintln!("Maximum number of decoding failures recorded.");
-            }
+/// Adds the output weights of a decoding failure to the failure weight histograms, and
+/// records the decoding failure if fewer than `record_max` have been recorded so far. With
+/// `--sample-failures`, once `record_max` failures have been recorded, each new failure
+/// instead replaces a uniformly random recorded one with probability `record_max / n`, where
+/// `n` is the number of failures seen so far (reservoir sampling), so the recorded failures
+/// are a uniform random sample of all failures seen.
+pub fn handle_decoding_failure<R>(
+    mut df: DecodingFailure,
+    weights: OutputWeights,
+    data: &mut DataRecord,
+    settings: &Settings,
+    rng: &mut R,
+) where
+    R: Rng + ?Sized,
+{
+    data.add_failure_weights(weights);
+    let recorded = data.decoding_failures().len();
+    let replace_index = if recorded < settings.record_max() {
+        None
+    } else if settings.sample_failures() {
+        let index = rng.gen_range(0..data.failures_seen()) as usize;
+        if index < recorded {
+            Some(index)
+        } else {
+            return;
         }
-        data.push_decoding_failure(df);
+    } else {
+        return;
+    };
+    prepare_decoding_failure(&mut df, settings);
+    if settings.verbose() >= 3 && recorded + 1 == settings.record_max() {
+        eprintln!("Maximum number of decoding failures recorded.");
+    }
+    match replace_index {
+        Some(index) => data.replace_decoding_failure(index, df),
+        None => data.push_decoding_failure(df),
+    }
+}
+
+/// Prepares a decoding failure to be recorded, classifying its key with `--classify-weak`
+/// and printing it with verbosity level 3 or higher.
+pub fn prepare_decoding_failure(df: &mut DecodingFailure, settings: &Settings) {
+    if let Some(threshold) = settings.classify_weak() {
+        df.weak_key = Some(df.key().classify_weak(threshold));
+    }
+    if settings.verbose() >= 3 {
+        eprintln!("Decoding failure found!");
+        eprintln!("Key: {}\nError vector: {}", df.key(), df.vector());
+    }
+}
+
+/// RNG used to choose which decoding failures to keep with `--sample-failures`. It's split
+/// off from the PRNG stream with the given seed and index, so it doesn't affect the trials.
+#[inline]
+pub fn failure_sampling_rng(seed: Seed, index: u32) -> impl Rng {
+    split_rng(&mut get_rng_from_seed(seed, index))
+}
+
+/// Records a successful decoding if fewer than the number of successes requested by
+/// `--record-successes` have been recorded so far.
+#[inline]
+pub fn handle_decoding_success(result: DecodingResult, data: &mut DataRecord, settings: &Settings) {
+    if data.successes().len() < settings.record_successes() {
+        data.push_success(result);
     }
 }
 
@@ -130,7 +373,13 @@ pub fn handle_progress(
     data.add_results(dfr);
     data.set_runtime(runtime);
     if settings.parallel() {
-        data.set_thread_count(Some(global_thread_count()));
+        // With deterministic threads, each thread index is a PRNG stream index instead
+        let thread_count = if settings.deterministic_threads() {
+            settings.thread_count() as u32
+        } else {
+            global_thread_count()
+        };
+        data.set_thread_count(Some(thread_count));
     }
     if settings.verbose() >= 2 {
         eprintln!(
@@ -142,41 +391,150 @@ pub fn handle_progress(
     }
 }
 
+/// Writes the data record to the output, as canonical JSON if `--canonical-json` is set.
+/// The record is only written out to the output location once the output is flushed.
+pub fn write_record(
+    writer: &mut SnapshotWriter,
+    settings: &Settings,
+    data: &DataRecord,
+) -> Result<(), OutputError> {
+    if settings.canonical_json() {
+        writer.write_canonical_json(data)
+    } else {
+        writer.write_json(data)
+    }
+}
+
+/// Loads the data record to be resumed from the output file specified in settings.
+pub fn resume_record(settings: &Settings) -> Result<DataRecord, anyhow::Error> {
+    let Some(path) = settings.output().path() else {
+        bail!("--resume requires an output file");
+    };
+    let data = DataRecord::load(path)
+        .with_context(|| format!("Failed to load record to resume from {}", path.display()))?;
+    if !data.matches_settings(settings.trial_settings()) {
+        bail!("Record to resume was generated with different parameters or key settings");
+    }
+    if settings.seed().is_some_and(|seed| seed != data.seed()) {
+        eprintln!("Warning: --seed ignored; using PRNG seed from resumed record.");
+    }
+    Ok(data)
+}
+
+/// Validates the settings for a run without running any trials, returning the message that
+/// would be printed at the start of the run. The output file is checked for writability but
+/// left untouched, and with `--resume` the record to be resumed is loaded and checked.
+pub fn dry_run(settings: &Settings) -> Result<String, anyhow::Error> {
+    output::probe_writable(settings.output())?;
+    if settings.resume() {
+        resume_record(settings)?;
+    }
+    Ok(start_message(settings))
+}
+
+/// Returns true if `--fail-on-dfr` is set and the DFR measured in `data` is strictly greater
+/// than the threshold, in which case the process should exit with a nonzero code.
+pub fn dfr_exceeds_threshold(settings: &Settings, data: &DataRecord) -> bool {
+    settings.fail_on_dfr().is_some_and(|threshold| {
+        data.num_trials() > 0 && data.decoding_failure_ratio().as_f64() > threshold
+    })
+}
+
 pub fn run(settings: &Settings) -> Result<DataRecord, anyhow::Error> {
     let start_time = Instant::now();
     if settings.verbose() >= 1 {
         eprintln!("{}", start_message(settings));
     }
+    // Initialize object storing data to be recorded, including the PRNG seed
+    let mut data = if settings.resume() {
+        resume_record(settings)?
+    } else {
+        let seed = settings.seed().unwrap_or_else(Seed::from_entropy);
+        let mut data = DataRecord::new(settings.key_filter(), settings.fixed_key().cloned(), seed);
+        data.set_t(settings.error_weight());
+        data.set_iterations(settings.iterations());
+        data.set_gray_threshold_diff(settings.tau());
+        data.set_ncw_sampling_weight(ncw_sampling_weight(settings.trial_settings()));
+        data
+    };
+    data.set_always_include_keys(settings.always_include_key());
+    if settings.count_distinct_keys() {
+        data.count_distinct_keys();
+    }
     output::check_writable(settings.output(), settings.overwrite())?;
-    // Set PRNG seed used for generating data
-    let seed = settings.seed().unwrap_or_else(Seed::from_entropy);
-    // Initialize object storing data to be recorded
-    let mut data = DataRecord::new(settings.key_filter(), settings.fixed_key().cloned(), seed);
+    let mut writer = SnapshotWriter::open(settings.output())?;
     let seed_index = settings.seed_index().unwrap_or_else(current_thread_id);
-    let mut rng = get_rng_from_seed(seed, seed_index);
-    let mut trials_remaining = settings.num_trials();
+    let mut rng = get_rng_from_seed(data.seed(), seed_index);
+    let mut sampling_rng = failure_sampling_rng(data.seed(), seed_index);
+    // Advance PRNG past any trials already recorded, so a resumed run reproduces
+    // the results of an uninterrupted run.
+    let error_weight = settings.error_weight();
+    for _ in 0..data.num_trials() {
+        if error_weight == ERROR_WEIGHT {
+            sample_key_vector(settings.trial_settings(), &mut rng);
+        } else {
+            sample_key_dyn_vector(settings.trial_settings(), error_weight, &mut rng);
+        }
+    }
+    let prior_runtime = data.runtime();
+    let mut trials_remaining = settings.num_trials().saturating_sub(data.num_trials());
+    if settings.resume() && trials_remaining == 0 {
+        write_record(&mut writer, settings, &data)?;
+        writer.flush()?;
+    }
+    let mut interval = 0;
     while trials_remaining > 0 {
+        interval += 1;
         let mut new_failure_count = 0;
-        let new_trials = settings.save_frequency().min(trials_remaining);
-        for _ in 0..new_trials {
-            let result = decoding_failure_trial(settings.trial_settings(), &mut rng);
-            if let Some(mut df) = result {
-                new_failure_count += 1;
-                df.thread = Some(seed_index);
-                handle_decoding_failure(df, &mut data, settings);
+        let mut new_trials = settings.save_frequency().min(trials_remaining);
+        for trial in 1..=new_trials {
+            if error_weight == ERROR_WEIGHT {
+                let (result, weights) =
+                    decoding_trial_with_weights(settings.trial_settings(), &mut rng);
+                if result.success() {
+                    handle_decoding_success(result, &mut data, settings);
+                } else if let Ok(mut df) = DecodingFailure::try_from(result) {
+                    new_failure_count += 1;
+                    df.thread = Some(seed_index);
+                    handle_decoding_failure(df, weights, &mut data, settings, &mut sampling_rng);
+                }
+            } else {
+                // Decoding failures with other error weights are counted but not recorded
+                let (result, weights) = decoding_trial_weighted_with_weights(
+                    settings.trial_settings(),
+                    error_weight,
+                    &mut rng,
+                );
+                if !result.success() {
+                    new_failure_count += 1;
+                    data.add_failure_weights(weights);
+                }
+            }
+            if should_stop(settings, start_time) {
+                new_trials = trial;
+                break;
             }
         }
         let dfr = DecodingFailureRatio::new(new_failure_count, new_trials)
             .expect("Number of decoding failures should be <= number of trials");
-        handle_progress(dfr, &mut data, settings, start_time.elapsed());
-        output::write_json(settings.output(), &data)?;
+        handle_progress(
+            dfr,
+            &mut data,
+            settings,
+            prior_runtime + start_time.elapsed(),
+        );
         trials_remaining -= new_trials;
+        let finished = trials_remaining == 0 || should_stop(settings, start_time);
+        write_record(&mut writer, settings, &data)?;
+        if finished || settings.flushes_at(interval) {
+            writer.flush()?;
+        }
+        if finished {
+            break;
+        }
     }
     if settings.verbose() >= 1 {
-        eprintln!(
-            "{}",
-            end_message(data.decoding_failure_ratio(), data.runtime())
-        );
+        eprintln!("{}", end_message(&data));
     }
     Ok(data)
 }
@@ -0,0 +1,144 @@
+use crate::{
+    aggregate::{aggregate, csv_summary},
+    record::{data_record_schema, DataRecord},
+    settings::Command,
+};
+use anyhow::Context;
+use bike_decoder::{
+    decoder::{simd_enabled, unsatisfied_parity_checks, unsatisfied_parity_checks_scalar},
+    keys::Key,
+    parameters::*,
+    random::custom_thread_rng,
+    syndrome::Syndrome,
+    threshold::{threshold_table, threshold_table_csv},
+    vectors::SparseErrorVector,
+};
+use std::{
+    fmt,
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+/// Runs the specified subcommand
+pub fn run(command: &Command) -> Result<(), anyhow::Error> {
+    match command {
+        Command::BenchUpc { number } => println!("{}", bench_upc(*number as u64)),
+        Command::PrintSchema => println!("{:#}", data_record_schema()),
+        Command::Aggregate { paths } => {
+            let records = paths
+                .iter()
+                .map(|path| {
+                    DataRecord::load(path).with_context(|| format!("Failed to load {path}"))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            print!("{}", csv_summary(&aggregate(&records)));
+        }
+        Command::DumpThresholds { r, d, t } => {
+            let table = threshold_table(*r, *d, *t)?;
+            print!("{}", threshold_table_csv(&table));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct UpcTiming {
+    pub iterations: u64,
+    pub runtime: Duration,
+}
+
+impl UpcTiming {
+    #[inline]
+    pub fn average_secs(&self) -> f64 {
+        self.runtime.as_secs_f64() / self.iterations as f64
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct UpcBenchmark {
+    pub simd_enabled: bool,
+    pub simd: UpcTiming,
+    pub scalar: UpcTiming,
+}
+
+impl UpcBenchmark {
+    #[inline]
+    pub fn speedup(&self) -> f64 {
+        self.scalar.average_secs() / self.simd.average_secs()
+    }
+}
+
+impl fmt::Display for UpcBenchmark {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let simd_status = if self.simd_enabled {
+            "enabled"
+        } else {
+            "unavailable, using scalar fallback"
+        };
+        write!(
+            f,
+            "Unsatisfied parity checks benchmark (N = {}) with parameters:\n    \
+            r = {BLOCK_LENGTH}, d = {BLOCK_WEIGHT}, t = {ERROR_WEIGHT}\n\
+            SIMD ({}): {:.3} μs\n\
+            Scalar: {:.3} μs\n\
+            Speedup: {:.2}x",
+            self.simd.iterations,
+            simd_status,
+            1e6 * self.simd.average_secs(),
+            1e6 * self.scalar.average_secs(),
+            self.speedup()
+        )
+    }
+}
+
+/// Times the computation of unsatisfied parity checks with and without SIMD
+/// instructions over the given number of iterations.
+pub fn bench_upc(iterations: u64) -> UpcBenchmark {
+    const NUM_INPUTS: usize = 16;
+    let iterations = iterations.max(1);
+    let mut rng = custom_thread_rng();
+    let mut inputs: Vec<(Key, Syndrome)> = (0..NUM_INPUTS)
+        .map(|_| {
+            let key = Key::random(&mut rng);
+            let e_supp = SparseErrorVector::random(&mut rng);
+            let syn = Syndrome::from_sparse(&key, &e_supp);
+            (key, syn)
+        })
+        .collect();
+    UpcBenchmark {
+        simd_enabled: simd_enabled(),
+        simd: time_upc(&mut inputs, iterations, unsatisfied_parity_checks),
+        scalar: time_upc(&mut inputs, iterations, unsatisfied_parity_checks_scalar),
+    }
+}
+
+fn time_upc<F>(inputs: &mut [(Key, Syndrome)], iterations: u64, upc: F) -> UpcTiming
+where
+    F: Fn(&Key, &mut Syndrome) -> [[u8; BLOCK_LENGTH]; 2],
+{
+    let start_time = Instant::now();
+    for i in 0..iterations {
+        let (key, syn) = &mut inputs[i as usize % inputs.len()];
+        black_box(upc(key, syn));
+    }
+    UpcTiming {
+        iterations,
+        runtime: start_time.elapsed(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bench_upc_runs() {
+        let bench = bench_upc(100);
+        assert_eq!(bench.simd.iterations, 100);
+        assert_eq!(bench.scalar.iterations, 100);
+        assert!(bench.simd.runtime > Duration::ZERO);
+        assert!(bench.scalar.runtime > Duration::ZERO);
+        assert!(bench.speedup() > 0.0);
+        run(&Command::BenchUpc { number: 10.0 }).unwrap();
+    }
+}
@@ -1,4 +1,6 @@
+pub mod aggregate;
 pub mod application;
+pub mod commands;
 pub mod output;
 pub mod parallel;
 pub mod record;
@@ -1,15 +1,37 @@
+use anyhow::Context;
 use bike_trials::{
-    application, parallel,
+    application, commands, parallel,
     settings::{Args, Settings},
 };
 use clap::Parser;
+use std::process;
 
 fn main() -> anyhow::Result<()> {
-    let settings = Settings::try_from(Args::parse())?;
-    if settings.parallel() {
-        parallel::run_parallel(&settings)?;
+    let args = Args::parse();
+    if let Some(command) = args.command() {
+        return commands::run(command);
+    }
+    let settings = Settings::try_from(args)?;
+    if settings.dry_run() {
+        print!("{}", application::dry_run(&settings)?);
+        return Ok(());
+    }
+    application::stop_on_interrupt().context("Failed to set signal handler")?;
+    let data = if settings.parallel() {
+        parallel::run_parallel(&settings)?
     } else {
-        application::run(&settings)?;
+        application::run(&settings)?
+    };
+    if application::interrupted() {
+        process::exit(130);
+    }
+    if application::dfr_exceeds_threshold(&settings, &data) {
+        eprintln!(
+            "DFR {:e} exceeds --fail-on-dfr threshold {:e}",
+            data.decoding_failure_ratio().as_f64(),
+            settings.fail_on_dfr().unwrap_or_default()
+        );
+        process::exit(1);
     }
     Ok(())
 }
@@ -1,9 +1,9 @@
 use serde::Serialize;
 use std::{
     fmt::Debug,
-    fs::{self, File},
-    io::{self, BufWriter, Write},
-    path::PathBuf,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Read, Seek, Write},
+    path::{Path, PathBuf},
 };
 use thiserror::Error;
 use uuid::Uuid;
@@ -13,16 +13,50 @@ pub enum OutputTo {
     #[default]
     Stdout,
     File(PathBuf),
+    /// File compressed with zstd
+    CompressedFile(PathBuf),
     Void,
 }
 
+impl OutputTo {
+    /// Output file path, whether or not the output is compressed.
+    #[inline]
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::File(path) | Self::CompressedFile(path) => Some(path),
+            Self::Stdout | Self::Void => None,
+        }
+    }
+}
+
+/// Magic number at the start of every zstd frame, in little-endian byte order.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Wraps `reader` in a zstd decoder if its contents start with a zstd frame, and otherwise
+/// reads it unchanged, so compressed and uncompressed JSON can be read the same way.
+pub fn decompressed<'a, R>(mut reader: R) -> io::Result<Box<dyn Read + 'a>>
+where
+    R: BufRead + 'a,
+{
+    if reader.fill_buf()?.starts_with(&ZSTD_MAGIC) {
+        Ok(Box::new(zstd::Decoder::with_buffer(reader)?))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Opens a file for reading, decompressing it if it's compressed with zstd.
+pub fn open_decompressed<P: AsRef<Path>>(path: P) -> io::Result<Box<dyn Read>> {
+    decompressed(BufReader::new(File::open(path)?))
+}
+
 /// Serializes data in JSON format to specified output location
 pub fn write_json<D>(output_to: &OutputTo, data: &D) -> Result<(), OutputError>
 where
     D: Debug + Serialize + ?Sized,
 {
     let result = match Writer::new(output_to) {
-        Some(Ok(mut writer)) => writer.write_json(data),
+        Some(Ok(writer)) => writer.write_json(data),
         Some(Err(e)) => Err(e),
         None => return Ok(()),
     };
@@ -35,8 +69,120 @@ where
     result
 }
 
+/// Same as `write_json`, but writes canonical JSON: the keys of every object are sorted and
+/// there is no insignificant whitespace, so the same data is always written identically.
+pub fn write_canonical_json<D>(output_to: &OutputTo, data: &D) -> Result<(), OutputError>
+where
+    D: Debug + Serialize + ?Sized,
+{
+    // serde_json::Map is a BTreeMap unless the preserve_order feature is enabled, so
+    // converting to a Value sorts the keys of all objects.
+    let value = serde_json::to_value(data).map_err(OutputError::JsonNotWritable)?;
+    write_json(output_to, &value)
+}
+
+/// Output location kept open over the course of a run, to which successive snapshots of the
+/// same data (e.g. a data record saved periodically) are written. Each snapshot replaces the
+/// previous one in memory, and only the latest is written out when the output is flushed,
+/// replacing the contents of the output file, so snapshots can be written frequently without
+/// rewriting the file each time. Each flush to stdout prints one line.
+#[derive(Debug)]
+pub struct SnapshotWriter {
+    output: OutputTo,
+    file: Option<BufWriter<File>>,
+    snapshot: Option<Vec<u8>>,
+}
+
+impl SnapshotWriter {
+    /// Opens the output location. An existing output file isn't truncated until the first
+    /// flush.
+    pub fn open(output: &OutputTo) -> Result<Self, OutputError> {
+        let file = output
+            .path()
+            .map(|path| {
+                OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .truncate(false)
+                    .open(path)
+            })
+            .transpose()
+            .map_err(OutputError::NotWritable)?
+            .map(BufWriter::new);
+        Ok(Self {
+            output: output.clone(),
+            file,
+            snapshot: None,
+        })
+    }
+
+    /// Serializes data in JSON format, replacing any snapshot that hasn't been flushed yet.
+    pub fn write_json<D>(&mut self, data: &D) -> Result<(), OutputError>
+    where
+        D: Serialize + ?Sized,
+    {
+        if self.output == OutputTo::Void {
+            return Ok(());
+        }
+        let mut json = serde_json::to_vec(data).map_err(OutputError::JsonNotWritable)?;
+        json.push(b'\n');
+        self.snapshot = Some(json);
+        Ok(())
+    }
+
+    /// Same as `write_json`, but serializes data as canonical JSON (see
+    /// `write_canonical_json`).
+    pub fn write_canonical_json<D>(&mut self, data: &D) -> Result<(), OutputError>
+    where
+        D: Serialize + ?Sized,
+    {
+        let value = serde_json::to_value(data).map_err(OutputError::JsonNotWritable)?;
+        self.write_json(&value)
+    }
+
+    /// Writes the latest snapshot to the output location, unless it's already been written.
+    /// If this fails, the snapshot is dumped to stderr instead.
+    pub fn flush(&mut self) -> Result<(), OutputError> {
+        let Some(snapshot) = self.snapshot.take() else {
+            return Ok(());
+        };
+        let result = self.write_snapshot(&snapshot);
+        if result.is_err() {
+            eprintln!("ERROR: failed to write JSON data; dumping to stderr.");
+            if io::stderr().write_all(&snapshot).is_err() {
+                eprintln!("ERROR: fallback also failed; data may have been lost.");
+            }
+        }
+        result
+    }
+
+    fn write_snapshot(&mut self, snapshot: &[u8]) -> Result<(), OutputError> {
+        let Some(file) = self.file.as_mut() else {
+            let mut stdout = io::stdout().lock();
+            return stdout
+                .write_all(snapshot)
+                .and_then(|()| stdout.flush())
+                .map_err(OutputError::NotWritable);
+        };
+        file.rewind()
+            .and_then(|()| file.get_ref().set_len(0))
+            .map_err(OutputError::NotWritable)?;
+        if let OutputTo::CompressedFile(_) = self.output {
+            let mut encoder =
+                zstd::Encoder::new(&mut *file, 0).map_err(OutputError::NotCompressible)?;
+            encoder
+                .write_all(snapshot)
+                .map_err(OutputError::NotWritable)?;
+            encoder.finish().map_err(OutputError::NotCompressible)?;
+        } else {
+            file.write_all(snapshot).map_err(OutputError::NotWritable)?;
+        }
+        file.flush().map_err(OutputError::NotWritable)
+    }
+}
+
 pub fn check_writable(output: &OutputTo, overwrite: bool) -> Result<(), OutputError> {
-    if let OutputTo::File(path) = output {
+    if let Some(path) = output.path() {
         if !overwrite
             && path.try_exists().map_err(OutputError::Inaccessible)?
             && fs::metadata(path).map_err(OutputError::BadMetadata)?.len() > 0
@@ -56,6 +202,24 @@ pub fn check_writable(output: &OutputTo, overwrite: bool) -> Result<(), OutputEr
     Ok(())
 }
 
+/// Checks that the output location could be written to, like `check_writable`, but without
+/// truncating or backing up an existing file. If the file doesn't exist yet, it is created
+/// and then removed again.
+pub fn probe_writable(output: &OutputTo) -> Result<(), OutputError> {
+    if let Some(path) = output.path() {
+        let existed = path.try_exists().map_err(OutputError::Inaccessible)?;
+        OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .map_err(OutputError::NotWritable)?;
+        if !existed {
+            fs::remove_file(path).map_err(OutputError::NotWritable)?;
+        }
+    }
+    Ok(())
+}
+
 #[derive(Debug, Error)]
 pub enum OutputError {
     #[error("Output file path should be accessible: {0}")]
@@ -66,14 +230,16 @@ pub enum OutputError {
     FailedBackup(io::Error),
     #[error("Output stream should be writable: {0}")]
     NotWritable(io::Error),
+    #[error("Output stream should be compressible: {0}")]
+    NotCompressible(io::Error),
     #[error("data should be writable as JSON: {0}")]
     JsonNotWritable(serde_json::Error),
 }
 
-#[derive(Debug)]
 enum Writer {
     Stdout(io::Stdout),
     File(BufWriter<File>),
+    Compressed(zstd::Encoder<'static, BufWriter<File>>),
 }
 
 impl Writer {
@@ -85,18 +251,38 @@ impl Writer {
                     .map(Self::from)
                     .map_err(OutputError::NotWritable),
             ),
+            OutputTo::CompressedFile(filename) => Some(
+                File::create(filename)
+                    .map_err(OutputError::NotWritable)
+                    .and_then(|file| {
+                        zstd::Encoder::new(BufWriter::new(file), 0)
+                            .map(Self::Compressed)
+                            .map_err(OutputError::NotCompressible)
+                    }),
+            ),
             OutputTo::Void => None,
         }
     }
 
-    fn write_json<D>(&mut self, data: &D) -> Result<(), OutputError>
+    fn write_json<D>(mut self, data: &D) -> Result<(), OutputError>
     where
         D: Serialize + ?Sized,
     {
-        serde_json::to_writer(&mut *self, data).map_err(OutputError::JsonNotWritable)?;
+        serde_json::to_writer(&mut self, data).map_err(OutputError::JsonNotWritable)?;
         self.write_all(b"\n").map_err(OutputError::NotWritable)?;
-        self.flush().map_err(OutputError::NotWritable)?;
-        Ok(())
+        self.finish()
+    }
+
+    /// Flushes the output, first completing the zstd frame if the output is compressed.
+    fn finish(self) -> Result<(), OutputError> {
+        match self {
+            Self::Compressed(encoder) => encoder
+                .finish()
+                .map_err(OutputError::NotCompressible)?
+                .flush()
+                .map_err(OutputError::NotWritable),
+            mut writer => writer.flush().map_err(OutputError::NotWritable),
+        }
     }
 }
 
@@ -105,6 +291,7 @@ impl Write for Writer {
         match self {
             Self::Stdout(stdout) => stdout.write(buf),
             Self::File(file) => file.write(buf),
+            Self::Compressed(encoder) => encoder.write(buf),
         }
     }
 
@@ -112,6 +299,7 @@ impl Write for Writer {
         match self {
             Self::Stdout(stdout) => stdout.flush(),
             Self::File(file) => file.flush(),
+            Self::Compressed(encoder) => encoder.flush(),
         }
     }
 }
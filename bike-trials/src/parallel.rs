@@ -1,103 +1,525 @@
 use crate::{
     application,
-    output::{self, OutputError},
-    record::{DataRecord, DecodingFailureRatio},
+    output::{self, OutputError, SnapshotWriter},
+    record::{ncw_sampling_weight, DataRecord, DecodingFailureRatio, OutputWeights},
     settings::{Settings, TrialSettings},
 };
 use anyhow::Context;
 use bike_decoder::{
     decoder::DecodingFailure,
+    parameters::ERROR_WEIGHT,
     random::{
-        current_thread_id, custom_thread_rng, get_or_insert_global_seed, try_insert_global_seed,
+        current_thread_id, custom_thread_rng, get_or_insert_global_seed, get_rng_from_seed,
+        try_insert_global_seed, Seed,
     },
 };
 use crossbeam_channel::{unbounded as channel, Receiver, Select, Sender};
+use indicatif::{ProgressBar, ProgressStyle};
 use rand::Rng;
 use rayon::prelude::*;
-use std::time::Instant;
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Instant,
+};
+
+/// Decoding failure found by a thread running trials, sent to the results channel along with
+/// the weights of the decoder output. Once `send_max` decoding failures have been found (see
+/// `TrialCounters`), the decoding failure itself is omitted, since it won't be recorded, but
+/// its weights are still sent to be added to the failure weight histograms.
+#[derive(Clone, Debug)]
+pub struct FailureReport {
+    pub failure: Option<DecodingFailure>,
+    pub weights: OutputWeights,
+    pub position: Option<StreamPosition>,
+}
+
+/// Position of a decoding failure found with deterministic threads: the index of its PRNG
+/// stream, the index of the trial within that stream, and a random priority drawn from a
+/// PRNG split off from the stream, used to sample decoding failures. Unlike the order in
+/// which decoding failures are received, these don't depend on thread scheduling.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct StreamPosition {
+    pub stream: u32,
+    pub trial: u64,
+    pub priority: u64,
+}
+
+/// Totals of trials run and decoding failures found, shared by the threads running trials.
+/// Threads update the totals directly rather than reporting the result of each trial, and
+/// progress updates are snapshots of the totals. Decoding failures are only included in the
+/// reports sent to the results channel while fewer than `send_max` have been found (by each
+/// PRNG stream, with deterministic threads), since the receiver doesn't record any more than
+/// that.
+#[derive(Debug)]
+pub struct TrialCounters {
+    trials: AtomicU64,
+    failures: AtomicU64,
+    send_max: u64,
+}
+
+impl TrialCounters {
+    pub fn new(send_max: u64) -> Self {
+        Self {
+            trials: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+            send_max,
+        }
+    }
+
+    /// Counters that send every decoding failure found.
+    #[inline]
+    pub fn unlimited() -> Self {
+        Self::new(u64::MAX)
+    }
+
+    /// Counters that send as many decoding failures as the settings can record, i.e., all of
+    /// them when sampling decoding failures and at most `record_max` otherwise. With
+    /// deterministic threads, the limit applies to each PRNG stream, since the decoding
+    /// failures recorded are the first `record_max` by trial index over all streams. The
+    /// weights of decoding failures that aren't sent are still reported either way.
+    pub fn for_settings(settings: &Settings) -> Self {
+        if settings.sample_failures() {
+            Self::unlimited()
+        } else {
+            Self::new(settings.record_max() as u64)
+        }
+    }
+
+    #[inline]
+    pub fn num_trials(&self) -> u64 {
+        self.trials.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub fn num_failures(&self) -> u64 {
+        self.failures.load(Ordering::Relaxed)
+    }
+
+    /// Current totals. These are only guaranteed to be consistent when no trials are running,
+    /// e.g. between batches.
+    pub fn snapshot(&self) -> DecodingFailureRatio {
+        let num_failures = self.num_failures();
+        DecodingFailureRatio::new(num_failures, self.num_trials().max(num_failures))
+            .expect("Number of decoding failures should be <= number of trials")
+    }
+
+    // Counts a trial, returning whether its decoding failure (if any) should be included in
+    // the report sent.
+    #[inline]
+    fn record_trial(&self, failure: bool) -> bool {
+        self.trials.fetch_add(1, Ordering::Relaxed);
+        failure && self.failures.fetch_add(1, Ordering::Relaxed) < self.send_max
+    }
+}
 
+#[inline]
 pub fn trial_iteration<R: Rng + ?Sized>(
     settings: &TrialSettings,
-    tx: &Sender<DecodingFailure>,
+    counters: &TrialCounters,
+    tx: &Sender<FailureReport>,
     rng: &mut R,
-) -> u64 {
-    let result = application::decoding_failure_trial(settings, rng);
-    if let Some(mut df) = result {
-        df.thread = Some(current_thread_id());
-        // Attempt to send decoding failure, but ignore errors, as the receiver may
-        // choose to hang up after receiving the maximum number of decoding failures.
-        tx.send(df).ok();
-        1
-    } else {
-        0
+) {
+    if let Some((df, weights, include_failure)) = counted_trial(settings, counters, rng) {
+        let failure = df.filter(|_| include_failure).map(|mut df| {
+            df.thread = Some(current_thread_id());
+            df
+        });
+        // Attempt to send the report, but ignore errors, as the receiver may hang up
+        // early if there's an error handling the results.
+        tx.send(FailureReport {
+            failure,
+            weights,
+            position: None,
+        })
+        .ok();
     }
 }
 
-// Runs decoding_trial in a loop, sending decoding failures via tx_results and
-// progress updates (counts of decoding failures and trials run) via tx_progress.
+// If a decoding trial fails, returns the decoding failure (unless it has a nonstandard error
+// weight), the weights of the decoder output, and whether fewer than send_max decoding
+// failures had been found. Counts the trial either way.
+fn counted_trial<R>(
+    settings: &TrialSettings,
+    counters: &TrialCounters,
+    rng: &mut R,
+) -> Option<(Option<DecodingFailure>, OutputWeights, bool)>
+where
+    R: Rng + ?Sized,
+{
+    let error_weight = settings.error_weight();
+    if error_weight != ERROR_WEIGHT {
+        // Decoding failures with other error weights are counted but not recorded
+        let (result, weights) =
+            application::decoding_trial_weighted_with_weights(settings, error_weight, rng);
+        let include_failure = counters.record_trial(!result.success());
+        return (!result.success()).then_some((None, weights, include_failure));
+    }
+    let (result, weights) = application::decoding_trial_with_weights(settings, rng);
+    let include_failure = counters.record_trial(!result.success());
+    let df = DecodingFailure::try_from(result).ok()?;
+    Some((Some(df), weights, include_failure))
+}
+
+// Runs decoding_trial in a loop, counting trials and decoding failures in counters,
+// sending decoding failures via tx_results and progress updates (counts of decoding
+// failures and trials run in each batch) via tx_progress.
+#[inline]
 pub fn trial_loop(
     settings: &TrialSettings,
     num_trials: u64,
     save_frequency: u64,
-    tx_results: &Sender<DecodingFailure>,
+    counters: &TrialCounters,
+    tx_results: &Sender<FailureReport>,
+    tx_progress: &Sender<DecodingFailureRatio>,
+) -> Result<(), anyhow::Error> {
+    trial_loop_until(
+        settings,
+        num_trials,
+        save_frequency,
+        None,
+        counters,
+        tx_results,
+        tx_progress,
+    )
+}
+
+// Same as trial_loop, but also stops running trials once the deadline has passed, if one is
+// given, or the trials have been interrupted (see application::stop_on_interrupt). Threads
+// check before each trial, so the last batch is cut short.
+pub fn trial_loop_until(
+    settings: &TrialSettings,
+    num_trials: u64,
+    save_frequency: u64,
+    deadline: Option<Instant>,
+    counters: &TrialCounters,
+    tx_results: &Sender<FailureReport>,
+    tx_progress: &Sender<DecodingFailureRatio>,
+) -> Result<(), anyhow::Error> {
+    run_batches(
+        num_trials,
+        save_frequency,
+        deadline,
+        counters,
+        tx_progress,
+        |new_trials| {
+            (0..new_trials)
+                .into_par_iter()
+                .try_for_each_with(tx_results.clone(), |tx, _| {
+                    if should_stop(deadline) {
+                        return Err(());
+                    }
+                    trial_iteration(settings, counters, tx, &mut custom_thread_rng());
+                    Ok(())
+                })
+                .ok();
+        },
+    )
+}
+
+// Same as trial_loop_until, but divides each batch of trials evenly among PRNG streams
+// initialized from the seed and the stream index, one for each thread in the current
+// Rayon thread pool, rather than using the PRNG of whichever thread picks up each trial.
+// The decoding failures found (labeled by stream index) thus don't depend on scheduling,
+// and their reports include their positions in the streams, so the receiver can choose
+// which to record independently of the order in which they arrive.
+#[allow(clippy::too_many_arguments)]
+pub fn trial_loop_deterministic(
+    settings: &TrialSettings,
+    num_trials: u64,
+    save_frequency: u64,
+    deadline: Option<Instant>,
+    seed: Seed,
+    counters: &TrialCounters,
+    tx_results: &Sender<FailureReport>,
     tx_progress: &Sender<DecodingFailureRatio>,
 ) -> Result<(), anyhow::Error> {
+    let num_streams = rayon::current_num_threads() as u32;
+    let mut streams: Vec<_> = (0..num_streams)
+        .map(|stream| TrialStream {
+            rng: get_rng_from_seed(seed, stream),
+            sampling_rng: application::failure_sampling_rng(seed, stream),
+            trials: 0,
+            failures: 0,
+        })
+        .collect();
+    let num_streams = u64::from(num_streams);
+    run_batches(
+        num_trials,
+        save_frequency,
+        deadline,
+        counters,
+        tx_progress,
+        |new_trials| {
+            streams.par_iter_mut().enumerate().for_each_with(
+                tx_results.clone(),
+                |tx, (index, stream)| {
+                    let index = index as u32;
+                    let stream_trials = new_trials / num_streams
+                        + u64::from(u64::from(index) < new_trials % num_streams);
+                    for _ in 0..stream_trials {
+                        if should_stop(deadline) {
+                            break;
+                        }
+                        let trial = stream.trials;
+                        stream.trials += 1;
+                        let Some((df, weights, _)) =
+                            counted_trial(settings, counters, &mut stream.rng)
+                        else {
+                            continue;
+                        };
+                        let failure =
+                            df.filter(|_| stream.failures < counters.send_max)
+                                .map(|mut df| {
+                                    df.thread = Some(index);
+                                    df
+                                });
+                        stream.failures += 1;
+                        let position = StreamPosition {
+                            stream: index,
+                            trial,
+                            priority: stream.sampling_rng.gen(),
+                        };
+                        tx.send(FailureReport {
+                            failure,
+                            weights,
+                            position: Some(position),
+                        })
+                        .ok();
+                    }
+                },
+            )
+        },
+    )
+}
+
+// State of a PRNG stream used by trial_loop_deterministic
+struct TrialStream<R, S> {
+    rng: R,
+    sampling_rng: S,
+    trials: u64,
+    failures: u64,
+}
+
+// Runs batches of at most save_frequency trials using run_batch, which updates counters,
+// and sends the DFR of each batch, computed from snapshots of counters, via tx_progress.
+fn run_batches<F>(
+    num_trials: u64,
+    save_frequency: u64,
+    deadline: Option<Instant>,
+    counters: &TrialCounters,
+    tx_progress: &Sender<DecodingFailureRatio>,
+    mut run_batch: F,
+) -> Result<(), anyhow::Error>
+where
+    F: FnMut(u64),
+{
     let mut trials_remaining = num_trials;
+    let mut previous = counters.snapshot();
     while trials_remaining > 0 {
         let new_trials = save_frequency.min(trials_remaining);
-        let new_failure_count = (0..new_trials)
-            .into_par_iter()
-            .map_with((settings, tx_results), |(settings, tx), _| {
-                trial_iteration(settings, tx, &mut custom_thread_rng())
-            })
-            .sum();
-        let dfr = DecodingFailureRatio::new(new_failure_count, new_trials)
-            .expect("Number of decoding failures should be <= number of trials");
+        run_batch(new_trials);
+        let current = counters.snapshot();
+        let dfr = DecodingFailureRatio::new(
+            current.num_failures() - previous.num_failures(),
+            current.num_trials() - previous.num_trials(),
+        )
+        .expect("Number of decoding failures should be <= number of trials");
+        previous = current;
         tx_progress
             .send(dfr)
             .context("Progress receiver should not be closed")?;
         trials_remaining -= new_trials;
+        if should_stop(deadline) {
+            break;
+        }
     }
     Ok(())
 }
 
+// Whether trials should stop early, because the deadline has passed or they've been
+// interrupted
+#[inline]
+fn should_stop(deadline: Option<Instant>) -> bool {
+    application::interrupted() || deadline.is_some_and(|deadline| Instant::now() >= deadline)
+}
+
+// Progress bar drawn to stderr, so it doesn't interfere with JSON output to stdout.
+// With a time budget, the total number of trials isn't known in advance, so a spinner
+// is shown instead.
+fn progress_bar(settings: &Settings) -> Option<ProgressBar> {
+    settings.progress().then(|| {
+        let (bar, template) = if settings.time_budget().is_some() {
+            (
+                ProgressBar::new_spinner(),
+                "[{elapsed_precise}] {spinner} {pos} trials ({msg})",
+            )
+        } else {
+            (
+                ProgressBar::new(settings.num_trials()),
+                "[{elapsed_precise}] {wide_bar} {pos}/{len} trials ({msg}, ETA {eta})",
+            )
+        };
+        bar.set_style(
+            ProgressStyle::with_template(template).expect("Progress bar template should be valid"),
+        );
+        bar
+    })
+}
+
+fn report_progress(
+    dfr: DecodingFailureRatio,
+    data: &mut DataRecord,
+    settings: &Settings,
+    start_time: Instant,
+    bar: Option<&ProgressBar>,
+) {
+    let Some(bar) = bar else {
+        application::handle_progress(dfr, data, settings, start_time.elapsed());
+        return;
+    };
+    bar.suspend(|| application::handle_progress(dfr, data, settings, start_time.elapsed()));
+    bar.set_position(data.num_trials());
+    bar.set_message(format!(
+        "DFR = {:.3e}",
+        data.decoding_failure_ratio().as_f64()
+    ));
+}
+
+// Adds the weights in a report to the failure weight histograms, recording the decoding
+// failure if it's included. With deterministic threads, the decoding failure is instead
+// ranked by its position.
+fn handle_report<R: Rng + ?Sized>(
+    report: FailureReport,
+    data: &mut DataRecord,
+    settings: &Settings,
+    sampling_rng: &mut R,
+    ranked: Option<&mut RankedFailures>,
+) {
+    let Some(mut df) = report.failure else {
+        data.add_failure_weights(report.weights);
+        return;
+    };
+    match (ranked, report.position) {
+        (Some(ranked), Some(position)) => {
+            data.add_failure_weights(report.weights);
+            application::prepare_decoding_failure(&mut df, settings);
+            ranked.insert(position, df);
+        }
+        _ => application::handle_decoding_failure(df, report.weights, data, settings, sampling_rng),
+    }
+}
+
+// Decoding failures found with deterministic threads. The ones recorded are the first
+// record_max in order of trial index and then stream index, or with --sample-failures, the
+// record_max with the lowest priorities, which are a uniform random sample of all decoding
+// failures found. Either way, they don't depend on the order in which they're received.
+struct RankedFailures {
+    failures: Vec<((u64, u64, u32), DecodingFailure)>,
+    record_max: usize,
+    sample: bool,
+}
+
+impl RankedFailures {
+    fn new(settings: &Settings) -> Self {
+        Self {
+            failures: Vec::new(),
+            record_max: settings.record_max(),
+            sample: settings.sample_failures(),
+        }
+    }
+
+    fn insert(&mut self, position: StreamPosition, df: DecodingFailure) {
+        let rank = if self.sample {
+            (position.priority, position.trial, position.stream)
+        } else {
+            (position.trial, 0, position.stream)
+        };
+        self.failures.push((rank, df));
+        // Only keep the highest ranked decoding failures, truncating in bulk so inserting
+        // takes amortized constant time
+        if self.failures.len() >= 2 * self.record_max.max(1) {
+            self.truncate();
+        }
+    }
+
+    fn truncate(&mut self) {
+        self.failures.sort_unstable_by_key(|(rank, _)| *rank);
+        self.failures.truncate(self.record_max);
+    }
+
+    // The highest ranked decoding failures found so far, which are the ones to be recorded
+    fn top(&mut self) -> Vec<DecodingFailure> {
+        self.truncate();
+        self.failures.iter().map(|(_, df)| df.clone()).collect()
+    }
+}
+
 pub fn record_trial_results(
     settings: &Settings,
-    rx_results: Receiver<DecodingFailure>,
+    rx_results: Receiver<FailureReport>,
     rx_progress: Receiver<DecodingFailureRatio>,
     start_time: Instant,
 ) -> Result<DataRecord, OutputError> {
     let seed = get_or_insert_global_seed(settings.seed());
     let mut data = DataRecord::new(settings.key_filter(), settings.fixed_key().cloned(), seed);
+    data.set_t(settings.error_weight());
+    data.set_iterations(settings.iterations());
+    data.set_gray_threshold_diff(settings.tau());
+    data.set_ncw_sampling_weight(ncw_sampling_weight(settings.trial_settings()));
+    data.set_always_include_keys(settings.always_include_key());
+    if settings.count_distinct_keys() {
+        data.count_distinct_keys();
+    }
+    let bar = progress_bar(settings);
+    let mut sampling_rng = application::failure_sampling_rng(seed, 0);
+    let mut ranked = settings
+        .deterministic_threads()
+        .then(|| RankedFailures::new(settings));
     const CONSECUTIVE_RESULTS_MAX: usize = 10_000;
-    let mut unwritten_data = false;
+    let mut writer = SnapshotWriter::open(settings.output())?;
+    let mut interval = 0;
     let mut selector = Select::new();
     let rx_results_idx = selector.recv(&rx_results);
     let rx_progress_idx = selector.recv(&rx_progress);
-    // Receive and handle messages from rx_results and rx_progress until rx_results
-    // closes or the maximum number of decoding failures have been recorded.
-    while data.decoding_failures().len() < settings.record_max() {
+    // Receive and handle messages from rx_results and rx_progress until rx_results closes.
+    // Reports keep being handled after the maximum number of decoding failures have been
+    // recorded, since their weights are still added to the failure weight histograms.
+    loop {
         let oper = selector.select();
         match oper.index() {
             i if i == rx_results_idx => match oper.recv(&rx_results) {
-                Ok(df) => {
-                    application::handle_decoding_failure(df, &mut data, settings);
-                    unwritten_data = true;
-                    for df in rx_results.try_iter().take(CONSECUTIVE_RESULTS_MAX) {
-                        application::handle_decoding_failure(df, &mut data, settings);
+                Ok(report) => {
+                    handle_report(
+                        report,
+                        &mut data,
+                        settings,
+                        &mut sampling_rng,
+                        ranked.as_mut(),
+                    );
+                    for report in rx_results.try_iter().take(CONSECUTIVE_RESULTS_MAX) {
+                        handle_report(
+                            report,
+                            &mut data,
+                            settings,
+                            &mut sampling_rng,
+                            ranked.as_mut(),
+                        );
                     }
                 }
                 Err(_) => break,
             },
             i if i == rx_progress_idx => match oper.recv(&rx_progress) {
                 Ok(dfr) => {
-                    application::handle_progress(dfr, &mut data, settings, start_time.elapsed());
-                    if data.num_trials() == settings.num_trials() {
-                        // Defer final write to make sure all decoding failures have been recorded
-                        unwritten_data = true;
-                    } else {
-                        output::write_json(settings.output(), &data)?;
-                        unwritten_data = false;
+                    report_progress(dfr, &mut data, settings, start_time, bar.as_ref());
+                    interval += 1;
+                    if let Some(ranked) = ranked.as_mut() {
+                        data.set_decoding_failures(ranked.top());
+                    }
+                    application::write_record(&mut writer, settings, &data)?;
+                    // For the final batch, defer flushing until all decoding failures have
+                    // been recorded
+                    if data.num_trials() != settings.num_trials() && settings.flushes_at(interval) {
+                        writer.flush()?;
                     }
                 }
                 Err(_) => selector.remove(rx_progress_idx),
@@ -105,18 +527,25 @@ pub fn record_trial_results(
             _ => unreachable!(),
         }
     }
-    // Drops the results receiver so no more decoding failures are handled
-    drop(rx_results);
+    if let Some(mut ranked) = ranked {
+        data.set_decoding_failures(ranked.top());
+    }
     // Receive and handle all remaining progress updates
     for dfr in rx_progress {
-        application::handle_progress(dfr, &mut data, settings, start_time.elapsed());
-        output::write_json(settings.output(), &data)?;
-        unwritten_data = false;
+        report_progress(dfr, &mut data, settings, start_time, bar.as_ref());
+        interval += 1;
+        application::write_record(&mut writer, settings, &data)?;
+        if settings.flushes_at(interval) {
+            writer.flush()?;
+        }
     }
-    // Failsafe to ensure any remaining data is written
-    if unwritten_data {
-        output::write_json(settings.output(), &data)?;
+    if let Some(bar) = bar {
+        bar.finish();
     }
+    // Sort decoding failures so the final output doesn't depend on thread scheduling
+    data.sort_decoding_failures();
+    application::write_record(&mut writer, settings, &data)?;
+    writer.flush()?;
     Ok(data)
 }
 
@@ -135,18 +564,40 @@ pub fn run_parallel(settings: &Settings) -> Result<DataRecord, anyhow::Error> {
     // Start main trial loop in separate thread
     let trial_thread = std::thread::spawn({
         let settings = settings.clone();
+        let num_threads = if settings.deterministic_threads() {
+            settings.thread_count()
+        } else {
+            settings.threads()
+        };
         let pool = rayon::ThreadPoolBuilder::new()
-            .num_threads(settings.threads())
+            .num_threads(num_threads)
             .build()?;
         move || {
+            let deadline = settings.time_budget().map(|budget| start_time + budget);
+            let counters = TrialCounters::for_settings(&settings);
             pool.install(|| {
-                trial_loop(
-                    settings.trial_settings(),
-                    settings.num_trials(),
-                    settings.save_frequency(),
-                    &tx_results,
-                    &tx_progress,
-                )
+                if settings.deterministic_threads() {
+                    trial_loop_deterministic(
+                        settings.trial_settings(),
+                        settings.num_trials(),
+                        settings.save_frequency(),
+                        deadline,
+                        seed,
+                        &counters,
+                        &tx_results,
+                        &tx_progress,
+                    )
+                } else {
+                    trial_loop_until(
+                        settings.trial_settings(),
+                        settings.num_trials(),
+                        settings.save_frequency(),
+                        deadline,
+                        &counters,
+                        &tx_results,
+                        &tx_progress,
+                    )
+                }
             })
         }
     });
@@ -158,10 +609,7 @@ pub fn run_parallel(settings: &Settings) -> Result<DataRecord, anyhow::Error> {
         .join()
         .unwrap_or_else(|err| std::panic::resume_unwind(err))?;
     if settings.verbose() >= 1 {
-        eprintln!(
-            "{}",
-            application::end_message(data.decoding_failure_ratio(), data.runtime())
-        );
+        eprintln!("{}", application::end_message(&data));
     }
     Ok(data)
 }
@@ -1,28 +1,45 @@
+use crate::{output, settings::TrialSettings};
 use bike_decoder::{
-    decoder::DecodingFailure,
+    decoder::{bgf_decoder_with_params, DecodingFailure, DecodingResult},
     keys::{Key, KeyFilter},
+    ncw::{ncw_patterns, near_codeword_set_fraction, NearCodewordClass},
     parameters::*,
     random::Seed,
+    syndrome::Syndrome,
     threshold::{bf_masked_threshold, bf_threshold_min},
+    vectors::{ErrorVector, Index, SparseErrorVector},
 };
 use getset::{CopyGetters, Getters, Setters};
-use serde::{Deserialize, Serialize};
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{json, Map, Value};
 use serde_with::{formats::Flexible, serde_as, DurationSecondsWithFrac};
-use std::{fmt, ops::AddAssign, time::Duration};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt, io,
+    ops::AddAssign,
+    path::Path,
+    time::Duration,
+};
 use thiserror::Error;
 
+// With a fixed key, the key fields are omitted from each recorded decoding failure (unless
+// always_include_keys is set), and are filled back in from fixed_key when deserializing.
+// failures_seen is filled in from num_failures for records written before it was added.
+// The derived (de)serialization is available as DataRecord::serialize/deserialize via
+// remote = "Self", and is wrapped by the Serialize/Deserialize impls below.
 #[serde_as]
 #[derive(Clone, CopyGetters, Debug, Deserialize, Getters, Serialize, Setters)]
+#[serde(remote = "Self")]
 pub struct DataRecord {
     #[getset(get_copy = "pub")]
     r: usize,
     #[getset(get_copy = "pub")]
     d: usize,
-    #[getset(get_copy = "pub")]
+    #[getset(get_copy = "pub", set = "pub")]
     t: usize,
-    #[getset(get_copy = "pub")]
+    #[getset(get_copy = "pub", set = "pub")]
     iterations: usize,
-    #[getset(get_copy = "pub")]
+    #[getset(get_copy = "pub", set = "pub")]
     gray_threshold_diff: u8,
     #[getset(get_copy = "pub")]
     bf_threshold_min: u8,
@@ -35,8 +52,24 @@ pub struct DataRecord {
     #[getset(get = "pub")]
     #[serde(flatten)]
     decoding_failure_ratio: DecodingFailureRatio,
+    #[getset(get_copy = "pub", set = "pub")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ncw_sampling_weight: Option<f64>,
     #[getset(get = "pub")]
     decoding_failures: Vec<DecodingFailure>,
+    #[getset(get = "pub")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    successes: Vec<DecodingResult>,
+    #[getset(get_copy = "pub")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    distinct_failure_keys: Option<usize>,
+    #[serde(skip)]
+    failure_keys: HashSet<KeySupports>,
+    #[getset(get = "pub")]
+    #[serde(default, skip_serializing_if = "FailureWeights::is_empty")]
+    failure_weights: FailureWeights,
+    #[getset(get_copy = "pub")]
+    failures_seen: u64,
     #[getset(get_copy = "pub")]
     seed: Seed,
     #[getset(get_copy = "pub", set = "pub")]
@@ -44,6 +77,53 @@ pub struct DataRecord {
     runtime: Duration,
     #[getset(get_copy = "pub", set = "pub")]
     thread_count: Option<u32>,
+    #[getset(get_copy = "pub", set = "pub")]
+    #[serde(skip)]
+    always_include_keys: bool,
+}
+
+impl Serialize for DataRecord {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.fixed_key.is_none() || self.always_include_keys {
+            return Self::serialize(self, serializer);
+        }
+        let mut value =
+            Self::serialize(self, serde_json::value::Serializer).map_err(ser::Error::custom)?;
+        for df in failure_objects(&mut value) {
+            df.remove("h0");
+            df.remove("h1");
+        }
+        value.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for DataRecord {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut value = Value::deserialize(deserializer)?;
+        if let Value::Object(fields) = &mut value {
+            if let Some(num_failures) = fields.get("num_failures").cloned() {
+                fields.entry("failures_seen").or_insert(num_failures);
+            }
+        }
+        if let Some(Value::Object(key)) = value.get("fixed_key").cloned() {
+            for df in failure_objects(&mut value) {
+                for (field, supp) in &key {
+                    df.entry(field.clone()).or_insert_with(|| supp.clone());
+                }
+            }
+        }
+        Self::deserialize(value).map_err(de::Error::custom)
+    }
+}
+
+// JSON objects of the decoding failures in a serialized DataRecord
+fn failure_objects(value: &mut Value) -> impl Iterator<Item = &mut Map<String, Value>> {
+    value
+        .get_mut("decoding_failures")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_object_mut)
 }
 
 impl DataRecord {
@@ -59,18 +139,114 @@ impl DataRecord {
             key_filter,
             fixed_key,
             decoding_failure_ratio: DecodingFailureRatio::default(),
+            ncw_sampling_weight: None,
             decoding_failures: Vec::new(),
+            successes: Vec::new(),
+            distinct_failure_keys: None,
+            failure_keys: HashSet::new(),
+            failure_weights: FailureWeights::default(),
+            failures_seen: 0,
             seed,
             runtime: Duration::new(0, 0),
             thread_count: None,
+            always_include_keys: false,
         }
     }
 
+    /// Loads a record previously written to a file in JSON format, which may be compressed
+    /// with zstd.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, LoadRecordError> {
+        let reader = output::open_decompressed(path).map_err(LoadRecordError::Unreadable)?;
+        serde_json::from_reader(reader).map_err(LoadRecordError::Unparseable)
+    }
+
+    /// Checks whether the record was generated with the compile-time parameters
+    /// of this build and the given trial settings.
+    pub fn matches_settings(&self, settings: &TrialSettings) -> bool {
+        (
+            self.r,
+            self.d,
+            self.t,
+            self.iterations,
+            self.gray_threshold_diff,
+        ) == (
+            BLOCK_LENGTH,
+            BLOCK_WEIGHT,
+            settings.error_weight(),
+            settings.iterations(),
+            settings.tau(),
+        ) && self.key_filter == settings.key_filter()
+            && self.fixed_key.as_ref() == settings.fixed_key()
+    }
+
+    /// Starts keeping track of the number of distinct keys among the recorded
+    /// decoding failures, including any that have already been recorded. Keys related by a
+    /// simultaneous cyclic shift of both blocks are counted once.
+    pub fn count_distinct_keys(&mut self) {
+        self.failure_keys = self
+            .decoding_failures
+            .iter()
+            .map(|df| key_class(df.key()))
+            .collect();
+        self.distinct_failure_keys = Some(self.failure_keys.len());
+    }
+
     #[inline]
     pub fn push_decoding_failure(&mut self, df: DecodingFailure) {
+        if let Some(count) = self.distinct_failure_keys.as_mut() {
+            if self.failure_keys.insert(key_class(df.key())) {
+                *count += 1;
+            }
+        }
         self.decoding_failures.push(df);
     }
 
+    /// Replaces the recorded decoding failure at `index` with `df`, updating the count of
+    /// distinct keys if it's being tracked.
+    pub fn replace_decoding_failure(&mut self, index: usize, df: DecodingFailure) {
+        self.decoding_failures[index] = df;
+        if self.distinct_failure_keys.is_some() {
+            self.count_distinct_keys();
+        }
+    }
+
+    /// Replaces all recorded decoding failures with `failures`, updating the count of
+    /// distinct keys if it's being tracked.
+    pub fn set_decoding_failures(&mut self, failures: Vec<DecodingFailure>) {
+        self.decoding_failures = failures;
+        if self.distinct_failure_keys.is_some() {
+            self.count_distinct_keys();
+        }
+    }
+
+    #[inline]
+    pub fn push_success(&mut self, result: DecodingResult) {
+        self.successes.push(result);
+    }
+
+    /// Sorts the recorded decoding failures by thread index, then key support, then error
+    /// vector support, so that the output of parallel runs doesn't depend on the order in
+    /// which the decoding failures were received.
+    pub fn sort_decoding_failures(&mut self) {
+        self.decoding_failures.sort_by_cached_key(|df| {
+            (
+                df.thread,
+                key_supports(df.key()),
+                *df.vector().vector().clone().sorted().support(),
+                *df.vector().source(),
+            )
+        });
+    }
+
+    /// Counts a decoding failure as seen, whether or not it's recorded, and adds the weights
+    /// of its final syndrome, output vector, and difference between input and output vectors
+    /// to the corresponding histograms.
+    #[inline]
+    pub fn add_failure_weights(&mut self, weights: OutputWeights) {
+        self.failures_seen += 1;
+        self.failure_weights.add(weights);
+    }
+
     #[inline]
     pub fn num_failures(&self) -> u64 {
         self.decoding_failure_ratio.num_failures()
@@ -81,18 +257,353 @@ impl DataRecord {
         self.decoding_failure_ratio.num_trials()
     }
 
+    /// Estimate of the contribution of the near-codeword set A_{t,l}(S) sampled from to the
+    /// DFR over uniformly random error vectors: the DFR measured on A_{t,l}(S), weighted by
+    /// the fraction of all error vectors lying in A_{t,l}(S). Only available if the sampling
+    /// weight is known (see `ncw_sampling_weight`).
+    #[inline]
+    pub fn importance_weighted_dfr(&self) -> Option<f64> {
+        self.ncw_sampling_weight
+            .map(|weight| self.decoding_failure_ratio.as_f64() * weight)
+    }
+
     #[inline]
     pub fn add_results(&mut self, dfr: DecodingFailureRatio) {
         self.decoding_failure_ratio += dfr;
     }
 }
 
+type KeySupports = ([Index; BLOCK_WEIGHT], [Index; BLOCK_WEIGHT]);
+
+// Identifies keys up to reordering of their supports
+fn key_supports(key: &Key) -> KeySupports {
+    let key = key.clone().sorted();
+    (*key.h0().support(), *key.h1().support())
+}
+
+// Identifies keys up to reordering of their supports and simultaneous cyclic shifts of both
+// blocks, which yield equivalent codes (see `Key::canonical`)
+fn key_class(key: &Key) -> KeySupports {
+    let key = key.canonical();
+    (*key.h0().support(), *key.h1().support())
+}
+
 impl fmt::Display for DataRecord {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.write_str(&serde_json::to_string(self).or(Err(fmt::Error))?)
     }
 }
 
+/// Fraction of all error vectors of weight t lying in the near-codeword set A_{t,l}(S) that
+/// error vectors are sampled from with the given settings (see
+/// `ncw::near_codeword_set_fraction`). Returns `None` unless the near-codeword class, the
+/// overlap l, and the key are all fixed, since whether the size of A_{t,l}(S) can be
+/// computed depends on how much distinct near-codewords of the key overlap. Also returns
+/// `None` for the 2N class, whose many patterns make this too costly to check, if the size
+/// isn't known, or if only some error vectors are sampled from A_{t,l}(S), since the DFR
+/// isn't then measured on A_{t,l}(S).
+pub fn ncw_sampling_weight(settings: &TrialSettings) -> Option<f64> {
+    if settings.ncw_fraction().is_some() {
+        return None;
+    }
+    let class = settings.ncw_class()?;
+    let l = settings.ncw_overlap()?;
+    if class == NearCodewordClass::TwoN {
+        return None;
+    }
+    let patterns = ncw_patterns(settings.fixed_key()?, class);
+    near_codeword_set_fraction::<BLOCK_LENGTH>(&patterns, l, settings.error_weight())
+}
+
+/// JSON Schema (draft 7) describing the serialized format of a `DataRecord`.
+pub fn data_record_schema() -> serde_json::Value {
+    let uint = json!({"type": "integer", "minimum": 0});
+    let support = |description: &str| {
+        json!({
+            "description": description,
+            "type": "array",
+            "items": {"type": "integer", "minimum": 0},
+        })
+    };
+    let histogram = json!({
+        "description": "Map from weights to the number of vectors of that weight",
+        "type": "object",
+        "propertyNames": {"pattern": "^[0-9]+$"},
+        "additionalProperties": uint,
+    });
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "DataRecord",
+        "description": "Results of decoding failure trials generated by bike-trials",
+        "type": "object",
+        "definitions": {
+            "key": {
+                "type": "object",
+                "properties": {
+                    "h0": support("Support of the first circulant block"),
+                    "h1": support("Support of the second circulant block"),
+                },
+                "required": ["h0", "h1"],
+            },
+            "key_filter": {
+                "oneOf": [
+                    {"const": "Any"},
+                    {
+                        "type": "object",
+                        "properties": {"NonWeak": uint},
+                        "required": ["NonWeak"],
+                        "additionalProperties": false,
+                    },
+                    {
+                        "type": "object",
+                        "properties": {
+                            "Weak": {
+                                "type": "array",
+                                "items": [{"enum": ["Type1", "Type2", "Type3"]}, uint],
+                                "minItems": 2,
+                                "maxItems": 2,
+                            },
+                        },
+                        "required": ["Weak"],
+                        "additionalProperties": false,
+                    },
+                ],
+            },
+            "e_source": {
+                "oneOf": [
+                    {"enum": ["Random", "Other", "Unknown"]},
+                    {
+                        "type": "object",
+                        "properties": {
+                            "NearCodeword": {
+                                "type": "object",
+                                "properties": {
+                                    "class": {"enum": ["C", "N", "2N"]},
+                                    "l": uint,
+                                    "delta": uint,
+                                },
+                                "required": ["class", "l", "delta"],
+                            },
+                        },
+                        "required": ["NearCodeword"],
+                        "additionalProperties": false,
+                    },
+                ],
+            },
+            "decoding_failure": {
+                "type": "object",
+                "properties": {
+                    "h0": support("Support of the first circulant block of the key"),
+                    "h1": support("Support of the second circulant block of the key"),
+                    "e_supp": support("Support of the error vector"),
+                    "e_source": {"$ref": "#/definitions/e_source"},
+                    "thread": {"type": ["integer", "null"], "minimum": 0},
+                    "weak_key": {
+                        "description": "Weak key types of the key at the given threshold",
+                        "type": "object",
+                        "properties": {
+                            "threshold": uint,
+                            "type2": {"type": "boolean"},
+                            "type3": {"type": "boolean"},
+                        },
+                        "required": ["threshold", "type2", "type3"],
+                        "additionalProperties": false,
+                    },
+                },
+                "description": "The key fields h0 and h1 are omitted if fixed_key is set, \
+                    unless the run used --always-include-key",
+                "required": ["e_supp"],
+            },
+        },
+        "properties": {
+            "r": {"description": "Block length", "type": "integer", "minimum": 0},
+            "d": {"description": "Block weight", "type": "integer", "minimum": 0},
+            "t": {"description": "Error weight", "type": "integer", "minimum": 0},
+            "iterations": {
+                "description": "Number of decoder iterations",
+                "type": "integer",
+                "minimum": 0,
+            },
+            "gray_threshold_diff": uint,
+            "bf_threshold_min": uint,
+            "bf_masked_threshold": uint,
+            "key_filter": {"$ref": "#/definitions/key_filter"},
+            "fixed_key": {
+                "oneOf": [{"type": "null"}, {"$ref": "#/definitions/key"}],
+            },
+            "num_failures": uint,
+            "num_trials": uint,
+            "ncw_sampling_weight": {
+                "description": "Fraction of all error vectors lying in the near-codeword set \
+                    sampled from",
+                "type": "number",
+                "minimum": 0,
+                "maximum": 1,
+            },
+            "decoding_failures": {
+                "type": "array",
+                "items": {"$ref": "#/definitions/decoding_failure"},
+            },
+            "successes": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "key": {"$ref": "#/definitions/key"},
+                        "vector": {
+                            "type": "object",
+                            "properties": {
+                                "e_supp": support("Support of the error vector"),
+                                "e_source": {"$ref": "#/definitions/e_source"},
+                            },
+                            "required": ["e_supp"],
+                        },
+                        "success": {"const": true},
+                    },
+                    "required": ["key", "vector", "success"],
+                },
+            },
+            "distinct_failure_keys": uint,
+            "failures_seen": {
+                "description": "Number of decoding failures seen, including any not recorded",
+                "type": "integer",
+                "minimum": 0,
+            },
+            "failure_weights": {
+                "type": "object",
+                "properties": {
+                    "syndrome": histogram,
+                    "e_out": histogram,
+                    "diff": histogram,
+                },
+                "required": ["syndrome", "e_out", "diff"],
+            },
+            "seed": {
+                "description": "PRNG seed as a hex string",
+                "type": "string",
+                "pattern": "^[0-9a-fA-F]{64}$",
+            },
+            "runtime": {"description": "Runtime in seconds", "type": "number", "minimum": 0},
+            "thread_count": {"type": ["integer", "null"], "minimum": 0},
+        },
+        "required": [
+            "r",
+            "d",
+            "t",
+            "iterations",
+            "gray_threshold_diff",
+            "bf_threshold_min",
+            "bf_masked_threshold",
+            "key_filter",
+            "fixed_key",
+            "num_failures",
+            "num_trials",
+            "decoding_failures",
+            "failures_seen",
+            "seed",
+            "runtime",
+            "thread_count",
+        ],
+    })
+}
+
+/// Distribution of the weights of a family of vectors, serialized as a map from weights
+/// to counts.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct WeightHistogram(BTreeMap<usize, u64>);
+
+impl WeightHistogram {
+    #[inline]
+    pub fn add(&mut self, weight: usize) {
+        *self.0.entry(weight).or_default() += 1;
+    }
+
+    #[inline]
+    pub fn get(&self, weight: usize) -> u64 {
+        self.0.get(&weight).copied().unwrap_or(0)
+    }
+
+    #[inline]
+    pub fn total(&self) -> u64 {
+        self.0.values().sum()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (usize, u64)> + '_ {
+        self.0.iter().map(|(&weight, &count)| (weight, count))
+    }
+}
+
+/// Histograms of weights of the final syndrome, the output vector `e_out`, and the
+/// difference `e_in - e_out` among decoding failures.
+#[derive(Clone, Debug, Default, Deserialize, Eq, Getters, PartialEq, Serialize)]
+#[getset(get = "pub")]
+pub struct FailureWeights {
+    syndrome: WeightHistogram,
+    e_out: WeightHistogram,
+    diff: WeightHistogram,
+}
+
+impl FailureWeights {
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.syndrome.is_empty()
+    }
+
+    #[inline]
+    pub fn add(&mut self, weights: OutputWeights) {
+        self.syndrome.add(weights.syndrome);
+        self.e_out.add(weights.e_out);
+        self.diff.add(weights.diff);
+    }
+}
+
+/// Weights of the final syndrome, the output vector `e_out`, and the difference
+/// `e_in - e_out` for a single decoding trial. These are computed by whichever thread ran
+/// the trial, so the decoder doesn't need to be rerun to add them to `FailureWeights`.
+#[derive(Clone, Copy, CopyGetters, Debug, Eq, PartialEq)]
+#[getset(get_copy = "pub")]
+pub struct OutputWeights {
+    syndrome: usize,
+    e_out: usize,
+    diff: usize,
+}
+
+impl OutputWeights {
+    pub fn new(e_in: &SparseErrorVector, syn: &Syndrome, e_out: &ErrorVector) -> Self {
+        Self::from_support(e_in.support(), syn, e_out)
+    }
+
+    /// Same as `new`, but takes the support of the input error vector, which may have any
+    /// weight.
+    pub fn from_support(e_in_supp: &[Index], syn: &Syndrome, e_out: &ErrorVector) -> Self {
+        let e_out_weight = e_out.hamming_weight();
+        let overlap = e_in_supp
+            .iter()
+            .filter(|idx| e_out.get(**idx as usize))
+            .count();
+        Self {
+            syndrome: syn.hamming_weight(),
+            e_out: e_out_weight,
+            diff: e_in_supp.len() + e_out_weight - 2 * overlap,
+        }
+    }
+
+    /// Reruns the decoder on a decoding failure to compute its output weights.
+    pub fn recompute(df: &DecodingFailure, iterations: usize, tau: u8) -> Self {
+        let e_in = df.vector().vector();
+        let mut syn = Syndrome::from_sparse(df.key(), e_in);
+        let (e_out, _) = bgf_decoder_with_params(df.key(), &mut syn, iterations, tau);
+        Self::new(e_in, &syn, &e_out)
+    }
+}
+
 #[derive(Clone, CopyGetters, Debug, Default, Serialize, Deserialize)]
 #[getset(get_copy = "pub")]
 pub struct DecodingFailureRatio {
@@ -124,12 +635,103 @@ impl DecodingFailureRatio {
     pub fn as_f64(&self) -> f64 {
         self.num_failures as f64 / self.num_trials as f64
     }
+
+    /// Wilson score interval `(lower, upper)` for the true DFR at confidence level
+    /// `1 - alpha`, e.g. `alpha = 0.05` for a 95% confidence interval. If no trials have
+    /// been run, returns `(0.0, 1.0)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `alpha` is not strictly between 0 and 1.
+    pub fn confidence_interval(&self, alpha: f64) -> (f64, f64) {
+        assert!(
+            0.0 < alpha && alpha < 1.0,
+            "alpha must be strictly between 0 and 1"
+        );
+        if self.num_trials == 0 {
+            return (0.0, 1.0);
+        }
+        let z = normal_quantile(1.0 - alpha / 2.0);
+        let n = self.num_trials as f64;
+        let p = self.as_f64();
+        let z2 = z * z;
+        let denom = 1.0 + z2 / n;
+        let center = (p + z2 / (2.0 * n)) / denom;
+        let half_width = z * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt() / denom;
+        // The bounds are exactly 0 or 1 at the extremes, but may be slightly off due to rounding
+        let lower = if self.num_failures == 0 {
+            0.0
+        } else {
+            center - half_width
+        };
+        let upper = if self.num_failures == self.num_trials {
+            1.0
+        } else {
+            center + half_width
+        };
+        (lower, upper)
+    }
+}
+
+// Quantile function of the standard normal distribution, using Acklam's rational
+// approximation (relative error less than 1.2e-9).
+fn normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969683028665376e1,
+        2.209460984245205e2,
+        -2.759285104469687e2,
+        1.38357751867269e2,
+        -3.066479806614716e1,
+        2.506628277459239,
+    ];
+    const B: [f64; 5] = [
+        -5.447609879822406e1,
+        1.615858368580409e2,
+        -1.556989798598866e2,
+        6.680131188771972e1,
+        -1.328068155288572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784894002430293e-3,
+        -3.223964580411365e-1,
+        -2.400758277161838,
+        -2.549732539343734,
+        4.374664141464968,
+        2.938163982698783,
+    ];
+    const D: [f64; 4] = [
+        7.784695709041462e-3,
+        3.224671290700398e-1,
+        2.445134137142996,
+        3.754408661907416,
+    ];
+    const P_LOW: f64 = 0.02425;
+    if p < P_LOW {
+        let q = (-2.0 * p.ln()).sqrt();
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    } else if p <= 1.0 - P_LOW {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    } else {
+        -normal_quantile(1.0 - p)
+    }
 }
 
 #[derive(Clone, Copy, Debug, Error)]
 #[error("invalid decoding failure ratio: number of failures must be <= number of trials")]
 pub struct InvalidDFRError;
 
+#[derive(Debug, Error)]
+pub enum LoadRecordError {
+    #[error("Record file should be readable: {0}")]
+    Unreadable(io::Error),
+    #[error("Record file should contain valid JSON data record: {0}")]
+    Unparseable(serde_json::Error),
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -141,7 +743,7 @@ mod tests {
         1000000,"decoding_failures":[{"h0":[11,21,100,124,229,271,284,307,380,397,420,438,445,495,
         555],"h1":[10,41,50,59,62,119,153,164,179,208,284,384,438,513,554],"e_supp":[42,187,189,
         336,409,445,464,485,524,532,617,804,877,892,1085,1099,1117,1150],"e_source":"Random",
-        "thread":2}],"seed":"52e19bb7d8474289f86caee35a11ac16dd09902d84fa01173ad83d7b1c376109",
+        "thread":2}],"failures_seen":1,"seed":"52e19bb7d8474289f86caee35a11ac16dd09902d84fa01173ad83d7b1c376109",
         "runtime":1.478772912,"thread_count":8}"#
             .split_whitespace()
             .collect()
@@ -154,6 +756,165 @@ mod tests {
         assert_eq!(json_str, serde_json::to_string(&data_record).unwrap());
     }
 
+    #[test]
+    fn failures_seen_defaults_to_num_failures() {
+        let mut json_data: Value = serde_json::from_str(&json_test_string()).unwrap();
+        json_data["num_failures"] = 5.into();
+        json_data.as_object_mut().unwrap().remove("failures_seen");
+        let data_record: DataRecord = serde_json::from_value(json_data).unwrap();
+        assert_eq!(data_record.failures_seen(), 5);
+        assert!(data_record.failure_weights().is_empty());
+    }
+
+    #[test]
+    fn distinct_keys_up_to_shift() {
+        let json_data: Value = serde_json::from_str(&json_test_string()).unwrap();
+        let mut data_record: DataRecord = serde_json::from_value(json_data).unwrap();
+        data_record.count_distinct_keys();
+        assert_eq!(data_record.distinct_failure_keys(), Some(1));
+        let df = data_record.decoding_failures()[0].clone();
+        for delta in [1, 100, 586] {
+            let mut df_json = serde_json::to_value(&df).unwrap();
+            let shifted = df.key().shifted(delta);
+            df_json["h0"] = serde_json::to_value(shifted.h0()).unwrap();
+            df_json["h1"] = serde_json::to_value(shifted.h1()).unwrap();
+            data_record.push_decoding_failure(serde_json::from_value(df_json).unwrap());
+        }
+        assert_eq!(data_record.distinct_failure_keys(), Some(1));
+        let mut df_json = serde_json::to_value(&df).unwrap();
+        df_json["h0"] = serde_json::to_value(df.key().h1()).unwrap();
+        df_json["h1"] = serde_json::to_value(df.key().h0()).unwrap();
+        data_record.push_decoding_failure(serde_json::from_value(df_json).unwrap());
+        assert_eq!(data_record.distinct_failure_keys(), Some(2));
+        data_record.count_distinct_keys();
+        assert_eq!(data_record.distinct_failure_keys(), Some(2));
+    }
+
+    #[test]
+    fn sort_decoding_failures() {
+        let json_data: Value = serde_json::from_str(&json_test_string()).unwrap();
+        let mut data_record: DataRecord = serde_json::from_value(json_data).unwrap();
+        let df = data_record.decoding_failures()[0].clone();
+        let mut classified = df.clone();
+        classified.weak_key = Some(df.key().classify_weak(3));
+        assert_ne!(classified, df);
+        let mut failures = Vec::new();
+        for thread in [Some(2), None, Some(0)] {
+            for e_supp in [
+                [
+                    3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 1000,
+                ],
+                [
+                    1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18,
+                ],
+            ] {
+                let mut df_json = serde_json::to_value(&df).unwrap();
+                df_json["e_supp"] = e_supp[..].into();
+                df_json["thread"] = thread.into();
+                failures.push(serde_json::from_value::<DecodingFailure>(df_json).unwrap());
+            }
+        }
+        data_record.decoding_failures = failures.clone();
+        data_record.sort_decoding_failures();
+        let expected = data_record.decoding_failures().clone();
+        assert_eq!(expected[0].thread, None);
+        assert_eq!(expected[5].thread, Some(2));
+        assert_eq!(expected[0].vector().vector().support()[0], 1);
+        for order in [[5, 0, 3, 1, 4, 2], [2, 4, 1, 3, 0, 5], [0, 1, 2, 3, 4, 5]] {
+            data_record.decoding_failures.clear();
+            for i in order {
+                data_record.push_decoding_failure(failures[i].clone());
+            }
+            data_record.sort_decoding_failures();
+            assert_eq!(data_record.decoding_failures(), &expected);
+        }
+    }
+
+    #[test]
+    fn confidence_intervals() {
+        // Reference Wilson score intervals: (failures, trials, alpha, lower, upper)
+        let reference = [
+            (1, 1_000_000, 0.05, 1.7652458e-7, 5.6649118e-6),
+            (10, 5000, 0.05, 1.0867469e-3, 3.6778843e-3),
+            (1, 1_000_000, 0.01, 1.1740547e-7, 8.5174338e-6),
+            (37, 1000, 0.1, 2.8364863e-2, 4.8133710e-2),
+        ];
+        for (num_failures, num_trials, alpha, ref_lower, ref_upper) in reference {
+            let dfr = DecodingFailureRatio::new(num_failures, num_trials).unwrap();
+            let (lower, upper) = dfr.confidence_interval(alpha);
+            assert!(lower < dfr.as_f64() && dfr.as_f64() < upper);
+            assert!((lower - ref_lower).abs() / ref_lower < 1e-6);
+            assert!((upper - ref_upper).abs() / ref_upper < 1e-6);
+        }
+    }
+
+    #[test]
+    fn confidence_interval_edge_cases() {
+        let zero = DecodingFailureRatio::new(0, 0).unwrap();
+        assert_eq!(zero.confidence_interval(0.05), (0.0, 1.0));
+        let none = DecodingFailureRatio::new(0, 100).unwrap();
+        let (lower, upper) = none.confidence_interval(0.05);
+        assert_eq!(lower, 0.0);
+        assert!(upper > 0.0 && upper < 0.05);
+        let all = DecodingFailureRatio::new(100, 100).unwrap();
+        let (lower, upper) = all.confidence_interval(0.05);
+        assert!(lower > 0.95 && lower < 1.0);
+        assert_eq!(upper, 1.0);
+    }
+
+    #[test]
+    fn failure_weight_histograms() {
+        let mut data_record: DataRecord = serde_json::from_str(&json_test_string()).unwrap();
+        assert!(data_record.failure_weights().is_empty());
+        let df = data_record.decoding_failures()[0].clone();
+        let key = Key::from_support(
+            [
+                93, 99, 105, 121, 126, 141, 156, 193, 194, 197, 264, 301, 360, 400, 429,
+            ],
+            [
+                100, 117, 189, 191, 211, 325, 340, 386, 440, 461, 465, 474, 534, 565, 578,
+            ],
+        )
+        .unwrap();
+        let mut df_json = serde_json::to_value(&df).unwrap();
+        df_json["h0"] = key.h0().support()[..].into();
+        df_json["h1"] = key.h1().support()[..].into();
+        df_json["e_supp"] = [
+            16, 73, 89, 201, 346, 522, 547, 553, 574, 575, 613, 619, 637, 713, 955, 960, 983, 1008,
+        ][..]
+            .into();
+        let df_cycle: DecodingFailure = serde_json::from_value(df_json).unwrap();
+        let (iterations, tau) = (data_record.iterations(), data_record.gray_threshold_diff());
+        for _ in 0..3 {
+            data_record.add_failure_weights(OutputWeights::recompute(&df, iterations, tau));
+        }
+        data_record.add_failure_weights(OutputWeights::recompute(&df_cycle, iterations, tau));
+        assert_eq!(data_record.failures_seen(), 5);
+        let weights = data_record.failure_weights();
+        for histogram in [weights.syndrome(), weights.e_out(), weights.diff()] {
+            assert_eq!(histogram.total(), 4);
+        }
+        assert_eq!(
+            weights.syndrome().iter().collect::<Vec<_>>(),
+            [(15, 3), (114, 1)]
+        );
+        assert_eq!(
+            weights.e_out().iter().collect::<Vec<_>>(),
+            [(16, 1), (25, 3)]
+        );
+        assert_eq!(
+            weights.diff().iter().collect::<Vec<_>>(),
+            [(15, 3), (26, 1)]
+        );
+        let json = serde_json::to_value(&data_record).unwrap();
+        assert_eq!(json["failure_weights"]["syndrome"]["114"], 1);
+        let deserialized: DataRecord = serde_json::from_value(json).unwrap();
+        assert_eq!(
+            deserialized.failure_weights(),
+            data_record.failure_weights()
+        );
+    }
+
     #[test]
     fn data_record_serde_value() {
         let json_data: Value = serde_json::from_str(&json_test_string()).unwrap();
@@ -2,21 +2,47 @@ use crate::output::OutputTo;
 use bike_decoder::{
     keys::{FilterError, Key, KeyFilter},
     ncw::NearCodewordClass,
-    random::{Seed, SeedFromHexError},
+    parameters::{
+        BLOCK_LENGTH, BLOCK_WEIGHT, ERROR_WEIGHT, GRAY_THRESHOLD_DIFF, NB_ITER, ROW_LENGTH,
+    },
+    random::{RngState, RngStateParseError, Seed, SeedFromHexError},
     vectors::InvalidSupport,
 };
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 use derive_builder::Builder;
 use getset::{CopyGetters, Getters};
 use hex::FromHex;
-use std::num::NonZeroU64;
+use rand::Rng;
+use std::{
+    fs, io,
+    num::NonZeroU64,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 use thiserror::Error;
 
 #[derive(Clone, Debug, Parser)]
 #[command(author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct Args {
-    #[arg(short = 'N', long, help = "Number of trials (required)")]
-    number: f64, // parsed as scientific notation to usize
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[arg(
+        short = 'N',
+        long,
+        required_unless_present = "time_budget",
+        help = "Number of trials (required unless --time-budget is given)"
+    )]
+    number: Option<f64>, // parsed as scientific notation to usize
+    #[arg(
+        long,
+        help = "Run trials until the given number of seconds has elapsed (ignores -N)"
+    )]
+    time_budget: Option<f64>,
     #[arg(short, long, default_value_t=0, value_parser=clap::value_parser!(i8).range(-1..=3),
         help="Weak key filter (-1: non-weak only; 0: no filter; 1-3: type 1-3 only)")]
     weak_keys: i8,
@@ -25,6 +51,26 @@ pub struct Args {
     weak_key_threshold: u8,
     #[arg(long, help = "Always use the specified key (in JSON format)")]
     fixed_key: Option<String>,
+    #[arg(
+        long,
+        conflicts_with = "fixed_key",
+        help = "Use keys from the given file containing a JSON array of keys"
+    )]
+    key_file: Option<String>,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        requires = "key_file",
+        help = "Order in which keys from --key-file are used"
+    )]
+    key_order: KeyOrder,
+    #[arg(
+        long,
+        requires = "fixed_key",
+        help = "Include the fixed key in each recorded decoding failure"
+    )]
+    always_include_key: bool,
     #[arg(
         short = 'S',
         long,
@@ -38,8 +84,62 @@ pub struct Args {
         requires = "ncw"
     )]
     ncw_overlap: Option<usize>,
+    #[arg(
+        long,
+        requires = "ncw",
+        help = "Fraction of trials using error vectors from A_{t,l}(S), with the rest uniformly random [default: 1]"
+    )]
+    error_source_mix: Option<f64>,
+    #[arg(
+        long,
+        help = "Exit with an error instead of a warning if every trial is expected to fail"
+    )]
+    deny_guaranteed_failure: bool,
+    #[arg(long, conflicts_with = "ncw",
+        value_parser = clap::value_parser!(u64).range(0..=ROW_LENGTH as u64),
+        help = "Weight of error vectors [default: ERROR_WEIGHT set at compile time]")]
+    error_weight: Option<u64>,
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..=u32::MAX as u64),
+        help = "Number of decoder iterations [default: NB_ITER set at compile time]")]
+    iterations: Option<u64>,
+    #[arg(
+        long,
+        help = "Gray threshold difference tau [default: GRAY_THRESHOLD_DIFF set at compile time]"
+    )]
+    tau: Option<u8>,
+    #[arg(long, value_parser=clap::value_parser!(u8).range(3..),
+        help="Record whether the key of each recorded decoding failure is weak of type 2 or 3 \
+        with the given threshold")]
+    classify_weak: Option<u8>,
+    #[arg(
+        long,
+        help = "Count distinct keys (up to cyclic shifts) among recorded decoding failures"
+    )]
+    count_distinct_keys: bool,
+    #[arg(
+        long,
+        help = "Display progress bar with current DFR and ETA (parallel mode only)"
+    )]
+    progress: bool,
     #[arg(short, long, help = "Output file [default: stdout]")]
     output: Option<String>,
+    #[arg(
+        long,
+        conflicts_with = "output",
+        help = "Write output to a file in the given directory named after the parameters and seed"
+    )]
+    output_dir: Option<String>,
+    #[arg(
+        long,
+        requires = "output",
+        help = "Compress output file with zstd (default if output file name ends in .zst)"
+    )]
+    compress: bool,
+    #[arg(
+        long,
+        help = "Write JSON output with sorted keys and no insignificant whitespace"
+    )]
+    canonical_json: bool,
     #[arg(
         long,
         help = "If output file already exists, overwrite without creating backup"
@@ -51,6 +151,9 @@ pub struct Args {
         help = "Run in parallel with automatically chosen number of threads"
     )]
     parallel: bool,
+    #[arg(long, requires = "output", conflicts_with_all = ["parallel", "threads"],
+        help = "Continue trials recorded in existing output file (single-threaded only)")]
+    resume: bool,
     #[arg(
         short,
         long,
@@ -58,37 +161,129 @@ pub struct Args {
         help = "Max number of decoding failures recorded"
     )]
     recordmax: f64, // parsed as scientific notation to usize
+    #[arg(
+        long,
+        help = "Record a uniform random sample of decoding failures instead of the first ones found"
+    )]
+    sample_failures: bool,
+    #[arg(long, conflicts_with_all = ["parallel", "threads"],
+        help = "Record up to N successful decodings in addition to failures (single-threaded only)")]
+    record_successes: Option<usize>,
     #[arg(short, long, help = "Save to disk frequency [default: only at end]")]
     savefreq: Option<f64>, // parsed as scientific notation to usize
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..),
+        help = "Number of save intervals between flushes to disk (results are always flushed at end)")]
+    flush_every: u64,
     #[arg(
         long,
         help = "Specify PRNG seed as 256-bit hex string [default: random]"
     )]
     seed: Option<String>,
+    #[arg(
+        long,
+        conflicts_with = "seed",
+        help = "Derive PRNG seed from the given passphrase"
+    )]
+    seed_phrase: Option<String>,
     #[arg(long, conflicts_with_all=["parallel", "threads"],
         help="Initialize PRNG to match specified thread index (single-threaded only)")]
     seed_index: Option<u32>,
+    #[arg(long, conflicts_with_all=["seed", "seed_phrase", "seed_index", "parallel", "threads"],
+        help="Initialize PRNG to state <SEED>:<INDEX> (single-threaded only)")]
+    rng_state: Option<String>,
     #[arg(long, help = "Set number of threads (ignores --parallel)")]
     threads: Option<usize>,
+    #[arg(
+        long,
+        help = "Make parallel results reproducible for a given seed and number of threads"
+    )]
+    deterministic_threads: bool,
+    #[arg(
+        long,
+        help = "Validate settings and print the run parameters without running any trials"
+    )]
+    dry_run: bool,
+    #[arg(
+        long,
+        help = "Exit with code 1 if the measured DFR exceeds the given threshold"
+    )]
+    fail_on_dfr: Option<f64>,
     #[arg(short, long, action = clap::ArgAction::Count,
         help="Print statistics and/or decoding failures [repeat for more verbose, max 3]")]
     verbose: u8,
 }
 
-#[derive(Builder, Clone, CopyGetters, Debug, Eq, Getters, PartialEq)]
+impl Args {
+    #[inline]
+    pub fn command(&self) -> Option<&Command> {
+        self.command.as_ref()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Subcommand)]
+pub enum Command {
+    #[command(
+        about = "Compare runtime of unsatisfied parity check computation with and \
+        without SIMD instructions"
+    )]
+    BenchUpc {
+        #[arg(
+            short = 'N',
+            long,
+            default_value_t = 1e5,
+            help = "Number of iterations"
+        )]
+        number: f64, // parsed as scientific notation to usize
+    },
+    #[command(about = "Print JSON Schema describing the output format and exit")]
+    PrintSchema,
+    #[command(
+        about = "Combine results from multiple output files and print a CSV summary of the \
+        DFR for each distinct configuration"
+    )]
+    Aggregate {
+        #[arg(required = true, help = "Output files to combine")]
+        paths: Vec<String>,
+    },
+    #[command(
+        about = "Print the threshold for each syndrome weight as CSV, without running trials"
+    )]
+    DumpThresholds {
+        #[arg(short, default_value_t = BLOCK_LENGTH, help = "Block length")]
+        r: usize,
+        #[arg(short, default_value_t = BLOCK_WEIGHT, help = "Block weight")]
+        d: usize,
+        #[arg(short, default_value_t = ERROR_WEIGHT, help = "Error weight")]
+        t: usize,
+    },
+}
+
+#[derive(Builder, Clone, CopyGetters, Debug, Getters, PartialEq)]
 pub struct Settings {
     #[getset(get_copy = "pub")]
     num_trials: u64,
     #[builder(default)]
+    #[getset(get_copy = "pub")]
+    time_budget: Option<Duration>,
+    #[builder(default)]
     #[getset(get = "pub")]
     trial_settings: TrialSettings,
     #[builder(default)]
     save_frequency: Option<NonZeroU64>,
+    #[builder(default = "1")]
+    #[getset(get_copy = "pub")]
+    flush_every: u64,
     #[builder(default = "10000")]
     #[getset(get_copy = "pub")]
     record_max: usize,
     #[builder(default)]
     #[getset(get_copy = "pub")]
+    sample_failures: bool,
+    #[builder(default)]
+    #[getset(get_copy = "pub")]
+    record_successes: usize,
+    #[builder(default)]
+    #[getset(get_copy = "pub")]
     verbose: u8,
     #[builder(default)]
     #[getset(get_copy = "pub")]
@@ -100,20 +295,77 @@ pub struct Settings {
     #[getset(get_copy = "pub")]
     threads: usize,
     #[builder(default)]
+    #[getset(get_copy = "pub")]
+    deterministic_threads: bool,
+    #[builder(default)]
     #[getset(get = "pub")]
     output: OutputTo,
     #[builder(default)]
     #[getset(get_copy = "pub")]
+    canonical_json: bool,
+    #[builder(default)]
+    #[getset(get_copy = "pub")]
     overwrite: bool,
+    #[builder(default)]
+    #[getset(get_copy = "pub")]
+    resume: bool,
+    #[builder(default)]
+    #[getset(get_copy = "pub")]
+    classify_weak: Option<u8>,
+    #[builder(default)]
+    #[getset(get_copy = "pub")]
+    count_distinct_keys: bool,
+    #[builder(default)]
+    #[getset(get_copy = "pub")]
+    always_include_key: bool,
+    #[builder(default)]
+    #[getset(get_copy = "pub")]
+    progress: bool,
+    #[builder(default)]
+    #[getset(get_copy = "pub")]
+    dry_run: bool,
+    #[builder(default)]
+    #[getset(get_copy = "pub")]
+    fail_on_dfr: Option<f64>,
 }
 
 impl TryFrom<Args> for Settings {
     type Error = SettingsError;
     fn try_from(args: Args) -> Result<Self, Self::Error> {
+        let rng_state = args
+            .rng_state
+            .as_deref()
+            .map(str::parse::<RngState>)
+            .transpose()?;
+        let time_budget = args
+            .time_budget
+            .map(|secs| {
+                Duration::try_from_secs_f64(secs).or(Err(SettingsError::InvalidTimeBudget(secs)))
+            })
+            .transpose()?;
+        let key_filter = KeyFilter::new(args.weak_keys, args.weak_key_threshold)?;
+        let seed = match rng_state {
+            Some(state) => Some(state.seed()),
+            None => match args.seed_phrase {
+                Some(phrase) => Some(Seed::from_passphrase(&phrase)),
+                None => args.seed.map(Seed::from_hex).transpose()?,
+            },
+        };
+        // The output file name depends on the seed, so it must be fixed now
+        let seed = seed.or_else(|| args.output_dir.is_some().then(Seed::from_entropy));
+        let error_weight = args
+            .error_weight
+            .map_or(ERROR_WEIGHT, |weight| weight as usize);
         let settings = Self {
-            num_trials: args.number as u64,
+            // With a time budget, trials run until the budget is exhausted
+            num_trials: if time_budget.is_some() {
+                u64::MAX
+            } else {
+                args.number.ok_or(SettingsError::MissingNumber)? as u64
+            },
+            time_budget,
             trial_settings: TrialSettings::new(
-                KeyFilter::new(args.weak_keys, args.weak_key_threshold)?,
+                key_filter,
                 args.fixed_key
                     .as_deref()
                     .map(serde_json::from_str)
@@ -121,21 +373,48 @@ impl TryFrom<Args> for Settings {
                     .map_err(SettingsError::UnparseableFixedKey)?,
                 args.ncw,
                 args.ncw_overlap,
-            )?,
+            )?
+            .with_iterations(args.iterations.map(|iters| iters as usize))
+            .with_tau(args.tau)
+            .with_error_weight(args.error_weight.map(|weight| weight as usize))
+            .with_ncw_fraction(
+                args.error_source_mix
+                    .map(|fraction| {
+                        (0.0..=1.0)
+                            .contains(&fraction)
+                            .then_some(fraction)
+                            .ok_or(SettingsError::InvalidErrorSourceMix(fraction))
+                    })
+                    .transpose()?,
+            )
+            .with_key_pool(
+                args.key_file
+                    .as_deref()
+                    .map(|path| KeyPool::from_file(path, args.key_order, key_filter))
+                    .transpose()?,
+            )
+            .check_guaranteed_failure(args.deny_guaranteed_failure)?,
             save_frequency: args
                 .savefreq
                 .map(|s| s as u64)
                 .map(|s| s.max(Self::MIN_SAVE_FREQUENCY))
                 .and_then(NonZeroU64::new),
+            flush_every: args.flush_every,
             record_max: args.recordmax as usize,
+            sample_failures: args.sample_failures,
+            record_successes: args.record_successes.unwrap_or(0),
             verbose: args.verbose,
-            seed: args.seed.map(Seed::from_hex).transpose()?,
-            seed_index: args.seed_index.map(|seed_idx| {
-                if seed_idx >= 1 << 24 {
-                    eprintln!("Warning: very large PRNG seed index will be slow to initialize.");
-                }
-                seed_idx
-            }),
+            seed,
+            seed_index: rng_state
+                .map(|state| state.index())
+                .or(args.seed_index)
+                .inspect(|&seed_idx| {
+                    if seed_idx >= 1 << 24 {
+                        eprintln!(
+                            "Warning: very large PRNG seed index will be slow to initialize."
+                        );
+                    }
+                }),
             // Default if --threads not specified:
             // * If --parallel flag not set, settings.threads = 1
             // * If --parallel flag set, settings.threads = 0, which tells
@@ -144,15 +423,51 @@ impl TryFrom<Args> for Settings {
                 || usize::from(!args.parallel),
                 |threads| threads.clamp(1, Self::MAX_THREAD_COUNT),
             ),
-            output: args
-                .output
-                .map_or(OutputTo::Stdout, |path| OutputTo::File(path.into())),
+            deterministic_threads: args.deterministic_threads,
+            output: match (args.output, args.output_dir) {
+                (_, Some(dir)) => OutputTo::File(output_dir_path(
+                    dir,
+                    error_weight,
+                    seed.expect("Seed should be set when --output-dir is given"),
+                )),
+                (Some(path), _) if args.compress || path.ends_with(".zst") => {
+                    OutputTo::CompressedFile(path.into())
+                }
+                (Some(path), _) => OutputTo::File(path.into()),
+                (None, None) => OutputTo::Stdout,
+            },
+            canonical_json: args.canonical_json,
             overwrite: args.overwrite,
+            resume: args.resume,
+            classify_weak: args.classify_weak,
+            count_distinct_keys: args.count_distinct_keys,
+            always_include_key: args.always_include_key,
+            progress: args.progress,
+            dry_run: args.dry_run,
+            fail_on_dfr: args
+                .fail_on_dfr
+                .map(|threshold| {
+                    (0.0..=1.0)
+                        .contains(&threshold)
+                        .then_some(threshold)
+                        .ok_or(SettingsError::InvalidDfrThreshold(threshold))
+                })
+                .transpose()?,
         };
         Ok(settings)
     }
 }
 
+/// Output file path used with `--output-dir`, of the form `<dir>/r<r>_d<d>_t<t>_<seed8>.json`,
+/// where `seed8` is the first 8 hex digits of the PRNG seed.
+pub fn output_dir_path<P: AsRef<Path>>(dir: P, error_weight: usize, seed: Seed) -> PathBuf {
+    let seed_hex = seed.to_string();
+    dir.as_ref().join(format!(
+        "r{BLOCK_LENGTH}_d{BLOCK_WEIGHT}_t{error_weight}_{}.json",
+        &seed_hex[..8]
+    ))
+}
+
 impl Settings {
     const MIN_SAVE_FREQUENCY: u64 = 10000;
     const MAX_THREAD_COUNT: usize = 1024;
@@ -177,18 +492,62 @@ impl Settings {
         self.trial_settings.ncw_overlap()
     }
 
+    #[inline]
+    pub fn iterations(&self) -> usize {
+        self.trial_settings.iterations()
+    }
+
+    #[inline]
+    pub fn error_weight(&self) -> usize {
+        self.trial_settings.error_weight()
+    }
+
+    #[inline]
+    pub fn tau(&self) -> u8 {
+        self.trial_settings.tau()
+    }
+
+    /// Number of trials between writes to disk. If a time budget is set, this is also the
+    /// number of trials between checks of the elapsed time in parallel mode, so it defaults
+    /// to `MIN_SAVE_FREQUENCY` rather than the number of trials.
     #[inline]
     pub fn save_frequency(&self) -> u64 {
-        self.save_frequency.map_or(self.num_trials, u64::from)
+        let default = if self.time_budget.is_some() {
+            Self::MIN_SAVE_FREQUENCY
+        } else {
+            self.num_trials
+        };
+        self.save_frequency.map_or(default, u64::from)
+    }
+
+    /// Whether the results should be flushed to disk at the end of the given save interval
+    /// (counting from 1), i.e., whether `interval` is a multiple of `flush_every`. Results
+    /// are always flushed at the end of the run regardless.
+    // u64::is_multiple_of requires Rust 1.87
+    #[allow(clippy::manual_is_multiple_of)]
+    #[inline]
+    pub fn flushes_at(&self, interval: u64) -> bool {
+        interval % self.flush_every.max(1) == 0
     }
 
     #[inline]
     pub fn parallel(&self) -> bool {
         self.threads != 1
     }
+
+    /// Number of threads to run, with 0 (choose automatically) replaced by the number of
+    /// logical CPUs.
+    #[inline]
+    pub fn thread_count(&self) -> usize {
+        if self.threads == 0 {
+            num_cpus::get()
+        } else {
+            self.threads
+        }
+    }
 }
 
-#[derive(Clone, CopyGetters, Debug, Default, PartialEq, Eq)]
+#[derive(Clone, CopyGetters, Debug, Default, PartialEq)]
 pub struct TrialSettings {
     #[getset(get_copy = "pub")]
     key_filter: KeyFilter,
@@ -197,6 +556,12 @@ pub struct TrialSettings {
     ncw_class: Option<NearCodewordClass>,
     #[getset(get_copy = "pub")]
     ncw_overlap: Option<usize>,
+    iterations: Option<usize>,
+    tau: Option<u8>,
+    error_weight: Option<usize>,
+    #[getset(get_copy = "pub")]
+    ncw_fraction: Option<f64>,
+    key_pool: Option<KeyPool>,
 }
 
 impl TrialSettings {
@@ -225,17 +590,200 @@ impl TrialSettings {
             fixed_key,
             ncw_class,
             ncw_overlap,
+            iterations: None,
+            tau: None,
+            error_weight: None,
+            ncw_fraction: None,
+            key_pool: None,
         })
     }
 
+    /// Overrides the number of decoder iterations, which otherwise defaults to `NB_ITER`.
+    #[inline]
+    pub fn with_iterations(mut self, iterations: Option<usize>) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Overrides the gray threshold difference, which otherwise defaults to
+    /// `GRAY_THRESHOLD_DIFF`.
+    #[inline]
+    pub fn with_tau(mut self, tau: Option<u8>) -> Self {
+        self.tau = tau;
+        self
+    }
+
+    /// Overrides the weight of the error vectors, which otherwise defaults to `ERROR_WEIGHT`.
+    /// Error vectors of any other weight are sampled uniformly at random, ignoring the
+    /// near-codeword settings.
+    #[inline]
+    pub fn with_error_weight(mut self, error_weight: Option<usize>) -> Self {
+        self.error_weight = error_weight;
+        self
+    }
+
+    /// Samples the error vector from the near-codeword set only with probability
+    /// `ncw_fraction`, and uniformly at random otherwise. If unset, every error vector is
+    /// sampled from the near-codeword set (if its class is set).
+    #[inline]
+    pub fn with_ncw_fraction(mut self, ncw_fraction: Option<f64>) -> Self {
+        self.ncw_fraction = ncw_fraction;
+        self
+    }
+
+    /// Draws the key of each trial from the given pool instead of generating it at random.
+    /// Has no effect if a fixed key is set.
+    #[inline]
+    pub fn with_key_pool(mut self, key_pool: Option<KeyPool>) -> Self {
+        self.key_pool = key_pool;
+        self
+    }
+
+    /// Returns true if every error vector sampled with these settings contains a whole
+    /// near-codeword of type N, i.e., `--ncw N --ncw-overlap BLOCK_WEIGHT`, in which case
+    /// decoding is expected to fail in every trial regardless of the key.
+    // Option::is_none_or requires Rust 1.82
+    #[allow(clippy::unnecessary_map_or)]
+    pub fn guarantees_failure(&self) -> bool {
+        self.error_weight() == ERROR_WEIGHT
+            && self.ncw_class == Some(NearCodewordClass::N)
+            && self.ncw_overlap == Some(NearCodewordClass::N.max_l())
+            && self.ncw_fraction.map_or(true, |fraction| fraction >= 1.0)
+    }
+
+    /// Warns on stderr if these settings are expected to make every trial fail, or returns
+    /// an error instead if `deny` is set, since such a DFR of 1 is rarely intended.
+    pub fn check_guaranteed_failure(self, deny: bool) -> Result<Self, SettingsError> {
+        if self.guarantees_failure() {
+            if deny {
+                return Err(SettingsError::GuaranteedFailure);
+            }
+            eprintln!(
+                "Warning: error vectors containing a whole near-codeword of type N are \
+                expected to cause a decoding failure in every trial."
+            );
+        }
+        Ok(self)
+    }
+
     #[inline]
     pub fn fixed_key(&self) -> Option<&Key> {
         self.fixed_key.as_ref()
     }
+
+    #[inline]
+    pub fn key_pool(&self) -> Option<&KeyPool> {
+        self.key_pool.as_ref()
+    }
+
+    #[inline]
+    pub fn iterations(&self) -> usize {
+        self.iterations.unwrap_or(NB_ITER)
+    }
+
+    #[inline]
+    pub fn tau(&self) -> u8 {
+        self.tau.unwrap_or(GRAY_THRESHOLD_DIFF)
+    }
+
+    #[inline]
+    pub fn error_weight(&self) -> usize {
+        self.error_weight.unwrap_or(ERROR_WEIGHT)
+    }
 }
 
+/// Order in which keys are taken from a `KeyPool`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum KeyOrder {
+    /// Each trial uses a uniformly random key from the pool.
+    #[default]
+    Random,
+    /// Trials cycle through the keys in the order they're listed.
+    RoundRobin,
+}
+
+/// List of keys from which the key of each trial is drawn.
+///
+/// With `KeyOrder::RoundRobin`, the position in the list is shared by all clones of the
+/// pool, so the keys are cycled through across all threads, but which thread gets which
+/// key then depends on scheduling.
+#[derive(Clone, Debug, Getters)]
+pub struct KeyPool {
+    #[getset(get = "pub")]
+    keys: Vec<Key>,
+    order: KeyOrder,
+    next: Arc<AtomicUsize>,
+}
+
+impl KeyPool {
+    /// Creates a pool from the given keys, each of which must be valid and match
+    /// `key_filter`. The keys are sorted, as with `--fixed-key`.
+    pub fn new(
+        mut keys: Vec<Key>,
+        order: KeyOrder,
+        key_filter: KeyFilter,
+    ) -> Result<Self, SettingsError> {
+        if keys.is_empty() {
+            return Err(SettingsError::EmptyKeyFile);
+        }
+        for (idx, key) in keys.iter_mut().enumerate() {
+            key.validate()
+                .map_err(|err| SettingsError::InvalidKeyFileKey(idx, err))?;
+            if !key.matches_filter(key_filter) {
+                return Err(SettingsError::KeyFileFilter(idx, key_filter));
+            }
+            key.sort();
+        }
+        Ok(Self {
+            keys,
+            order,
+            next: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    /// Loads the pool from a file containing a JSON array of keys.
+    pub fn from_file(
+        path: &str,
+        order: KeyOrder,
+        key_filter: KeyFilter,
+    ) -> Result<Self, SettingsError> {
+        let contents = fs::read_to_string(path).map_err(SettingsError::UnreadableKeyFile)?;
+        let keys = serde_json::from_str(&contents).map_err(SettingsError::UnparseableKeyFile)?;
+        Self::new(keys, order, key_filter)
+    }
+
+    #[inline]
+    pub fn order(&self) -> KeyOrder {
+        self.order
+    }
+
+    /// Returns the key to use for the next trial.
+    pub fn sample<R>(&self, rng: &mut R) -> &Key
+    where
+        R: Rng + ?Sized,
+    {
+        let idx = match self.order {
+            KeyOrder::Random => rng.gen_range(0..self.keys.len()),
+            KeyOrder::RoundRobin => self.next.fetch_add(1, Ordering::Relaxed) % self.keys.len(),
+        };
+        &self.keys[idx]
+    }
+}
+
+impl PartialEq for KeyPool {
+    fn eq(&self, other: &Self) -> bool {
+        self.keys == other.keys && self.order == other.order
+    }
+}
+
+impl Eq for KeyPool {}
+
 #[derive(Debug, Error)]
 pub enum SettingsError {
+    #[error("number of trials must be specified")]
+    MissingNumber,
+    #[error("--time-budget must be a nonnegative number of seconds: {0}")]
+    InvalidTimeBudget(f64),
     #[error(transparent)]
     InvalidFilter(#[from] FilterError),
     #[error(
@@ -247,23 +795,48 @@ pub enum SettingsError {
     InvalidFixedKeySupport(#[from] InvalidSupport),
     #[error("--fixed-key must match key filter {0:?}")]
     FixedKeyFilter(KeyFilter),
+    #[error("--key-file could not be read")]
+    UnreadableKeyFile(#[source] io::Error),
+    #[error(
+        "--key-file should contain a JSON array of keys\n\n\
+        Caused by:\n    {0}"
+    )]
+    UnparseableKeyFile(serde_json::Error),
+    #[error("--key-file must contain at least one key")]
+    EmptyKeyFile,
+    #[error("key {0} in --key-file must specify valid key support")]
+    InvalidKeyFileKey(usize, #[source] InvalidSupport),
+    #[error("key {0} in --key-file must match key filter {1:?}")]
+    KeyFileFilter(usize, KeyFilter),
+    #[error("--fail-on-dfr must be a number between 0 and 1: {0}")]
+    InvalidDfrThreshold(f64),
+    #[error("--error-source-mix must be a number between 0 and 1: {0}")]
+    InvalidErrorSourceMix(f64),
     #[error("--seed should be 256-bit hex string")]
     InvalidSeed(#[from] SeedFromHexError),
+    #[error("--rng-state should have the form <SEED>:<INDEX>")]
+    InvalidRngState(#[from] RngStateParseError),
     #[error("ncw_overlap requires ncw_class to be set")]
     NcwDependency,
     #[error("l must be in range 0..{} in A_{{t,l}}({0})", .0.max_l())]
     NcwRange(NearCodewordClass),
+    #[error(
+        "--ncw N --ncw-overlap {} is expected to fail in every trial",
+        BLOCK_WEIGHT
+    )]
+    GuaranteedFailure,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::path::PathBuf;
 
     #[test]
     fn from_args_example() {
         let args = Args {
-            number: 1.75e4,
+            command: None,
+            number: Some(1.75e4),
+            time_budget: None,
             weak_keys: -1,
             weak_key_threshold: 4,
             fixed_key: Some(
@@ -271,24 +844,48 @@ mod tests {
                 "h1":[35,108,119,159,160,163,221,246,249,286,310,360,484,559,580]}"#
                     .to_string(),
             ),
+            key_file: None,
+            key_order: KeyOrder::Random,
             ncw: Some(NearCodewordClass::C),
             ncw_overlap: Some(7),
+            error_source_mix: Some(0.25),
+            deny_guaranteed_failure: false,
+            error_weight: None,
+            iterations: Some(12),
+            tau: Some(2),
+            classify_weak: Some(4),
+            count_distinct_keys: true,
+            always_include_key: true,
+            progress: true,
             output: Some("test/path/to/file.json".to_string()),
+            output_dir: None,
+            compress: false,
+            canonical_json: true,
             overwrite: true,
             parallel: true,
+            resume: false,
             recordmax: 123.4,
+            sample_failures: true,
+            record_successes: None,
             savefreq: Some(50.0),
+            flush_every: 3,
             seed: Some(
                 "874a5940435d8a5462d8579af9f4cad2a737880dfb13620c5257a60ffaaae6cf".to_string(),
             ),
+            seed_phrase: None,
             seed_index: None,
+            rng_state: None,
             threads: Some(usize::MAX),
+            deterministic_threads: true,
+            dry_run: true,
+            fail_on_dfr: Some(1e-3),
             verbose: 2,
         };
         let mut args2 = args.clone();
         args2.savefreq = None;
         let settings = Settings::try_from(args).unwrap();
         assert_eq!(settings.num_trials, 17500);
+        assert!(settings.time_budget.is_none());
         assert_eq!(settings.trial_settings.key_filter, KeyFilter::NonWeak(4));
         assert_eq!(
             settings.trial_settings.fixed_key,
@@ -305,8 +902,15 @@ mod tests {
             Some(NearCodewordClass::C)
         );
         assert_eq!(settings.trial_settings.ncw_overlap, Some(7));
+        assert_eq!(settings.trial_settings.ncw_fraction, Some(0.25));
+        assert_eq!(settings.iterations(), 12);
+        assert_eq!(settings.tau(), 2);
         assert_eq!(settings.save_frequency(), Settings::MIN_SAVE_FREQUENCY);
+        assert_eq!(settings.flush_every, 3);
+        assert!(!settings.flushes_at(2) && settings.flushes_at(6));
         assert_eq!(settings.record_max, 123);
+        assert!(settings.sample_failures);
+        assert_eq!(settings.record_successes, 0);
         assert_eq!(settings.verbose, 2);
         assert_eq!(
             settings.seed,
@@ -317,15 +921,288 @@ mod tests {
         );
         assert!(settings.seed_index().is_none());
         assert_eq!(settings.threads, Settings::MAX_THREAD_COUNT);
+        assert!(settings.deterministic_threads);
+        assert_eq!(settings.thread_count(), Settings::MAX_THREAD_COUNT);
         assert_eq!(
             settings.output,
             OutputTo::File(PathBuf::from("test/path/to/file.json"))
         );
+        assert!(settings.canonical_json);
         assert!(settings.overwrite);
+        assert_eq!(settings.classify_weak, Some(4));
+        assert!(settings.count_distinct_keys);
+        assert!(settings.always_include_key);
+        assert!(settings.progress);
+        assert!(settings.dry_run);
+        assert_eq!(settings.fail_on_dfr, Some(1e-3));
         let settings2 = Settings::try_from(args2).unwrap();
         assert_eq!(settings2.save_frequency(), settings2.num_trials());
     }
 
+    #[test]
+    fn output_dir_arg() {
+        let seed = "874a5940435d8a5462d8579af9f4cad2a737880dfb13620c5257a60ffaaae6cf";
+        let args = Args::try_parse_from([
+            "bike-trials",
+            "-N=10",
+            "--output-dir=results",
+            "--seed",
+            seed,
+        ])
+        .unwrap();
+        let settings = Settings::try_from(args).unwrap();
+        let path = settings.output().path().unwrap();
+        assert_eq!(path.parent(), Some(Path::new("results")));
+        let file_name = path.file_name().unwrap().to_str().unwrap();
+        assert_eq!(
+            file_name,
+            format!("r{BLOCK_LENGTH}_d{BLOCK_WEIGHT}_t{ERROR_WEIGHT}_874a5940.json")
+        );
+        // Without --seed, a random seed is fixed so that it matches the file name
+        let args = Args::try_parse_from(["bike-trials", "-N=10", "--output-dir=results"]).unwrap();
+        let settings = Settings::try_from(args).unwrap();
+        let seed_hex = settings.seed().unwrap().to_string();
+        assert!(settings.output().path().unwrap().ends_with(format!(
+            "r{BLOCK_LENGTH}_d{BLOCK_WEIGHT}_t{ERROR_WEIGHT}_{}.json",
+            &seed_hex[..8]
+        )));
+        assert!(
+            Args::try_parse_from(["bike-trials", "-N=10", "--output-dir=a", "-o=b.json"]).is_err()
+        );
+    }
+
+    #[test]
+    fn compress_arg() {
+        let output = |argv: &[&str]| {
+            let args = Args::try_parse_from(argv).unwrap();
+            Settings::try_from(args).unwrap().output
+        };
+        assert_eq!(
+            output(&["bike-trials", "-N=10", "-o=out.json"]),
+            OutputTo::File(PathBuf::from("out.json"))
+        );
+        assert_eq!(
+            output(&["bike-trials", "-N=10", "-o=out.json", "--compress"]),
+            OutputTo::CompressedFile(PathBuf::from("out.json"))
+        );
+        assert_eq!(
+            output(&["bike-trials", "-N=10", "-o=out.json.zst"]),
+            OutputTo::CompressedFile(PathBuf::from("out.json.zst"))
+        );
+        assert!(Args::try_parse_from(["bike-trials", "-N=10", "--compress"]).is_err());
+    }
+
+    #[test]
+    fn rng_state_arg() {
+        let seed = "874a5940435d8a5462d8579af9f4cad2a737880dfb13620c5257a60ffaaae6cf";
+        let rng_state = format!("{seed}:42");
+        let args =
+            Args::try_parse_from(["bike-trials", "-N=10", "--rng-state", &rng_state]).unwrap();
+        let settings = Settings::try_from(args).unwrap();
+        assert_eq!(settings.seed(), Some(seed.parse().unwrap()));
+        assert_eq!(settings.seed_index(), Some(42));
+        for conflicting in [["--seed", seed], ["--seed-index", "3"], ["--threads", "2"]] {
+            let mut argv = vec!["bike-trials", "-N=10", "--rng-state", &rng_state];
+            argv.extend(conflicting);
+            assert!(Args::try_parse_from(argv).is_err());
+        }
+        let args = Args::try_parse_from(["bike-trials", "-N=10", "--rng-state", seed]).unwrap();
+        assert!(matches!(
+            Settings::try_from(args),
+            Err(SettingsError::InvalidRngState(
+                RngStateParseError::MissingIndex
+            ))
+        ));
+    }
+
+    #[test]
+    fn key_file_pool() {
+        let keys = [
+            r#"{"h0":[6,25,77,145,165,212,230,232,247,261,306,341,449,466,493],
+            "h1":[35,108,119,159,160,163,221,246,249,286,310,360,484,559,580]}"#,
+            r#"{"h0":[93,99,105,121,126,141,156,193,194,197,264,301,360,400,429],
+            "h1":[100,117,189,191,211,325,340,386,440,461,465,474,534,565,578]}"#,
+        ];
+        let path = std::env::temp_dir().join(format!(
+            "bike-trials-key-file-{}.json",
+            uuid::Uuid::new_v4()
+        ));
+        fs::write(&path, format!("[{}]", keys.join(","))).unwrap();
+        let expected: Vec<Key> = keys
+            .iter()
+            .map(|key| serde_json::from_str(key).unwrap())
+            .collect();
+        let mut rng = rand::thread_rng();
+        for order in ["random", "round-robin"] {
+            let args = Args::try_parse_from([
+                "bike-trials",
+                "-N=10",
+                "--key-file",
+                path.to_str().unwrap(),
+                "--key-order",
+                order,
+            ])
+            .unwrap();
+            let settings = Settings::try_from(args).unwrap();
+            assert_eq!(
+                settings.trial_settings().key_pool().unwrap().keys(),
+                &expected
+            );
+            let mut seen = [0; 2];
+            for _ in 0..100 {
+                let result =
+                    crate::application::decoding_trial(settings.trial_settings(), &mut rng);
+                let idx = expected.iter().position(|key| key == result.key()).unwrap();
+                seen[idx] += 1;
+            }
+            assert!(seen.iter().all(|&count| count > 0));
+            if order == "round-robin" {
+                assert_eq!(seen, [50, 50]);
+            }
+        }
+        let load = |weak_keys: &str| {
+            let args = Args::try_parse_from([
+                "bike-trials",
+                "-N=10",
+                weak_keys,
+                "--key-file",
+                path.to_str().unwrap(),
+            ])
+            .unwrap();
+            Settings::try_from(args)
+        };
+        let filtered = load("--weak-keys=2");
+        let invalid_key = keys[1].replace("429", &BLOCK_LENGTH.to_string());
+        fs::write(&path, format!("[{},{invalid_key}]", keys[0])).unwrap();
+        let invalid = load("--weak-keys=0");
+        fs::write(&path, "[]").unwrap();
+        let empty = load("--weak-keys=0");
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            filtered,
+            Err(SettingsError::KeyFileFilter(0, KeyFilter::Weak(_, 3)))
+        ));
+        assert!(matches!(
+            invalid,
+            Err(SettingsError::InvalidKeyFileKey(
+                1,
+                InvalidSupport::OutOfBounds(_)
+            ))
+        ));
+        assert!(matches!(empty, Err(SettingsError::EmptyKeyFile)));
+        assert!(matches!(
+            load("--weak-keys=0"),
+            Err(SettingsError::UnreadableKeyFile(_))
+        ));
+    }
+
+    #[test]
+    fn guaranteed_failure_check() {
+        let overlap = BLOCK_WEIGHT.to_string();
+        let args =
+            Args::try_parse_from(["bike-trials", "-N=10", "--ncw=N", "-l", &overlap]).unwrap();
+        let settings = Settings::try_from(args).unwrap();
+        assert!(settings.trial_settings().guarantees_failure());
+        let args = Args::try_parse_from([
+            "bike-trials",
+            "-N=10",
+            "--ncw=N",
+            "-l",
+            &overlap,
+            "--deny-guaranteed-failure",
+        ])
+        .unwrap();
+        assert!(matches!(
+            Settings::try_from(args),
+            Err(SettingsError::GuaranteedFailure)
+        ));
+        for ncw_args in [["--ncw=N", "-l", "1"], ["--ncw=C", "-l", &overlap]] {
+            let args = Args::try_parse_from(
+                ["bike-trials", "-N=10", "--deny-guaranteed-failure"]
+                    .into_iter()
+                    .chain(ncw_args),
+            )
+            .unwrap();
+            let settings = Settings::try_from(args).unwrap();
+            assert!(!settings.trial_settings().guarantees_failure());
+        }
+    }
+
+    #[test]
+    fn time_budget_arg() {
+        let args = Args::try_parse_from(["bike-trials", "--time-budget", "1.5"]).unwrap();
+        let settings = Settings::try_from(args).unwrap();
+        assert_eq!(settings.time_budget(), Some(Duration::from_millis(1500)));
+        assert_eq!(settings.num_trials(), u64::MAX);
+        assert_eq!(settings.save_frequency(), Settings::MIN_SAVE_FREQUENCY);
+        let args = Args::try_parse_from(["bike-trials", "-N=100", "--time-budget=2"]).unwrap();
+        assert_eq!(Settings::try_from(args).unwrap().num_trials(), u64::MAX);
+        let args = Args::try_parse_from(["bike-trials", "--time-budget=-1"]).unwrap();
+        assert!(matches!(
+            Settings::try_from(args),
+            Err(SettingsError::InvalidTimeBudget(_))
+        ));
+        assert!(Args::try_parse_from(["bike-trials"]).is_err());
+    }
+
+    #[test]
+    fn dry_run_invalid_fixed_key() {
+        let fixed_key = r#"{"h0":[6,25,77,145,165,212,230,232,247,261,306,341,449,466,493],
+            "h1":[35,108,119,159,160,163,221,246,249,286,310,360,484,559,580]}"#;
+        let args = Args::try_parse_from([
+            "bike-trials",
+            "-N=10",
+            "--dry-run",
+            "--weak-keys=2",
+            "--fixed-key",
+            fixed_key,
+        ])
+        .unwrap();
+        assert!(matches!(
+            Settings::try_from(args),
+            Err(SettingsError::FixedKeyFilter(KeyFilter::Weak(_, 3)))
+        ));
+        let args = Args::try_parse_from([
+            "bike-trials",
+            "-N=10",
+            "--dry-run",
+            "--fixed-key",
+            fixed_key,
+        ])
+        .unwrap();
+        let settings = Settings::try_from(args).unwrap();
+        assert!(settings.dry_run());
+    }
+
+    #[test]
+    fn parse_subcommand() {
+        let args = Args::try_parse_from(["bike-trials", "bench-upc", "-N", "1e3"]).unwrap();
+        assert_eq!(args.command(), Some(&Command::BenchUpc { number: 1e3 }));
+        assert!(Args::try_parse_from(["bike-trials", "bench-upc", "-N=1e3", "-p"]).is_err());
+        assert!(Args::try_parse_from(["bike-trials", "-p"]).is_err());
+        let args = Args::try_parse_from(["bike-trials", "-N=1e3"]).unwrap();
+        assert!(args.command().is_none());
+        let args = Args::try_parse_from(["bike-trials", "print-schema"]).unwrap();
+        assert_eq!(args.command(), Some(&Command::PrintSchema));
+        let args = Args::try_parse_from(["bike-trials", "aggregate", "a.json", "b.json"]).unwrap();
+        assert_eq!(
+            args.command(),
+            Some(&Command::Aggregate {
+                paths: vec!["a.json".to_string(), "b.json".to_string()]
+            })
+        );
+        assert!(Args::try_parse_from(["bike-trials", "aggregate"]).is_err());
+        let args = Args::try_parse_from(["bike-trials", "dump-thresholds", "-r", "1000"]).unwrap();
+        assert_eq!(
+            args.command(),
+            Some(&Command::DumpThresholds {
+                r: 1000,
+                d: BLOCK_WEIGHT,
+                t: ERROR_WEIGHT
+            })
+        );
+    }
+
     #[test]
     fn settings_builder() {
         let settings = SettingsBuilder::default()
@@ -337,17 +1214,33 @@ mod tests {
             settings,
             Settings {
                 num_trials: 12345,
+                time_budget: None,
                 trial_settings: TrialSettings::default(),
                 save_frequency: None,
+                flush_every: 1,
                 record_max: 10000,
+                sample_failures: false,
+                record_successes: 0,
                 verbose: 0,
                 seed: None,
                 seed_index: None,
                 threads: 1,
+                deterministic_threads: false,
                 output: OutputTo::Void,
+                canonical_json: false,
                 overwrite: false,
+                resume: false,
+                classify_weak: None,
+                count_distinct_keys: false,
+                always_include_key: false,
+                progress: false,
+                dry_run: false,
+                fail_on_dfr: None,
             }
         );
         assert_eq!(settings.save_frequency(), settings.num_trials());
+        assert_eq!(settings.iterations(), NB_ITER);
+        assert_eq!(settings.error_weight(), ERROR_WEIGHT);
+        assert_eq!(settings.tau(), GRAY_THRESHOLD_DIFF);
     }
 }
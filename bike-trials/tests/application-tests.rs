@@ -1,11 +1,22 @@
 use bike_decoder::{
-    keys::Key,
-    ncw::ErrorVectorSource,
+    keys::{Key, KeyFilter},
+    ncw::{ErrorVectorSource, NearCodewordClass},
+    parameters::*,
     random::{self, Seed},
     vectors::SparseErrorVector,
 };
-use bike_trials::{application, output::OutputTo, parallel, settings::SettingsBuilder};
+use bike_trials::{
+    application,
+    output::{OutputTo, SnapshotWriter},
+    parallel,
+    record::{self, data_record_schema, DataRecord, OutputWeights},
+    settings::{Settings, SettingsBuilder, TrialSettings},
+};
 use hex::FromHex;
+use std::{
+    fs,
+    time::{Duration, Instant},
+};
 
 #[test]
 fn main_single_threaded_test() {
@@ -44,6 +55,387 @@ fn main_single_threaded_test() {
     assert_eq!(*e_supp.source(), ErrorVectorSource::Random);
 }
 
+#[test]
+fn iterations_override() {
+    let seed =
+        Seed::from_hex("9d1c7a20f8c5e3b4a6d2f0e1b3c5a7d9e2f4b6c8a0d1e3f5a7b9c2d4e6f8a0b1").unwrap();
+    let settings_with_iters = |iterations: Option<usize>| {
+        SettingsBuilder::default()
+            .num_trials(1000)
+            .trial_settings(TrialSettings::default().with_iterations(iterations))
+            .output(OutputTo::Void)
+            .seed(Some(seed))
+            .seed_index(Some(0))
+            .build()
+            .unwrap()
+    };
+    let data_default = application::run(&settings_with_iters(None)).unwrap();
+    let data_one = application::run(&settings_with_iters(Some(1))).unwrap();
+    assert_eq!(data_default.iterations(), NB_ITER);
+    assert_eq!(data_one.iterations(), 1);
+    // Decoding stops once the syndrome is zero, so fewer iterations can only add failures
+    assert!(data_one.num_failures() > data_default.num_failures());
+}
+
+#[test]
+fn fail_on_dfr_threshold() {
+    let seed =
+        Seed::from_hex("9d1c7a20f8c5e3b4a6d2f0e1b3c5a7d9e2f4b6c8a0d1e3f5a7b9c2d4e6f8a0b1").unwrap();
+    let settings_with_threshold = |threshold: Option<f64>| {
+        SettingsBuilder::default()
+            .num_trials(1000)
+            .trial_settings(TrialSettings::default().with_iterations(Some(1)))
+            .output(OutputTo::Void)
+            .seed(Some(seed))
+            .seed_index(Some(0))
+            .fail_on_dfr(threshold)
+            .build()
+            .unwrap()
+    };
+    let settings = settings_with_threshold(None);
+    let data = application::run(&settings).unwrap();
+    let dfr = data.decoding_failure_ratio().as_f64();
+    assert!(dfr > 0.0 && dfr < 1.0);
+    assert!(!application::dfr_exceeds_threshold(&settings, &data));
+    for (threshold, exceeded) in [(0.0, true), (dfr / 2.0, true), (dfr, false), (1.0, false)] {
+        let settings = settings_with_threshold(Some(threshold));
+        let data = application::run(&settings).unwrap();
+        assert_eq!(
+            application::dfr_exceeds_threshold(&settings, &data),
+            exceeded
+        );
+    }
+}
+
+#[test]
+fn weighted_trial_matches_standard() {
+    let seed =
+        Seed::from_hex("c4e2a0f8d6b4927e5c3a1f0d8b6e4c2a09f7d5b3e1c8a6f4d2b0e9c7a5f3d1b2").unwrap();
+    // With one iteration, enough trials fail to compare both outcomes
+    let settings = TrialSettings::default().with_iterations(Some(1));
+    let mut rng = random::get_rng_from_seed(seed, 0);
+    let mut rng_weighted = rng.clone();
+    let mut failures = 0;
+    for _ in 0..1000 {
+        let result = application::decoding_trial(&settings, &mut rng);
+        let result_weighted =
+            application::decoding_trial_weighted(&settings, ERROR_WEIGHT, &mut rng_weighted);
+        assert_eq!(result.key(), result_weighted.key());
+        assert_eq!(
+            result.vector().vector().support(),
+            result_weighted.e_supp().support()
+        );
+        assert_eq!(result.success(), result_weighted.success());
+        failures += u32::from(!result.success());
+    }
+    assert!(failures > 0);
+}
+
+#[test]
+fn error_weight_override() {
+    let seed =
+        Seed::from_hex("7b2d9f4e1a6c3085d7e2b9f4a1c6d3e8b5f2a7c4d9e1b6f3a8c5d2e7b4f9a1c6").unwrap();
+    let settings_with_weight = |error_weight: Option<usize>| {
+        SettingsBuilder::default()
+            .num_trials(200)
+            .trial_settings(TrialSettings::default().with_error_weight(error_weight))
+            .output(OutputTo::Void)
+            .seed(Some(seed))
+            .seed_index(Some(0))
+            .build()
+            .unwrap()
+    };
+    let data_low = application::run(&settings_with_weight(Some(5))).unwrap();
+    assert_eq!(data_low.t(), 5);
+    assert_eq!(data_low.num_failures(), 0);
+    // Far above the error correction capacity, every trial fails, but failures with
+    // nonstandard error weights aren't recorded
+    let data_high = application::run(&settings_with_weight(Some(120))).unwrap();
+    assert_eq!(data_high.t(), 120);
+    assert_eq!(data_high.num_failures(), 200);
+    assert!(data_high.decoding_failures().is_empty());
+    // They're still counted as seen and added to the failure weight histograms
+    assert_eq!(data_high.failures_seen(), 200);
+}
+
+#[test]
+fn tau_override() {
+    let seed =
+        Seed::from_hex("3e6f1a9c2b7d4e8f0a5c1b3d7e9f2a4c6b8d0e1f3a5c7b9d2e4f6a8c0b1d3e5f").unwrap();
+    let settings_with_tau = |tau: Option<u8>| {
+        SettingsBuilder::default()
+            .num_trials(1000)
+            .trial_settings(TrialSettings::default().with_tau(tau))
+            .output(OutputTo::Void)
+            .seed(Some(seed))
+            .seed_index(Some(0))
+            .build()
+            .unwrap()
+    };
+    let data_default = application::run(&settings_with_tau(None)).unwrap();
+    let data_explicit = application::run(&settings_with_tau(Some(GRAY_THRESHOLD_DIFF))).unwrap();
+    let data_zero = application::run(&settings_with_tau(Some(0))).unwrap();
+    assert_eq!(data_default.gray_threshold_diff(), GRAY_THRESHOLD_DIFF);
+    assert_eq!(data_zero.gray_threshold_diff(), 0);
+    assert_eq!(data_default.num_failures(), data_explicit.num_failures());
+    assert_eq!(data_zero.num_trials(), 1000);
+    let trial_settings = TrialSettings::default().with_tau(Some(0));
+    assert!(data_zero.matches_settings(&trial_settings));
+    assert!(!data_default.matches_settings(&trial_settings));
+}
+
+#[test]
+fn count_distinct_keys() {
+    let key = Key::from_support(
+        [
+            78, 96, 136, 206, 231, 307, 378, 443, 475, 493, 498, 512, 535, 560, 576,
+        ],
+        [
+            14, 41, 88, 94, 112, 117, 168, 186, 215, 251, 354, 366, 493, 560, 563,
+        ],
+    )
+    .unwrap();
+    let settings_with_key = |fixed_key: Option<Key>| {
+        SettingsBuilder::default()
+            .num_trials(100)
+            .trial_settings(
+                TrialSettings::new(
+                    KeyFilter::Any,
+                    fixed_key,
+                    Some(NearCodewordClass::N),
+                    Some(BLOCK_WEIGHT),
+                )
+                .unwrap(),
+            )
+            .output(OutputTo::Void)
+            .count_distinct_keys(true)
+            .build()
+            .unwrap()
+    };
+    let data = application::run(&settings_with_key(Some(key))).unwrap();
+    assert_eq!(data.num_failures(), 100);
+    assert_eq!(data.distinct_failure_keys(), Some(1));
+    let data = application::run(&settings_with_key(None)).unwrap();
+    assert_eq!(data.num_failures(), 100);
+    assert_eq!(data.distinct_failure_keys(), Some(100));
+    let json = serde_json::to_value(&data).unwrap();
+    assert_eq!(json["distinct_failure_keys"], 100);
+}
+
+#[test]
+fn ncw_importance_weighting() {
+    let key = Key::from_support(
+        [
+            78, 96, 136, 206, 231, 307, 378, 443, 475, 493, 498, 512, 535, 560, 576,
+        ],
+        [
+            14, 41, 88, 94, 112, 117, 168, 186, 215, 251, 354, 366, 493, 560, 563,
+        ],
+    )
+    .unwrap();
+    let settings_with_ncw = |ncw_class: Option<NearCodewordClass>,
+                             l: Option<usize>,
+                             fixed_key: Option<Key>| {
+        SettingsBuilder::default()
+            .num_trials(20)
+            .trial_settings(TrialSettings::new(KeyFilter::Any, fixed_key, ncw_class, l).unwrap())
+            .output(OutputTo::Void)
+            .build()
+            .unwrap()
+    };
+    let data = application::run(&settings_with_ncw(
+        Some(NearCodewordClass::N),
+        Some(BLOCK_WEIGHT),
+        Some(key.clone()),
+    ))
+    .unwrap();
+    let weight = data.ncw_sampling_weight().unwrap();
+    assert!(0.0 < weight && weight < 1.0);
+    assert_eq!(
+        data.importance_weighted_dfr(),
+        Some(data.decoding_failure_ratio().as_f64() * weight)
+    );
+    let json = serde_json::to_value(&data).unwrap();
+    assert_eq!(json["ncw_sampling_weight"], weight);
+    // Without a fixed l and key, for class 2N, or if the near-codeword sets for distinct
+    // near-codewords can intersect, the weight isn't known
+    for (ncw_class, l, fixed_key) in [
+        (None, None, None),
+        (Some(NearCodewordClass::C), None, Some(key.clone())),
+        (Some(NearCodewordClass::N), Some(BLOCK_WEIGHT), None),
+        (Some(NearCodewordClass::N), Some(2), Some(key.clone())),
+        (Some(NearCodewordClass::TwoN), Some(5), Some(key.clone())),
+    ] {
+        let data = application::run(&settings_with_ncw(ncw_class, l, fixed_key)).unwrap();
+        assert!(data.importance_weighted_dfr().is_none());
+        let json = serde_json::to_value(&data).unwrap();
+        assert!(json.get("ncw_sampling_weight").is_none());
+    }
+    for ncw_class in [NearCodewordClass::C, NearCodewordClass::N] {
+        for l in 0..=ERROR_WEIGHT {
+            let Ok(trial_settings) =
+                TrialSettings::new(KeyFilter::Any, Some(key.clone()), Some(ncw_class), Some(l))
+            else {
+                continue;
+            };
+            if let Some(weight) = record::ncw_sampling_weight(&trial_settings) {
+                assert!((0.0..=1.0).contains(&weight), "{ncw_class}, l = {l}");
+            }
+        }
+    }
+}
+
+#[test]
+fn classify_weak_failures() {
+    let threshold = 4;
+    let key = Key::random_weak_type3(threshold, &mut rand::thread_rng());
+    let settings_classify = |classify_weak: Option<u8>| {
+        SettingsBuilder::default()
+            .num_trials(10)
+            .trial_settings(
+                TrialSettings::new(
+                    KeyFilter::Any,
+                    Some(key.clone()),
+                    Some(NearCodewordClass::N),
+                    Some(BLOCK_WEIGHT),
+                )
+                .unwrap(),
+            )
+            .output(OutputTo::Void)
+            .classify_weak(classify_weak)
+            .build()
+            .unwrap()
+    };
+    let data = application::run(&settings_classify(Some(threshold))).unwrap();
+    assert_eq!(data.decoding_failures().len(), 10);
+    for df in data.decoding_failures() {
+        let weak_key = df.weak_key.expect("Recorded failures should be classified");
+        assert_eq!(weak_key.threshold, threshold);
+        assert!(weak_key.type3);
+        assert_eq!(weak_key.type2, key.is_weak_type2(threshold));
+    }
+    let json = serde_json::to_value(&data).unwrap();
+    assert_eq!(json["decoding_failures"][0]["weak_key"]["type3"], true);
+    let data = application::run(&settings_classify(None)).unwrap();
+    assert!(data
+        .decoding_failures()
+        .iter()
+        .all(|df| df.weak_key.is_none()));
+    let json = serde_json::to_value(&data).unwrap();
+    assert!(json["decoding_failures"][0].get("weak_key").is_none());
+}
+
+#[test]
+fn sample_failures_uniform() {
+    const FAILURES: usize = 10;
+    const RECORD_MAX: usize = 2;
+    const RUNS: usize = 2000;
+    let source_settings = SettingsBuilder::default()
+        .num_trials(FAILURES as u64)
+        .trial_settings(
+            TrialSettings::new(
+                KeyFilter::Any,
+                None,
+                Some(NearCodewordClass::N),
+                Some(BLOCK_WEIGHT),
+            )
+            .unwrap(),
+        )
+        .output(OutputTo::Void)
+        .build()
+        .unwrap();
+    let failures = application::run(&source_settings)
+        .unwrap()
+        .decoding_failures()
+        .clone();
+    assert_eq!(failures.len(), FAILURES);
+    let weights: Vec<_> = failures
+        .iter()
+        .map(|df| OutputWeights::recompute(df, NB_ITER, GRAY_THRESHOLD_DIFF))
+        .collect();
+    let settings = SettingsBuilder::default()
+        .num_trials(FAILURES as u64)
+        .record_max(RECORD_MAX)
+        .sample_failures(true)
+        .output(OutputTo::Void)
+        .build()
+        .unwrap();
+    let mut rng = rand::thread_rng();
+    let mut retained = [0usize; FAILURES];
+    for _ in 0..RUNS {
+        let mut data = DataRecord::new(KeyFilter::Any, None, Seed::from_entropy());
+        for (i, (df, &weights)) in failures.iter().zip(&weights).enumerate() {
+            let mut df = df.clone();
+            df.thread = Some(i as u32);
+            application::handle_decoding_failure(df, weights, &mut data, &settings, &mut rng);
+        }
+        assert_eq!(data.decoding_failures().len(), RECORD_MAX);
+        for df in data.decoding_failures() {
+            retained[df.thread.unwrap() as usize] += 1;
+        }
+    }
+    // Each failure is retained with probability RECORD_MAX / FAILURES, so the expected
+    // count is 400 with standard deviation about 18.
+    let expected = RUNS * RECORD_MAX / FAILURES;
+    for count in retained {
+        assert!(count.abs_diff(expected) < 100, "{retained:?}");
+    }
+}
+
+#[test]
+fn record_successes() {
+    let seed =
+        Seed::from_hex("0f8e2d6c4b1a3958e7d6c5b4a3928170f6e5d4c3b2a1908f7e6d5c4b3a291807").unwrap();
+    let settings_with_successes = |record_successes: usize| {
+        SettingsBuilder::default()
+            .num_trials(100)
+            .output(OutputTo::Void)
+            .seed(Some(seed))
+            .seed_index(Some(0))
+            .record_successes(record_successes)
+            .build()
+            .unwrap()
+    };
+    // With this seed, all 100 trials decode successfully
+    let data = application::run(&settings_with_successes(20)).unwrap();
+    assert_eq!(data.num_failures(), 0);
+    assert_eq!(data.successes().len(), 20);
+    assert!(data.successes().iter().all(|result| result.success()));
+    let data_all = application::run(&settings_with_successes(1000)).unwrap();
+    assert_eq!(data_all.successes().len(), 100);
+    assert_eq!(
+        serde_json::to_value(data.successes()).unwrap(),
+        serde_json::to_value(&data_all.successes()[..20]).unwrap()
+    );
+    let data = application::run(&settings_with_successes(0)).unwrap();
+    assert!(data.successes().is_empty());
+    let json = serde_json::to_value(&data).unwrap();
+    assert!(json.get("successes").is_none());
+}
+
+#[test]
+fn time_budget() {
+    let budget = Duration::from_millis(50);
+    for threads in [1, 2] {
+        let settings = SettingsBuilder::default()
+            .num_trials(u64::MAX)
+            .time_budget(Some(budget))
+            .threads(threads)
+            .output(OutputTo::Void)
+            .build()
+            .unwrap();
+        let start_time = Instant::now();
+        let data = if settings.parallel() {
+            parallel::run_parallel(&settings).unwrap()
+        } else {
+            application::run(&settings).unwrap()
+        };
+        assert!(start_time.elapsed() < Duration::from_secs(10));
+        assert!(data.num_trials() > 0);
+        assert!(data.runtime() >= budget);
+    }
+}
+
 #[test]
 fn parallel_fail_if_seed_fail() {
     let settings = SettingsBuilder::default()
@@ -58,3 +450,332 @@ fn parallel_fail_if_seed_fail() {
         .unwrap_err()
         .is::<random::TryInsertGlobalSeedError>());
 }
+
+#[test]
+fn resume_from_file() {
+    let path =
+        std::env::temp_dir().join(format!("bike-trials-resume-{}.json", uuid::Uuid::new_v4()));
+    let resume_settings = |num_trials: u64, output: OutputTo, resume: bool| -> Settings {
+        let seed =
+            Seed::from_hex("3bb6a3c4d4b2e6f2a1b7b0a3ffa0f26d3c1e5c4b7b1a44d7e5f7c3a2b1e0d9c8")
+                .unwrap();
+        SettingsBuilder::default()
+            .num_trials(num_trials)
+            .trial_settings(
+                TrialSettings::new(
+                    KeyFilter::Any,
+                    None,
+                    Some(NearCodewordClass::N),
+                    Some(BLOCK_WEIGHT),
+                )
+                .unwrap(),
+            )
+            .output(output)
+            .overwrite(true)
+            .seed(Some(seed))
+            .seed_index(Some(0))
+            .resume(resume)
+            .build()
+            .unwrap()
+    };
+    application::run(&resume_settings(500, OutputTo::File(path.clone()), false)).unwrap();
+    assert_eq!(DataRecord::load(&path).unwrap().num_trials(), 500);
+    let resumed = application::run(&resume_settings(1000, OutputTo::File(path.clone()), true));
+    let saved = DataRecord::load(&path);
+    fs::remove_file(&path).unwrap();
+    let (resumed, saved) = (resumed.unwrap(), saved.unwrap());
+    assert_eq!(resumed.num_trials(), 1000);
+    assert_eq!(saved.num_trials(), 1000);
+    // Resumed run should reproduce the results of an uninterrupted run
+    let uninterrupted = application::run(&resume_settings(1000, OutputTo::Void, false)).unwrap();
+    assert_eq!(resumed.num_failures(), uninterrupted.num_failures());
+    assert_eq!(
+        serde_json::to_value(resumed.decoding_failures()).unwrap(),
+        serde_json::to_value(uninterrupted.decoding_failures()).unwrap()
+    );
+}
+
+#[test]
+fn compressed_output_roundtrip() {
+    let path = std::env::temp_dir().join(format!(
+        "bike-trials-compressed-{}.json.zst",
+        uuid::Uuid::new_v4()
+    ));
+    let settings_with_trials = |num_trials: u64, resume: bool| {
+        SettingsBuilder::default()
+            .num_trials(num_trials)
+            .trial_settings(
+                TrialSettings::new(
+                    KeyFilter::Any,
+                    None,
+                    Some(NearCodewordClass::N),
+                    Some(BLOCK_WEIGHT),
+                )
+                .unwrap(),
+            )
+            .output(OutputTo::CompressedFile(path.clone()))
+            .overwrite(true)
+            .resume(resume)
+            .build()
+            .unwrap()
+    };
+    let data = application::run(&settings_with_trials(20, false));
+    let contents = fs::read(&path);
+    let loaded = DataRecord::load(&path);
+    let resumed = application::run(&settings_with_trials(40, true));
+    fs::remove_file(&path).unwrap();
+    let (data, contents, loaded) = (data.unwrap(), contents.unwrap(), loaded.unwrap());
+    assert!(contents.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]));
+    assert_eq!(
+        serde_json::to_value(&loaded).unwrap(),
+        serde_json::to_value(&data).unwrap()
+    );
+    assert_eq!(resumed.unwrap().num_failures(), 40);
+}
+
+#[test]
+fn flush_every_writes_final_results() {
+    for (parallel, flush_every, compressed) in [
+        (false, 1, false),
+        (false, 4, false),
+        (false, 1000, true),
+        (true, 1, true),
+        (true, 4, false),
+        (true, 1000, false),
+    ] {
+        let path =
+            std::env::temp_dir().join(format!("bike-trials-flush-{}.json", uuid::Uuid::new_v4()));
+        let output = if compressed {
+            OutputTo::CompressedFile(path.clone())
+        } else {
+            OutputTo::File(path.clone())
+        };
+        let settings = SettingsBuilder::default()
+            .num_trials(1000)
+            .trial_settings(TrialSettings::default().with_iterations(Some(1)))
+            .save_frequency(std::num::NonZeroU64::new(90))
+            .flush_every(flush_every)
+            .threads(if parallel { 2 } else { 1 })
+            .output(output)
+            .overwrite(true)
+            .build()
+            .unwrap();
+        let data = if parallel {
+            parallel::run_parallel(&settings)
+        } else {
+            application::run(&settings)
+        };
+        let saved = DataRecord::load(&path);
+        fs::remove_file(&path).unwrap();
+        let (data, saved) = (data.unwrap(), saved.unwrap());
+        assert_eq!(saved.num_trials(), settings.num_trials());
+        assert!(data.num_failures() > 0);
+        assert_eq!(saved.num_failures(), data.num_failures());
+        assert_eq!(
+            serde_json::to_value(saved.decoding_failures()).unwrap(),
+            serde_json::to_value(data.decoding_failures()).unwrap()
+        );
+    }
+}
+
+#[test]
+fn snapshot_writer_replaces_contents() {
+    let path = std::env::temp_dir().join(format!(
+        "bike-trials-snapshot-{}.json",
+        uuid::Uuid::new_v4()
+    ));
+    fs::write(
+        &path,
+        "previous contents that are longer than the snapshots",
+    )
+    .unwrap();
+    let mut writer = SnapshotWriter::open(&OutputTo::File(path.clone())).unwrap();
+    let untouched = fs::read_to_string(&path);
+    writer.write_json(&[1, 2, 3]).unwrap();
+    writer.write_json(&[4]).unwrap();
+    let unflushed = fs::read_to_string(&path);
+    writer.flush().unwrap();
+    let flushed = fs::read_to_string(&path);
+    writer.write_json(&[5, 6]).unwrap();
+    writer.flush().unwrap();
+    drop(writer);
+    let rewritten = fs::read_to_string(&path);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(unflushed.unwrap(), untouched.unwrap());
+    assert_eq!(flushed.unwrap(), "[4]\n");
+    assert_eq!(rewritten.unwrap(), "[5,6]\n");
+}
+
+#[test]
+fn fixed_key_omitted_from_failures() {
+    let key = Key::from_support(
+        [
+            6, 25, 77, 145, 165, 212, 230, 232, 247, 261, 306, 341, 449, 466, 493,
+        ],
+        [
+            35, 108, 119, 159, 160, 163, 221, 246, 249, 286, 310, 360, 484, 559, 580,
+        ],
+    )
+    .unwrap();
+    for always_include_key in [false, true] {
+        let path = std::env::temp_dir().join(format!(
+            "bike-trials-fixed-key-{}.json",
+            uuid::Uuid::new_v4()
+        ));
+        let settings = SettingsBuilder::default()
+            .num_trials(20)
+            .trial_settings(
+                TrialSettings::new(
+                    KeyFilter::Any,
+                    Some(key.clone()),
+                    Some(NearCodewordClass::N),
+                    Some(BLOCK_WEIGHT),
+                )
+                .unwrap(),
+            )
+            .always_include_key(always_include_key)
+            .output(OutputTo::File(path.clone()))
+            .overwrite(true)
+            .build()
+            .unwrap();
+        let data = application::run(&settings);
+        let contents = fs::read_to_string(&path);
+        let loaded = DataRecord::load(&path);
+        fs::remove_file(&path).unwrap();
+        let (data, contents, loaded) = (data.unwrap(), contents.unwrap(), loaded.unwrap());
+        let json: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        let failures = json["decoding_failures"].as_array().unwrap();
+        assert_eq!(failures.len(), 20);
+        assert!(failures
+            .iter()
+            .all(|df| df.get("h0").is_some() == always_include_key));
+        assert_eq!(loaded.decoding_failures().len(), 20);
+        assert!(loaded.decoding_failures().iter().all(|df| df.key() == &key));
+        assert_eq!(
+            serde_json::to_value(loaded.decoding_failures()).unwrap(),
+            serde_json::to_value(data.decoding_failures()).unwrap()
+        );
+    }
+}
+
+#[test]
+fn canonical_json_output() {
+    let seed =
+        Seed::from_hex("9e0d4c6b2f8a31e5d7c9b1a3f5e7092b4d6f8a1c3e5b7d9f0a2c4e6b8d0f1a3c").unwrap();
+    let run_once = || {
+        let path = std::env::temp_dir().join(format!(
+            "bike-trials-canonical-{}.json",
+            uuid::Uuid::new_v4()
+        ));
+        let settings = SettingsBuilder::default()
+            .num_trials(200)
+            .trial_settings(TrialSettings::default().with_iterations(Some(1)))
+            .seed(Some(seed))
+            .seed_index(Some(0))
+            .canonical_json(true)
+            .output(OutputTo::File(path.clone()))
+            .overwrite(true)
+            .build()
+            .unwrap();
+        let data = application::run(&settings);
+        let contents = fs::read_to_string(&path);
+        fs::remove_file(&path).unwrap();
+        data.unwrap();
+        contents.unwrap()
+    };
+    let (contents, contents2) = (run_once(), run_once());
+    let mut value: serde_json::Value = serde_json::from_str(&contents).unwrap();
+    let mut value2: serde_json::Value = serde_json::from_str(&contents2).unwrap();
+    // Keys are sorted and there is no insignificant whitespace
+    assert_eq!(contents, format!("{value}\n"));
+    let keys: Vec<_> = value.as_object().unwrap().keys().collect();
+    assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(contents.find("\"decoding_failures\"") < contents.find("\"r\""));
+    // Apart from the runtime, the output of both runs is identical
+    let runtime_pattern = |value: &serde_json::Value| format!("\"runtime\":{}", value["runtime"]);
+    assert_eq!(
+        contents.replace(&runtime_pattern(&value), ""),
+        contents2.replace(&runtime_pattern(&value2), "")
+    );
+    value.as_object_mut().unwrap().remove("runtime");
+    value2.as_object_mut().unwrap().remove("runtime");
+    assert_eq!(value, value2);
+}
+
+#[test]
+fn dry_run_leaves_output_untouched() {
+    let path =
+        std::env::temp_dir().join(format!("bike-trials-dry-run-{}.json", uuid::Uuid::new_v4()));
+    let settings = SettingsBuilder::default()
+        .num_trials(1000)
+        .output(OutputTo::File(path.clone()))
+        .dry_run(true)
+        .build()
+        .unwrap();
+    let message = application::dry_run(&settings).unwrap();
+    assert!(message.starts_with("Starting decoding trials (N = 1000)"));
+    assert!(!path.exists());
+    fs::write(&path, "existing contents").unwrap();
+    application::dry_run(&settings).unwrap();
+    let contents = fs::read_to_string(&path);
+    let resume_settings = SettingsBuilder::default()
+        .num_trials(1000)
+        .output(OutputTo::File(path.clone()))
+        .resume(true)
+        .dry_run(true)
+        .build()
+        .unwrap();
+    let resume_result = application::dry_run(&resume_settings);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(contents.unwrap(), "existing contents");
+    assert!(resume_result.is_err());
+}
+
+#[test]
+fn data_record_matches_schema() {
+    let schema = jsonschema::JSONSchema::compile(&data_record_schema()).unwrap();
+    let key = Key::from_support(
+        [
+            78, 96, 136, 206, 231, 307, 378, 443, 475, 493, 498, 512, 535, 560, 576,
+        ],
+        [
+            14, 41, 88, 94, 112, 117, 168, 186, 215, 251, 354, 366, 493, 560, 563,
+        ],
+    )
+    .unwrap();
+    let settings = SettingsBuilder::default()
+        .num_trials(10)
+        .trial_settings(
+            TrialSettings::new(
+                KeyFilter::Any,
+                Some(key),
+                Some(NearCodewordClass::N),
+                Some(BLOCK_WEIGHT),
+            )
+            .unwrap(),
+        )
+        .output(OutputTo::Void)
+        .count_distinct_keys(true)
+        .build()
+        .unwrap();
+    let data = application::run(&settings).unwrap();
+    assert_eq!(data.num_failures(), 10);
+    let value = serde_json::to_value(&data).unwrap();
+    if let Err(errors) = schema.validate(&value) {
+        let messages: Vec<_> = errors.map(|err| err.to_string()).collect();
+        panic!("DataRecord didn't match schema: {messages:?}");
+    }
+    let settings = SettingsBuilder::default()
+        .num_trials(10)
+        .trial_settings(TrialSettings::new(KeyFilter::NonWeak(3), None, None, None).unwrap())
+        .output(OutputTo::Void)
+        .record_successes(5)
+        .build()
+        .unwrap();
+    let value = serde_json::to_value(application::run(&settings).unwrap()).unwrap();
+    assert_eq!(value["successes"].as_array().map(Vec::len), Some(5));
+    assert!(schema.is_valid(&value));
+    let mut invalid = value;
+    invalid["seed"] = "not a seed".into();
+    assert!(!schema.is_valid(&invalid));
+}
@@ -1,10 +1,14 @@
 use bike_decoder::{keys::KeyFilter, ncw::NearCodewordClass, parameters::*, random};
 use bike_trials::{
+    output::OutputTo,
     parallel,
     settings::{SettingsBuilder, TrialSettings},
 };
 use crossbeam_channel::unbounded as channel;
-use std::time::Duration;
+use std::{
+    num::NonZeroU64,
+    time::{Duration, Instant},
+};
 
 fn guaranteed_failure_settings() -> TrialSettings {
     TrialSettings::new(
@@ -21,11 +25,15 @@ fn receive_decoding_failure() {
     let settings = guaranteed_failure_settings();
     let (tx, rx) = channel();
     let mut rng = random::custom_thread_rng();
-    parallel::trial_iteration(&settings, &tx, &mut rng);
+    let counters = parallel::TrialCounters::unlimited();
+    parallel::trial_iteration(&settings, &counters, &tx, &mut rng);
     let result = rx
         .recv_timeout(Duration::from_secs(1))
         .expect("Should receive decoding failure in under 1 second");
-    assert_eq!(result.thread.unwrap(), random::current_thread_id());
+    let df = result
+        .failure
+        .expect("Decoding failure should be included in report");
+    assert_eq!(df.thread.unwrap(), random::current_thread_id());
 }
 
 #[test]
@@ -46,6 +54,7 @@ fn receive_progress_message() {
             settings.trial_settings(),
             settings.num_trials(),
             settings.save_frequency(),
+            &parallel::TrialCounters::for_settings(&settings),
             &tx_results,
             &tx_progress,
         )
@@ -57,3 +66,193 @@ fn receive_progress_message() {
     assert_eq!(dfr.num_failures(), 0);
     assert_eq!(dfr.num_trials(), 10);
 }
+
+#[test]
+fn progress_totals_sum_to_num_trials() {
+    let settings = SettingsBuilder::default()
+        .num_trials(1000)
+        .save_frequency(NonZeroU64::new(300))
+        .threads(2)
+        .output(OutputTo::Void)
+        .progress(true)
+        .build()
+        .unwrap();
+    let (tx_results, rx_results) = channel();
+    let (tx_progress, rx_progress) = channel();
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(settings.threads())
+        .build()
+        .unwrap();
+    pool.install(|| {
+        parallel::trial_loop(
+            settings.trial_settings(),
+            settings.num_trials(),
+            settings.save_frequency(),
+            &parallel::TrialCounters::for_settings(&settings),
+            &tx_results,
+            &tx_progress,
+        )
+        .unwrap()
+    });
+    drop(tx_results);
+    drop(tx_progress);
+    let updates: Vec<_> = rx_progress.iter().collect();
+    assert_eq!(updates.len(), 4);
+    let total: u64 = updates.iter().map(|dfr| dfr.num_trials()).sum();
+    assert_eq!(total, settings.num_trials());
+    let (tx_progress, rx_progress) = channel();
+    for dfr in updates {
+        tx_progress.send(dfr).unwrap();
+    }
+    drop(tx_progress);
+    let data =
+        parallel::record_trial_results(&settings, rx_results, rx_progress, Instant::now()).unwrap();
+    assert_eq!(data.num_trials(), settings.num_trials());
+}
+
+#[test]
+fn atomic_counters_match_results() {
+    let settings = guaranteed_failure_settings();
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(4)
+        .build()
+        .unwrap();
+    for (send_max, expected_sent) in [(u64::MAX, 40), (25, 25)] {
+        let counters = parallel::TrialCounters::new(send_max);
+        let (tx_results, rx_results) = channel();
+        let (tx_progress, rx_progress) = channel();
+        pool.install(|| {
+            parallel::trial_loop(&settings, 40, 15, &counters, &tx_results, &tx_progress).unwrap()
+        });
+        drop(tx_results);
+        drop(tx_progress);
+        assert_eq!(counters.num_trials(), 40);
+        assert_eq!(counters.num_failures(), 40);
+        let reports: Vec<_> = rx_results.iter().collect();
+        assert_eq!(reports.len(), 40);
+        assert_eq!(
+            reports
+                .iter()
+                .filter(|report| report.failure.is_some())
+                .count(),
+            expected_sent
+        );
+        let updates: Vec<_> = rx_progress.iter().collect();
+        assert_eq!(updates.len(), 3);
+        assert_eq!(updates.iter().map(|dfr| dfr.num_trials()).sum::<u64>(), 40);
+        assert_eq!(
+            updates.iter().map(|dfr| dfr.num_failures()).sum::<u64>(),
+            40
+        );
+    }
+}
+
+#[test]
+fn failure_weights_include_unrecorded_failures() {
+    let settings = SettingsBuilder::default()
+        .num_trials(40)
+        .trial_settings(guaranteed_failure_settings())
+        .record_max(5)
+        .save_frequency(NonZeroU64::new(15))
+        .output(OutputTo::Void)
+        .threads(4)
+        .build()
+        .unwrap();
+    let data = parallel::run_parallel(&settings).unwrap();
+    assert_eq!(data.num_failures(), 40);
+    assert_eq!(data.decoding_failures().len(), 5);
+    let weights = data.failure_weights();
+    for histogram in [weights.syndrome(), weights.e_out(), weights.diff()] {
+        assert_eq!(histogram.total(), 40);
+    }
+}
+
+#[test]
+fn deterministic_send_max_per_stream() {
+    let settings = SettingsBuilder::default()
+        .num_trials(40)
+        .trial_settings(guaranteed_failure_settings())
+        .record_max(5)
+        .deterministic_threads(true)
+        .build()
+        .unwrap();
+    let counters = parallel::TrialCounters::for_settings(&settings);
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(4)
+        .build()
+        .unwrap();
+    let (tx_results, rx_results) = channel();
+    let (tx_progress, _rx_progress) = channel();
+    pool.install(|| {
+        parallel::trial_loop_deterministic(
+            settings.trial_settings(),
+            settings.num_trials(),
+            15,
+            None,
+            random::Seed::from_entropy(),
+            &counters,
+            &tx_results,
+            &tx_progress,
+        )
+        .unwrap()
+    });
+    drop(tx_results);
+    let reports: Vec<_> = rx_results.iter().collect();
+    assert_eq!(reports.len(), 40);
+    for stream in 0..4 {
+        let sent = reports
+            .iter()
+            .filter(|report| report.position.unwrap().stream == stream)
+            .filter(|report| report.failure.is_some())
+            .count();
+        assert_eq!(sent, 5);
+    }
+}
+
+#[test]
+fn deadline_checked_within_batch() {
+    let settings = TrialSettings::default();
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(2)
+        .build()
+        .unwrap();
+    for deterministic in [false, true] {
+        let counters = parallel::TrialCounters::unlimited();
+        let (tx_results, _rx_results) = channel();
+        let (tx_progress, rx_progress) = channel();
+        let start_time = Instant::now();
+        let deadline = Some(start_time + Duration::from_millis(50));
+        // A single batch that would take far longer than the time budget to run in full
+        pool.install(|| {
+            if deterministic {
+                parallel::trial_loop_deterministic(
+                    &settings,
+                    1_000_000_000,
+                    1_000_000_000,
+                    deadline,
+                    random::Seed::from_entropy(),
+                    &counters,
+                    &tx_results,
+                    &tx_progress,
+                )
+            } else {
+                parallel::trial_loop_until(
+                    &settings,
+                    1_000_000_000,
+                    1_000_000_000,
+                    deadline,
+                    &counters,
+                    &tx_results,
+                    &tx_progress,
+                )
+            }
+            .unwrap()
+        });
+        assert!(start_time.elapsed() < Duration::from_secs(10));
+        drop(tx_progress);
+        let updates: Vec<_> = rx_progress.iter().collect();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].num_trials(), counters.num_trials());
+        assert!(counters.num_trials() > 0);
+    }
+}
@@ -55,3 +55,27 @@ fn guaranteed_decoding_failure() {
         assert!(!result.success());
     }
 }
+
+#[test]
+fn error_source_mix_fraction() {
+    const NCW_FRACTION: f64 = 0.1;
+    let settings = TrialSettings::new(KeyFilter::Any, None, Some(NearCodewordClass::C), None)
+        .unwrap()
+        .with_ncw_fraction(Some(NCW_FRACTION));
+    let mut rng = StdRng::seed_from_u64(4027195130368914027);
+    let ncw_count = (0..TRIALS)
+        .filter(|_| {
+            let (_, tagged_error_vector) = application::sample_key_vector(&settings, &mut rng);
+            matches!(
+                tagged_error_vector.source(),
+                ErrorVectorSource::NearCodeword(_)
+            )
+        })
+        .count();
+    // The standard deviation of the observed fraction is 0.003
+    let observed = ncw_count as f64 / TRIALS as f64;
+    assert!(
+        (observed - NCW_FRACTION).abs() < 0.015,
+        "observed fraction {observed}"
+    );
+}
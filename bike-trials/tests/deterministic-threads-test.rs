@@ -0,0 +1,79 @@
+use bike_decoder::random::Seed;
+use bike_trials::{
+    output::OutputTo,
+    parallel,
+    settings::{SettingsBuilder, TrialSettings},
+};
+use hex::FromHex;
+use std::num::NonZeroU64;
+
+#[test]
+fn deterministic_threads_reproducible() {
+    let seed =
+        Seed::from_hex("5f1c3a0e9d2b47a8c6e4f08b13d97a2ec5b8f6041d3e29a7b0c8f5e6a1d2c3b4").unwrap();
+    let settings = SettingsBuilder::default()
+        .num_trials(3000)
+        .trial_settings(TrialSettings::default().with_iterations(Some(1)))
+        .save_frequency(NonZeroU64::new(1000))
+        .output(OutputTo::Void)
+        .threads(3)
+        .deterministic_threads(true)
+        .seed(Some(seed))
+        .build()
+        .unwrap();
+    let data = parallel::run_parallel(&settings).unwrap();
+    let data2 = parallel::run_parallel(&settings).unwrap();
+    assert_eq!(data.thread_count(), Some(3));
+    assert_eq!(data.num_trials(), settings.num_trials());
+    assert!(!data.decoding_failures().is_empty());
+    assert_eq!(data.num_failures(), data2.num_failures());
+    assert!(data
+        .decoding_failures()
+        .iter()
+        .all(|df| df.thread.is_some_and(|thread| thread < 3)));
+    assert_eq!(data.decoding_failures(), data2.decoding_failures());
+    assert_eq!(
+        serde_json::to_string(data.decoding_failures()).unwrap(),
+        serde_json::to_string(data2.decoding_failures()).unwrap()
+    );
+}
+
+// Uses the same seed as deterministic_threads_reproducible, since the global seed can only be
+// set once per process
+#[test]
+fn deterministic_threads_record_max() {
+    let seed =
+        Seed::from_hex("5f1c3a0e9d2b47a8c6e4f08b13d97a2ec5b8f6041d3e29a7b0c8f5e6a1d2c3b4").unwrap();
+    let builder = |record_max, sample_failures| {
+        SettingsBuilder::default()
+            .num_trials(3000)
+            .trial_settings(TrialSettings::default().with_iterations(Some(1)))
+            .save_frequency(NonZeroU64::new(1000))
+            .record_max(record_max)
+            .sample_failures(sample_failures)
+            .output(OutputTo::Void)
+            .threads(3)
+            .deterministic_threads(true)
+            .seed(Some(seed))
+            .build()
+            .unwrap()
+    };
+    for sample_failures in [false, true] {
+        let all = parallel::run_parallel(&builder(10_000, sample_failures)).unwrap();
+        assert!(all.num_failures() > 5);
+        assert_eq!(all.decoding_failures().len() as u64, all.num_failures());
+        let settings = builder(5, sample_failures);
+        let data = parallel::run_parallel(&settings).unwrap();
+        assert_eq!(data.num_failures(), all.num_failures());
+        assert_eq!(data.failures_seen(), all.num_failures());
+        assert_eq!(data.decoding_failures().len(), 5);
+        assert!(data
+            .decoding_failures()
+            .iter()
+            .all(|df| all.decoding_failures().contains(df)));
+        for _ in 0..3 {
+            let data2 = parallel::run_parallel(&settings).unwrap();
+            assert_eq!(data.decoding_failures(), data2.decoding_failures());
+        }
+    }
+}
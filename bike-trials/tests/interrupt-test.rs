@@ -0,0 +1,46 @@
+#![cfg(unix)]
+use bike_trials::{
+    application,
+    output::OutputTo,
+    parallel,
+    record::DataRecord,
+    settings::{SettingsBuilder, TrialSettings},
+};
+use std::{fs, num::NonZeroU64, process::Command, thread, time::Duration};
+
+// Runs in its own test binary, since interrupting sets a flag that stops all trials
+// for the rest of the process
+#[test]
+fn interrupt_flushes_results() {
+    let path = std::env::temp_dir().join(format!(
+        "bike-trials-interrupt-{}.json",
+        uuid::Uuid::new_v4()
+    ));
+    let settings = SettingsBuilder::default()
+        .num_trials(u64::MAX)
+        .trial_settings(TrialSettings::default().with_iterations(Some(1)))
+        .save_frequency(NonZeroU64::new(100))
+        .flush_every(u64::MAX)
+        .threads(2)
+        .output(OutputTo::File(path.clone()))
+        .overwrite(true)
+        .build()
+        .unwrap();
+    application::stop_on_interrupt().unwrap();
+    let signaller = thread::spawn(|| {
+        thread::sleep(Duration::from_millis(200));
+        Command::new("kill")
+            .args(["-TERM", &std::process::id().to_string()])
+            .status()
+            .unwrap()
+    });
+    let data = parallel::run_parallel(&settings);
+    let saved = DataRecord::load(&path);
+    fs::remove_file(&path).unwrap();
+    assert!(signaller.join().unwrap().success());
+    assert!(application::interrupted());
+    let (data, saved) = (data.unwrap(), saved.unwrap());
+    assert!(data.num_trials() > 0);
+    assert_eq!(saved.num_trials(), data.num_trials());
+    assert_eq!(saved.num_failures(), data.num_failures());
+}
@@ -7,7 +7,7 @@ use bike_decoder::{
 use bike_trials::{
     output::OutputTo,
     parallel,
-    settings::{Settings, SettingsBuilder},
+    settings::{Settings, SettingsBuilder, TrialSettings},
 };
 use hex::FromHex;
 
@@ -59,3 +59,22 @@ fn main_multithreaded_test() {
     );
     assert_eq!(*e_supp.source(), ErrorVectorSource::Random);
 }
+
+#[test]
+fn sort_decoding_failures_is_stable() {
+    // Tests in this file must use the same seed, since the global seed can only be set once
+    let seed = multithreaded_example_settings().seed();
+    let settings = SettingsBuilder::default()
+        .num_trials(2000)
+        .trial_settings(TrialSettings::default().with_iterations(Some(1)))
+        .output(OutputTo::Void)
+        .threads(3)
+        .seed(seed)
+        .build()
+        .unwrap();
+    let data = parallel::run_parallel(&settings).unwrap();
+    assert!(data.decoding_failures().len() > 1);
+    let mut sorted = data.clone();
+    sorted.sort_decoding_failures();
+    assert_eq!(sorted.decoding_failures(), data.decoding_failures());
+}